@@ -1,42 +1,197 @@
 use eframe::egui;
-use std::{fs, path::PathBuf};
+use std::{
+    collections::VecDeque,
+    env, fs,
+    path::{Path, PathBuf},
+};
 
 use crate::{
-    home_screen::HomeScreen,
-    image_picker_popup::ImagePickerPopup,
-    image_store::ImageStore,
+    backups,
+    bulk_fetch::{self, BulkFetch, BulkFetchEvent, BulkFetchJob},
+    bundle::{self, OpenBundle},
+    connectivity::{Connection, ConnectivityMonitor},
+    file_watch::{FileWatcher, POLL_INTERVAL},
+    home_screen::{HomeScreen, HomeView},
+    image_input::{self, ImageInput},
+    image_maintenance::{self, ImageReport, MaintenancePlan},
+    image_maintenance_popup::{ImageHashingPopup, ImageMaintenancePopup},
+    image_picker_popup::{self, ImagePickerPopup},
+    image_provenance::{ImageProvenance, PLACEHOLDER_PROVIDER},
+    image_provider::ImageProviderConfig,
+    image_provider_popup::ImageProviderPopup,
+    image_store::{ImageGeometry, ImageStore},
     main_screen::ScreenState,
+    merge,
+    metadata_popup::{MetadataChoice, MetadataPopup},
     model::Model,
-    popup::{self, ConfirmDeleteCategoryPopup, ConfirmDuplicateSwitchPopup, Popup, PopupResponse},
+    popup::{
+        self, BulkFetchReviewPopup, ConfirmDeleteCategoryPopup, ConfirmDeleteEntriesPopup,
+        ConfirmDuplicateSwitchPopup, ConfirmMovePopup, ExternalChangePopup, FetchPlaceholdersPopup,
+        MessagePopup, Popup, PopupResponse, RestoreBackupPopup, UnrecognizedContentPopup,
+    },
     ranking_screen::{RankingOutcome, RankingScreen, RankingSource},
+    search_cache::SearchCache,
+    settings::{AppSettings, Theme},
     splash_screen::SplashScreen,
-    spreadsheet,
+    spreadsheet, title_card,
+    undo::{ImageUndo, UndoStack, UndoStep},
 };
 
+// Drag moves further than this many places can ask for a comparison first.
+const CONFIRM_MOVE_DISTANCE: usize = 3;
+
 pub struct MediaRatingApp {
     document: Option<DocumentContext>,
     screen: ScreenState,
     popup: Option<Box<dyn Popup>>,
+    settings: AppSettings,
+    // Started with the first frame, which brings the context it repaints.
+    connectivity: Option<ConnectivityMonitor>,
+    // Set when the document or its image providers change, so the hosts the
+    // monitor tries are worked out again.
+    probe_addresses_outdated: bool,
+    // The theme the context was last given, so visuals are set only on change.
+    applied_theme: Option<Theme>,
+    // Whether title cards may be waiting for a connection to fetch images.
+    refetch_placeholders: bool,
 }
 
 struct DocumentContext {
     spreadsheet_path: PathBuf,
+    bundle: Option<OpenBundle>,
     model: Model,
     images: ImageStore,
+    backup_count: usize,
+    // The model as last read from or written to disk, the common ancestor when
+    // merging edits made by another program.
+    saved_model: Model,
+    watcher: FileWatcher,
+    bulk_fetch: Option<BulkFetch>,
+    undo: UndoStack,
+    // Entries moved in bulk that are still to be ranked, in order.
+    rerank_queue: VecDeque<(String, String)>,
+    // Whether the ranking under way came from the queue, and so belongs to
+    // the undo step of the move.
+    ranking_queued: bool,
+    // Whether the running bulk fetch is the change of an undo step, so the
+    // images it sets leave that step undoable.
+    fetch_in_undo_step: bool,
 }
 
 impl DocumentContext {
-    fn save(&self) {
-        if let Err(e) = spreadsheet::save(&self.spreadsheet_path, &self.model) {
-            eprintln!("Could not save to spreadsheet: {e}");
+    fn save(&mut self) -> Result<(), String> {
+        self.undo.forget_if_changed(&self.model);
+        self.backup_if_due()
+            .map_err(|e| format!("Could not back up spreadsheet: {e}"))?;
+        spreadsheet::save(&self.spreadsheet_path, &self.model)
+            .map_err(|e| format!("Could not save to spreadsheet: {e}"))?;
+        self.pack_bundle()?;
+
+        self.saved_model = self.model.clone();
+        self.watcher.refresh();
+        Ok(())
+    }
+
+    // Bundles carry their images, so repack after images change on disk. A
+    // bundle changed by another program is left for the next save to merge.
+    fn repack_if_bundle(&mut self) -> Result<(), String> {
+        if self.bundle.is_none() || self.watcher.has_changed() {
+            return Ok(());
+        }
+        self.pack_bundle()?;
+        self.watcher.refresh();
+        Ok(())
+    }
+
+    fn pack_bundle(&mut self) -> Result<(), String> {
+        // The manifest is packed too, so it has to be up to date.
+        self.images.save_provenance();
+        let images = self.image_files();
+        match &mut self.bundle {
+            Some(bundle) => bundle
+                .save(&images)
+                .map_err(|e| format!("Could not save bundle: {e}")),
+            None => Ok(()),
+        }
+    }
+
+    // Back up the version on disk before it is replaced. For a bundle only the
+    // data is kept, as the images are not versioned.
+    fn backup_if_due(&self) -> std::io::Result<()> {
+        match &self.bundle {
+            Some(bundle) => backups::backup_if_due(
+                &bundle::data_backup_path(&bundle.path),
+                || {
+                    if !bundle.path.exists() {
+                        return Ok(None);
+                    }
+                    bundle::read_data(&bundle.path)
+                        .map(Some)
+                        .map_err(std::io::Error::other)
+                },
+                self.backup_count,
+            ),
+            None => backups::backup_if_due(
+                &self.spreadsheet_path,
+                || {
+                    if !self.spreadsheet_path.exists() {
+                        return Ok(None);
+                    }
+                    fs::read(&self.spreadsheet_path).map(Some)
+                },
+                self.backup_count,
+            ),
         }
     }
+
+    // The file the user opened: the bundle, or the loose spreadsheet.
+    fn file_path(&self) -> &Path {
+        self.bundle
+            .as_ref()
+            .map_or(&self.spreadsheet_path, |bundle| &bundle.path)
+    }
+
+    fn load_from_disk(&mut self) -> Result<Model, String> {
+        if let Some(bundle) = &mut self.bundle {
+            bundle.reload_data()?;
+        }
+        spreadsheet::load(&self.spreadsheet_path)
+    }
+
+    fn image_files(&self) -> Vec<PathBuf> {
+        self.model
+            .categories()
+            .flat_map(|(category, entries)| {
+                entries
+                    .iter()
+                    .filter_map(move |entry| self.images.existing_image_file(category, entry))
+            })
+            .chain(self.images.provenance_file())
+            .collect()
+    }
 }
 
 pub enum AppAction {
     OpenSpreadsheet(PathBuf),
     CreateSpreadsheet(PathBuf),
+    BrowseBackups(PathBuf),
+    ImportBundle {
+        bundle: PathBuf,
+        directory: PathBuf,
+    },
+    ExportBundle(PathBuf),
+    RestoreBackup {
+        spreadsheet: PathBuf,
+        backup: PathBuf,
+    },
     ReturnToSplash,
+    ForgetRecentDocument(PathBuf),
+    SaveSettings,
+    SetWorkOffline(bool),
+    SetHomeView(HomeView),
+    ReloadDocument,
+    OverwriteDocument,
+    MergeDocument,
     RequestDeleteCategory {
         category: String,
     },
@@ -69,6 +224,43 @@ pub enum AppAction {
         category: String,
         index: usize,
     },
+    // A drag and drop in the home screen, which may ask for a comparison.
+    RequestMoveEntry {
+        category: String,
+        from_index: usize,
+        to_index: usize,
+    },
+    MoveEntry {
+        category: String,
+        from_index: usize,
+        to_index: usize,
+    },
+    // Bulk actions on the entries selected in the home screen.
+    RequestDeleteEntries {
+        category: String,
+        entries: Vec<String>,
+    },
+    DeleteEntries {
+        category: String,
+        entries: Vec<String>,
+    },
+    MoveEntries {
+        from_category: String,
+        entries: Vec<String>,
+        to_category: String,
+        // Rank each moved entry in turn rather than leaving them at the end.
+        rank: bool,
+    },
+    RefreshImages {
+        category: String,
+        entries: Vec<String>,
+    },
+    ExportEntries {
+        category: String,
+        entries: Vec<String>,
+        path: PathBuf,
+    },
+    Undo,
     DeleteEntryAndStartRerank {
         delete_category: String,
         delete_index: usize,
@@ -79,234 +271,1477 @@ pub enum AppAction {
         category: String,
         entry: String,
     },
+    PasteEntryImage {
+        category: String,
+        entry: String,
+    },
+    StartBulkFetch,
+    FetchPlaceholders,
+    CancelBulkFetch,
+    ReviewBulkFetch,
+    CheckImages,
+    FinishImageCheck {
+        report: Box<ImageReport>,
+        hashes: Vec<(String, String, u64)>,
+    },
+    ApplyImageMaintenance(MaintenancePlan),
+    EditImageProvider {
+        category: String,
+    },
+    SetImageProvider {
+        category: String,
+        provider: ImageProviderConfig,
+        geometry: ImageGeometry,
+    },
+    LookUpMetadata {
+        category: String,
+        entry: String,
+    },
+    SetEntryMetadata {
+        category: String,
+        entry: String,
+        choice: Box<MetadataChoice>,
+    },
     SetEntryImage {
         category: String,
         entry: String,
         image: image::DynamicImage,
+        provenance: Box<ImageProvenance>,
         purpose: ImagePickPurpose,
     },
     RankingFinished(RankingOutcome),
     CancelRanking,
 }
 
-#[derive(Clone)]
-pub enum ImagePickPurpose {
-    RefreshOnly,
-    AddEntry,
-    SwitchCategory {
-        from_category: String,
-        from_index: usize,
-    },
-}
+#[derive(Clone)]
+pub enum ImagePickPurpose {
+    RefreshOnly,
+    AddEntry,
+    SwitchCategory {
+        from_category: String,
+        from_index: usize,
+    },
+}
+
+impl MediaRatingApp {
+    pub fn new(settings: AppSettings) -> Self {
+        let mut app = Self {
+            document: None,
+            screen: ScreenState::Splash(SplashScreen),
+            popup: None,
+            settings,
+            connectivity: None,
+            probe_addresses_outdated: true,
+            applied_theme: None,
+            refetch_placeholders: true,
+        };
+
+        if app.settings.reopen_last_document {
+            if let Some(path) = app.settings.recent_documents.first().cloned() {
+                app.open_document(path, false);
+            }
+        }
+
+        app
+    }
+}
+
+impl eframe::App for MediaRatingApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let mut actions = Vec::new();
+
+        let probe_addresses =
+            std::mem::take(&mut self.probe_addresses_outdated).then(|| self.probe_addresses());
+        let connectivity = self
+            .connectivity
+            .get_or_insert_with(|| ConnectivityMonitor::start(ctx));
+        connectivity.set_paused(self.settings.work_offline);
+        if let Some(addresses) = probe_addresses {
+            connectivity.set_probe_addresses(addresses);
+        }
+        let connection = self.connection();
+        if self.applied_theme != Some(self.settings.theme) {
+            ctx.set_visuals(self.settings.theme.visuals());
+            self.applied_theme = Some(self.settings.theme);
+        }
+
+        match (&mut self.screen, self.document.as_mut()) {
+            (ScreenState::Splash(screen), _) => {
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    actions.extend(screen.ui(ui, &mut self.settings));
+                });
+            }
+            (ScreenState::Home(screen), Some(document)) => {
+                egui::TopBottomPanel::top("Menu").show(ctx, |ui| {
+                    actions.extend(screen.menu_bar(
+                        ui,
+                        &document.model,
+                        document.bulk_fetch.as_ref(),
+                        connection,
+                        document.undo.next_description(),
+                    ));
+                });
+
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    actions.extend(screen.ui(ctx, ui, &document.model, &mut document.images));
+                });
+            }
+            (ScreenState::Ranking { ranking, .. }, Some(document)) => {
+                egui::TopBottomPanel::top("Menu").show(ctx, |ui| {
+                    actions.extend(ranking.menu_bar(ui));
+                });
+
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    actions.extend(ranking.ui(ctx, ui, &mut document.images));
+                });
+            }
+            (_, None) => actions.push(AppAction::ReturnToSplash),
+        }
+
+        self.remember_selected_category();
+        self.poll_bulk_fetch(ctx);
+        self.fetch_placeholders_when_online();
+
+        if let Some(document) = self.document.as_mut() {
+            if self.popup.is_none() && document.watcher.poll() {
+                self.popup = Some(Box::new(ExternalChangePopup::new(
+                    document.spreadsheet_path.clone(),
+                )));
+            }
+            ctx.request_repaint_after(POLL_INTERVAL);
+        }
+
+        if let Some(active_popup) = self.popup.as_mut() {
+            match popup::show_modal(ctx, active_popup.as_mut()) {
+                PopupResponse::KeepOpen => {}
+                PopupResponse::Close => self.popup = None,
+                PopupResponse::Action(action) => {
+                    self.popup = None;
+                    actions.push(action);
+                }
+            }
+        }
+        // After the popup, so a picker opened by this does not see the
+        // same drop or paste again.
+        self.accept_entry_image_input(ctx);
+
+        for action in actions {
+            self.handle_action(action, ctx);
+        }
+        // Once per frame, however many images the actions changed.
+        if let Some(document) = self.document.as_mut() {
+            document.images.save_provenance();
+        }
+    }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.settings.save();
+    }
+}
+
+impl MediaRatingApp {
+    fn handle_action(&mut self, action: AppAction, ctx: &egui::Context) {
+        match action {
+            AppAction::OpenSpreadsheet(path) => self.open_document(path, false),
+            AppAction::CreateSpreadsheet(path) => self.open_document(path, true),
+            AppAction::BrowseBackups(path) => self.browse_backups(path),
+            AppAction::ImportBundle { bundle, directory } => self.import_bundle(bundle, directory),
+            AppAction::ExportBundle(path) => self.export_bundle(path),
+            AppAction::RestoreBackup {
+                spreadsheet,
+                backup,
+            } => self.restore_backup(spreadsheet, backup),
+            AppAction::ReturnToSplash => self.return_to_splash(),
+            AppAction::ForgetRecentDocument(path) => {
+                self.settings.remove_recent_document(&path);
+                self.settings.save();
+            }
+            AppAction::SaveSettings => self.settings.save(),
+            AppAction::SetWorkOffline(offline) => {
+                self.settings.work_offline = offline;
+                self.settings.save();
+            }
+            AppAction::SetHomeView(view) => {
+                self.settings.home_view = view;
+                self.settings.save();
+            }
+            AppAction::ReloadDocument => self.reload_document(),
+            AppAction::OverwriteDocument => self.overwrite_document(),
+            AppAction::MergeDocument => self.merge_document(),
+            AppAction::RequestDeleteCategory { category } => {
+                self.popup = Some(Box::new(ConfirmDeleteCategoryPopup::new(category)));
+            }
+            AppAction::DeleteCategory { category } => self.delete_category(category),
+            AppAction::CreateCategory { name } => self.create_category(name),
+            AppAction::StartAddEntry { category, entry } => {
+                self.start_add_entry(category, entry, ctx);
+            }
+            AppAction::StartRerankEntry { category, index } => {
+                self.start_rerank_entry(category, index);
+            }
+            AppAction::StartSwitchCategory {
+                from_category,
+                from_index,
+                to_category,
+                entry,
+            } => self.start_switch_category(from_category, from_index, to_category, entry),
+            AppAction::RenameEntry {
+                category,
+                index,
+                new_name,
+            } => self.rename_entry(category, index, new_name),
+            AppAction::DeleteEntry { category, index } => self.delete_entry(category, index),
+            AppAction::RequestMoveEntry {
+                category,
+                from_index,
+                to_index,
+            } => self.request_move_entry(category, from_index, to_index, ctx),
+            AppAction::MoveEntry {
+                category,
+                from_index,
+                to_index,
+            } => self.move_entry(category, from_index, to_index),
+            AppAction::RequestDeleteEntries { category, entries } => {
+                self.popup = Some(Box::new(ConfirmDeleteEntriesPopup::new(category, entries)));
+            }
+            AppAction::DeleteEntries { category, entries } => {
+                self.delete_entries(category, entries);
+            }
+            AppAction::MoveEntries {
+                from_category,
+                entries,
+                to_category,
+                rank,
+            } => self.move_entries(from_category, entries, to_category, rank),
+            AppAction::RefreshImages { category, entries } => {
+                self.refresh_images(category, entries, ctx);
+            }
+            AppAction::ExportEntries {
+                category,
+                entries,
+                path,
+            } => self.export_entries(category, entries, path),
+            AppAction::Undo => self.undo(),
+            AppAction::DeleteEntryAndStartRerank {
+                delete_category,
+                delete_index,
+                rerank_category,
+                rerank_index,
+            } => self.delete_entry_and_start_rerank(
+                delete_category,
+                delete_index,
+                rerank_category,
+                rerank_index,
+            ),
+            AppAction::RefreshImage { category, entry } => {
+                self.open_image_picker(category, entry, ImagePickPurpose::RefreshOnly);
+            }
+            AppAction::SetEntryImage {
+                category,
+                entry,
+                image,
+                provenance,
+                purpose,
+            } => {
+                self.set_entry_image(category, entry, image, *provenance, purpose, ctx);
+            }
+            AppAction::PasteEntryImage { category, entry } => {
+                self.paste_entry_image(category, entry, ctx);
+            }
+            AppAction::StartBulkFetch => self.start_bulk_fetch(ctx),
+            AppAction::FetchPlaceholders => self.fetch_placeholders(ctx),
+            AppAction::CancelBulkFetch => self.cancel_bulk_fetch(),
+            AppAction::ReviewBulkFetch => self.review_bulk_fetch(),
+            AppAction::CheckImages => self.check_images(ctx),
+            AppAction::FinishImageCheck { report, hashes } => {
+                self.finish_image_check(*report, hashes);
+            }
+            AppAction::ApplyImageMaintenance(plan) => self.apply_image_maintenance(plan),
+            AppAction::EditImageProvider { category } => self.edit_image_provider(category),
+            AppAction::SetImageProvider {
+                category,
+                provider,
+                geometry,
+            } => self.set_image_provider(category, provider, geometry),
+            AppAction::LookUpMetadata { category, entry } => self.look_up_metadata(category, entry),
+            AppAction::SetEntryMetadata {
+                category,
+                entry,
+                choice,
+            } => self.set_entry_metadata(category, entry, *choice, ctx),
+            AppAction::RankingFinished(outcome) => self.finish_ranking(outcome, ctx),
+            AppAction::CancelRanking => self.cancel_ranking(),
+        }
+    }
+
+    fn open_document(&mut self, path: PathBuf, create_new: bool) {
+        let bundle = if bundle::is_bundle(&path) {
+            let opened = if create_new {
+                OpenBundle::create(path.clone(), spreadsheet::create_empty)
+            } else {
+                OpenBundle::open(path.clone())
+            };
+            match opened {
+                Ok(bundle) => Some(bundle),
+                Err(e) => {
+                    self.show_error("Could Not Open Bundle", e);
+                    return;
+                }
+            }
+        } else {
+            if create_new {
+                if let Err(e) = spreadsheet::create_empty(&path) {
+                    self.show_error("Could Not Create Spreadsheet", e);
+                    return;
+                }
+            }
+            None
+        };
+
+        let spreadsheet_path = bundle
+            .as_ref()
+            .map_or_else(|| path.clone(), OpenBundle::spreadsheet_path);
+        let Some(parent) = spreadsheet_path.parent() else {
+            eprintln!("Spreadsheet path has no parent directory");
+            return;
+        };
+        let document_directory = parent.to_path_buf();
+        let image_directory = document_directory.join("images");
+        if let Err(e) = fs::create_dir_all(&image_directory) {
+            eprintln!("Could not create image directory: {e}");
+            return;
+        }
+
+        let mut model = match spreadsheet::load(&spreadsheet_path) {
+            Ok(model) => model,
+            Err(e) => {
+                self.show_error("Could Not Open Spreadsheet", e);
+                return;
+            }
+        };
+
+        let migration_errors = image_maintenance::migrate_file_names(&image_directory, &model);
+
+        let findings = match spreadsheet::unrecognized_content(&spreadsheet_path) {
+            Ok(findings) => findings,
+            Err(e) => vec![format!("The workbook could not be fully inspected ({e}).")],
+        };
+
+        let categories = model.get_categories();
+        let selected_category = self
+            .settings
+            .last_category(&path)
+            .filter(|category| categories.iter().any(|existing| existing == category))
+            .map(str::to_string)
+            .or_else(|| categories.first().cloned());
+        let mut images = ImageStore::new(
+            document_directory,
+            self.settings.texture_budget_mb * 1024 * 1024,
+        );
+        // Geometries chosen before they were kept in the document move there
+        // with the next save.
+        for (category, geometry) in self.settings.image_geometries(&path) {
+            if model.image_geometry(&category).is_none() {
+                model.set_image_geometry(&category, geometry);
+            }
+        }
+        images.set_geometries(&model);
+        self.refetch_placeholders = true;
+        self.probe_addresses_outdated = true;
+        self.document = Some(DocumentContext {
+            watcher: FileWatcher::new(path.clone()),
+            spreadsheet_path,
+            bundle,
+            saved_model: model.clone(),
+            model,
+            images,
+            backup_count: self.settings.backup_count,
+            bulk_fetch: None,
+            undo: UndoStack::default(),
+            rerank_queue: VecDeque::new(),
+            ranking_queued: false,
+            fetch_in_undo_step: false,
+        });
+        self.screen =
+            ScreenState::Home(HomeScreen::new(selected_category, self.settings.home_view));
+        self.settings.add_recent_document(&path);
+        self.settings.save();
+
+        // Renames that failed are tried again on the next open, so the
+        // workbook findings come first.
+        if !findings.is_empty() {
+            self.popup = Some(Box::new(UnrecognizedContentPopup::new(findings)));
+        } else if !migration_errors.is_empty() {
+            self.show_error(
+                "Some Images Were Not Renamed",
+                format!(
+                    "{}\n\nUse Check Images to reattach them.",
+                    migration_errors.join("\n")
+                ),
+            );
+        }
+    }
+
+    fn browse_backups(&mut self, path: PathBuf) {
+        let backups = if bundle::is_bundle(&path) {
+            backups::list_backups(&bundle::data_backup_path(&path))
+        } else {
+            backups::list_backups(&path)
+        };
+        if backups.is_empty() {
+            self.show_error(
+                "No Backups Found",
+                format!("{} has no backups.", path.display()),
+            );
+            return;
+        }
+
+        self.popup = Some(Box::new(RestoreBackupPopup::new(path, backups)));
+    }
+
+    fn restore_backup(&mut self, spreadsheet: PathBuf, backup: PathBuf) {
+        let result = if bundle::is_bundle(&spreadsheet) {
+            self.restore_bundle_backup(&spreadsheet, &backup)
+        } else {
+            backups::restore_backup(&backup, &spreadsheet, self.settings.backup_count)
+                .map_err(|e| e.to_string())
+        };
+        if let Err(e) = result {
+            self.show_error("Could Not Restore Backup", e);
+            return;
+        }
+
+        self.open_document(spreadsheet, false);
+    }
+
+    // Bundle backups hold only the data, so put it back into the bundle and
+    // keep the images it has now.
+    fn restore_bundle_backup(&self, bundle_path: &Path, backup: &Path) -> Result<(), String> {
+        let contents = fs::read(backup).map_err(|e| e.to_string())?;
+        let current = bundle::read_data(bundle_path)?;
+        backups::keep_replaced(
+            &bundle::data_backup_path(bundle_path),
+            &current,
+            self.settings.backup_count,
+        )
+        .map_err(|e| e.to_string())?;
+        bundle::replace_data(bundle_path, &contents)
+    }
+
+    fn import_bundle(&mut self, bundle_path: PathBuf, directory: PathBuf) {
+        let stem = bundle_path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "Media Ratings".to_string());
+
+        match bundle::unpack(&bundle_path, &directory, &format!("{stem}.xlsx")) {
+            Ok(spreadsheet_path) => self.open_document(spreadsheet_path, false),
+            Err(e) => self.show_error("Could Not Import Bundle", e),
+        }
+    }
+
+    fn export_bundle(&mut self, path: PathBuf) {
+        let Some(document) = self.document.as_ref() else {
+            return;
+        };
+
+        if let Err(e) = bundle::pack(&document.spreadsheet_path, &document.image_files(), &path) {
+            self.show_error("Could Not Export Bundle", e);
+        }
+    }
+
+    fn save_document(&mut self) {
+        let Some(document) = self.document.as_mut() else {
+            return;
+        };
+
+        if document.watcher.has_changed() {
+            self.popup = Some(Box::new(ExternalChangePopup::new(
+                document.spreadsheet_path.clone(),
+            )));
+            return;
+        }
+
+        if let Err(e) = document.save() {
+            self.show_error("Save Failed", e);
+        }
+    }
+
+    fn reload_document(&mut self) {
+        self.cancel_ranking();
+        let Some(document) = self.document.as_mut() else {
+            return;
+        };
+
+        match document.load_from_disk() {
+            Ok(model) => {
+                document.saved_model = model.clone();
+                document.model = model;
+                document.images.set_geometries(&document.model);
+                document.watcher.refresh();
+                self.probe_addresses_outdated = true;
+            }
+            Err(e) => self.show_error("Could Not Reload Spreadsheet", e),
+        }
+
+        self.forget_missing_categories();
+    }
+
+    fn overwrite_document(&mut self) {
+        let Some(document) = self.document.as_mut() else {
+            return;
+        };
+
+        if let Err(e) = document.save() {
+            self.show_error("Save Failed", e);
+        }
+    }
+
+    fn merge_document(&mut self) {
+        self.cancel_ranking();
+        let Some(document) = self.document.as_mut() else {
+            return;
+        };
+
+        let theirs = match document.load_from_disk() {
+            Ok(model) => model,
+            Err(e) => {
+                self.show_error("Could Not Reload Spreadsheet", e);
+                return;
+            }
+        };
+
+        document.model = merge::merge_models(&document.saved_model, &document.model, &theirs);
+        document.images.set_geometries(&document.model);
+        document.saved_model = theirs;
+        document.watcher.refresh();
+        self.probe_addresses_outdated = true;
+
+        self.forget_missing_categories();
+        self.save_document();
+    }
+
+    fn forget_missing_categories(&mut self) {
+        let Some(document) = self.document.as_ref() else {
+            return;
+        };
+
+        let categories = document.model.get_categories();
+        if let Some(home) = self.home_screen_mut() {
+            home.retain_categories(&categories);
+        }
+    }
+
+    fn show_error(&mut self, title: &str, message: String) {
+        self.popup = Some(Box::new(MessagePopup::new(title.to_string(), message)));
+    }
+
+    fn remember_selected_category(&mut self) {
+        let Some(document) = self.document.as_ref() else {
+            return;
+        };
+        let ScreenState::Home(home) = &self.screen else {
+            return;
+        };
+
+        if self
+            .settings
+            .set_last_category(document.file_path(), home.selected_category())
+        {
+            self.settings.save();
+        }
+    }
+
+    fn return_to_splash(&mut self) {
+        self.document = None;
+        self.popup = None;
+        self.screen = ScreenState::Splash(SplashScreen);
+        self.probe_addresses_outdated = true;
+    }
+
+    fn create_category(&mut self, name: String) {
+        if let Some(document) = self.document.as_mut() {
+            document.model.create_category(name);
+            self.probe_addresses_outdated = true;
+            self.save_document();
+        }
+    }
+
+    fn delete_category(&mut self, category: String) {
+        if let Some(document) = self.document.as_mut() {
+            for entry in document.model.get_category_entries(&category) {
+                document.images.delete_image(&category, entry);
+            }
+            document.model.delete_category(&category);
+            self.probe_addresses_outdated = true;
+            self.save_document();
+        }
+
+        if let Some(home) = self.home_screen_mut() {
+            home.category_deleted(&category);
+        }
+    }
+
+    fn rename_entry(&mut self, category: String, index: usize, new_name: String) {
+        if let Some(document) = self.document.as_mut() {
+            if let Some(old_name) = document
+                .model
+                .rename_entry(&category, index, new_name.clone())
+            {
+                let renamed = document
+                    .images
+                    .rename_image(&category, &old_name, &new_name);
+                self.save_document();
+                if let Err(e) = renamed {
+                    self.show_error(
+                        "Could Not Rename Image",
+                        format!(
+                            "The image for {old_name} could not be renamed: {e}\n\nUse Check Images to reattach it."
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    fn delete_entry(&mut self, category: String, index: usize) {
+        if let Some(document) = self.document.as_mut() {
+            if let Some(entry) = document.model.delete_entry(&category, index) {
+                document.images.delete_image(&category, &entry);
+                self.save_document();
+            }
+        }
+    }
+
+    fn request_move_entry(
+        &mut self,
+        category: String,
+        from_index: usize,
+        to_index: usize,
+        ctx: &egui::Context,
+    ) {
+        let confirm = self.settings.confirm_long_moves
+            && from_index.abs_diff(to_index) > CONFIRM_MOVE_DISTANCE;
+        let Some(document) = self.document.as_mut().filter(|_| confirm) else {
+            self.move_entry(category, from_index, to_index);
+            return;
+        };
+
+        // The entry now at the drop position is the last one passed.
+        let model = &document.model;
+        let (Some(moved), Some(passed)) = (
+            model.get_entry(&category, from_index).map(str::to_string),
+            model.get_entry(&category, to_index).map(str::to_string),
+        ) else {
+            return;
+        };
+        let moved_thumbnail = document.images.get_entry_thumbnail(&moved, &category, ctx);
+        let passed_thumbnail = document.images.get_entry_thumbnail(&passed, &category, ctx);
+        self.popup = Some(Box::new(ConfirmMovePopup::new(
+            category,
+            from_index,
+            to_index,
+            (moved, moved_thumbnail),
+            (passed, passed_thumbnail),
+        )));
+    }
+
+    fn move_entry(&mut self, category: String, from_index: usize, to_index: usize) {
+        let Some(document) = self.document.as_mut() else {
+            return;
+        };
+        if from_index == to_index
+            || document.model.get_entry(&category, from_index).is_none()
+            || document.model.get_entry(&category, to_index).is_none()
+        {
+            return;
+        }
+
+        document.model.move_entry(&category, from_index, to_index);
+        if let Some(home) = self.home_screen_mut() {
+            home.entry_moved(&category, from_index, to_index);
+        }
+        self.save_document();
+    }
+
+    fn delete_entries(&mut self, category: String, entries: Vec<String>) {
+        let Some(document) = self.document.as_mut() else {
+            return;
+        };
+
+        let before = document.model.clone();
+        let mut images = Vec::new();
+        let mut deleted = 0;
+        for entry in &entries {
+            let Some(index) = document
+                .model
+                .get_category_entries(&category)
+                .iter()
+                .position(|existing| existing == entry)
+            else {
+                continue;
+            };
+            images.extend(
+                document
+                    .images
+                    .take_image(&category, entry)
+                    .map(ImageUndo::Restore),
+            );
+            document.model.delete_entry(&category, index);
+            deleted += 1;
+        }
+        if deleted == 0 {
+            return;
+        }
+
+        document.undo.push(UndoStep {
+            description: format!("Delete {deleted} Entries"),
+            before,
+            after: document.model.clone(),
+            images,
+        });
+        if let Some(home) = self.home_screen_mut() {
+            home.entries_changed();
+        }
+        self.save_document();
+    }
+
+    // Moved entries go to the end of the other category, keeping their order,
+    // and can then be ranked there one after another.
+    fn move_entries(
+        &mut self,
+        from_category: String,
+        entries: Vec<String>,
+        to_category: String,
+        rank: bool,
+    ) {
+        let Some(document) = self.document.as_mut() else {
+            return;
+        };
+        if from_category == to_category {
+            return;
+        }
+
+        let before = document.model.clone();
+        let mut images = Vec::new();
+        let mut moved = Vec::new();
+        let mut skipped = Vec::new();
+        let mut failures = Vec::new();
+        for entry in entries {
+            if document.model.contains_entry(&to_category, &entry) {
+                skipped.push(entry);
+                continue;
+            }
+            let Some(index) = document
+                .model
+                .get_category_entries(&from_category)
+                .iter()
+                .position(|existing| *existing == entry)
+            else {
+                continue;
+            };
+
+            let metadata = document.model.take_entry_metadata(&from_category, &entry);
+            document.model.delete_entry(&from_category, index);
+            let end = document.model.get_category_entries(&to_category).len();
+            document
+                .model
+                .insert_entry_at(&to_category, entry.clone(), end);
+            document
+                .model
+                .set_entry_metadata(&to_category, entry.clone(), metadata);
+
+            if let Err(e) = document
+                .images
+                .move_image(&from_category, &to_category, &entry)
+            {
+                failures.push(format!("{entry}: {e}"));
+            }
+            images.push(ImageUndo::Moved {
+                from_category: from_category.clone(),
+                to_category: to_category.clone(),
+                title: entry.clone(),
+            });
+            moved.push(entry);
+        }
+
+        if !moved.is_empty() {
+            document.undo.push(UndoStep {
+                description: format!("Move {} Entries To {}", moved.len(), to_category),
+                before,
+                after: document.model.clone(),
+                images,
+            });
+            if rank {
+                document.rerank_queue = moved
+                    .into_iter()
+                    .map(|entry| (to_category.clone(), entry))
+                    .collect();
+            }
+            if let Some(home) = self.home_screen_mut() {
+                home.entries_changed();
+            }
+            self.save_document();
+            self.start_next_queued_rerank();
+        }
+
+        if !skipped.is_empty() {
+            self.show_error(
+                "Some Entries Were Not Moved",
+                format!(
+                    "These are already in {to_category}:\n{}",
+                    skipped.join("\n")
+                ),
+            );
+        } else if !failures.is_empty() {
+            self.show_error(
+                "Some Images Were Not Moved",
+                format!(
+                    "{}\n\nUse Check Images to reattach them.",
+                    failures.join("\n")
+                ),
+            );
+        }
+    }
+
+    fn start_next_queued_rerank(&mut self) {
+        if !matches!(self.screen, ScreenState::Home(_)) {
+            return;
+        }
+        let Some(document) = self.document.as_mut() else {
+            return;
+        };
+
+        while let Some((category, entry)) = document.rerank_queue.pop_front() {
+            let index = document
+                .model
+                .get_category_entries(&category)
+                .iter()
+                .position(|existing| *existing == entry);
+            if let Some(index) = index {
+                self.start_rerank_entry(category, index);
+                let ranking = matches!(self.screen, ScreenState::Ranking { .. });
+                if let Some(document) = self.document.as_mut() {
+                    document.ranking_queued = ranking;
+                }
+                return;
+            }
+        }
+    }
+
+    // Fetches new images for the entries in the background, keeping the old
+    // ones so the whole refresh can be undone.
+    fn refresh_images(&mut self, category: String, entries: Vec<String>, ctx: &egui::Context) {
+        if self.connection().is_offline() {
+            self.show_error(
+                "You Are Offline",
+                "Images can be fetched once there is a connection.".to_string(),
+            );
+            return;
+        }
+        let Some(document) = self.document.as_ref() else {
+            return;
+        };
+        if document
+            .bulk_fetch
+            .as_ref()
+            .is_some_and(|bulk_fetch| !bulk_fetch.is_finished())
+        {
+            self.show_error(
+                "Already Fetching Images",
+                "Wait for the current fetch to finish or cancel it first.".to_string(),
+            );
+            return;
+        }
+
+        let images = entries
+            .iter()
+            .map(
+                |entry| match document.images.snapshot_image(&category, entry) {
+                    Some(saved) => ImageUndo::Restore(saved),
+                    None => ImageUndo::Added {
+                        category: category.clone(),
+                        title: entry.clone(),
+                    },
+                },
+            )
+            .collect();
+        let jobs = self.image_fetch_jobs(
+            entries
+                .iter()
+                .map(|entry| (category.clone(), entry.clone()))
+                .collect(),
+        );
+        if jobs.is_empty() {
+            return;
+        }
+
+        if let Some(document) = self.document.as_mut() {
+            document.undo.push(UndoStep {
+                description: format!("Refresh {} Images", jobs.len()),
+                before: document.model.clone(),
+                after: document.model.clone(),
+                images,
+            });
+            document.bulk_fetch = Some(BulkFetch::start(jobs, bulk_fetch::REQUEST_INTERVAL, ctx));
+            document.fetch_in_undo_step = true;
+        }
+    }
+
+    // A bundle holding only the given entries of one category and their images.
+    fn export_entries(&mut self, category: String, entries: Vec<String>, path: PathBuf) {
+        let Some(document) = self.document.as_ref() else {
+            return;
+        };
+
+        let model = document.model.subset(&category, &entries);
+        let images: Vec<PathBuf> = model
+            .get_category_entries(&category)
+            .iter()
+            .filter_map(|entry| document.images.existing_image_file(&category, entry))
+            .collect();
+        let spreadsheet =
+            env::temp_dir().join(format!("media-rating-export-{}.xlsx", std::process::id()));
+        fs::remove_file(&spreadsheet).ok();
+
+        let exported = spreadsheet::save(&spreadsheet, &model)
+            .and_then(|()| bundle::pack(&spreadsheet, &images, &path));
+        fs::remove_file(&spreadsheet).ok();
+        if let Err(e) = exported {
+            self.show_error("Could Not Export Selection", e);
+        }
+    }
+
+    fn undo(&mut self) {
+        let Some(document) = self.document.as_mut() else {
+            return;
+        };
+        let Some(step) = document.undo.pop() else {
+            return;
+        };
+        if document.model != step.after {
+            document.undo.clear();
+            self.show_error(
+                "Could Not Undo",
+                format!(
+                    "The document has changed since \"{}\", so it can no longer be undone.",
+                    step.description
+                ),
+            );
+            return;
+        }
+
+        // A refresh still running would overwrite the images put back.
+        if document
+            .bulk_fetch
+            .as_ref()
+            .is_some_and(|bulk_fetch| !bulk_fetch.is_finished())
+        {
+            document.bulk_fetch = None;
+        }
+        document.rerank_queue.clear();
+        document.model = step.before;
+        document.images.set_geometries(&document.model);
+        self.probe_addresses_outdated = true;
+
+        let mut failures = Vec::new();
+        for image in step.images.iter().rev() {
+            let restored = match image {
+                ImageUndo::Restore(saved) => document.images.restore_image(saved),
+                ImageUndo::Added { category, title } => {
+                    document.images.delete_image(category, title);
+                    Ok(())
+                }
+                ImageUndo::Moved {
+                    from_category,
+                    to_category,
+                    title,
+                } => document
+                    .images
+                    .move_image(to_category, from_category, title),
+            };
+            if let Err(e) = restored {
+                failures.push(e.to_string());
+            }
+        }
+
+        if let Some(home) = self.home_screen_mut() {
+            home.entries_changed();
+        }
+        self.save_document();
+        if !failures.is_empty() {
+            self.show_error(
+                "Some Images Were Not Restored",
+                format!(
+                    "{}\n\nUse Check Images to reattach them.",
+                    failures.join("\n")
+                ),
+            );
+        }
+    }
+
+    fn delete_entry_and_start_rerank(
+        &mut self,
+        delete_category: String,
+        delete_index: usize,
+        rerank_category: String,
+        rerank_index: usize,
+    ) {
+        self.delete_entry(delete_category, delete_index);
+        self.start_rerank_entry(rerank_category, rerank_index);
+    }
+
+    fn open_image_picker(&mut self, category: String, entry: String, purpose: ImagePickPurpose) {
+        let provider = self.image_provider(&category);
+        let geometry = self.image_geometry(&category);
+        let cache = self.search_cache();
+        let picker = ImagePickerPopup::new(category, entry, purpose, provider, geometry, cache)
+            .with_offline(self.connection().is_offline());
+        self.popup = Some(Box::new(picker));
+    }
+
+    // The hosts of the image providers the open document uses.
+    fn probe_addresses(&self) -> Vec<String> {
+        let providers = match self.document.as_ref() {
+            Some(document) => document
+                .model
+                .get_categories()
+                .iter()
+                .map(|category| self.image_provider(category))
+                .collect(),
+            None => vec![ImageProviderConfig::default()],
+        };
+        let mut addresses: Vec<String> = providers
+            .iter()
+            .filter_map(ImageProviderConfig::probe_address)
+            .collect();
+        addresses.sort();
+        addresses.dedup();
+        addresses
+    }
+
+    fn connection(&self) -> Connection {
+        if self.settings.work_offline {
+            Connection::WorkingOffline
+        } else if self
+            .connectivity
+            .as_ref()
+            .is_some_and(|connectivity| !connectivity.is_online())
+        {
+            Connection::Offline
+        } else {
+            Connection::Online
+        }
+    }
+
+    fn search_cache(&self) -> Option<SearchCache> {
+        if self.settings.search_cache_mb == 0 {
+            return None;
+        }
+        SearchCache::in_user_cache(
+            self.settings.search_cache_hours,
+            self.settings.search_cache_mb,
+        )
+    }
+
+    // Open the picker on an image given other than by searching, so it can
+    // still be framed.
+    fn open_image_picker_with(
+        &mut self,
+        category: String,
+        entry: String,
+        input: ImageInput,
+        ctx: &egui::Context,
+    ) {
+        let provider = self.image_provider(&category);
+        let geometry = self.image_geometry(&category);
+        let mut picker = ImagePickerPopup::new(
+            category,
+            entry,
+            ImagePickPurpose::RefreshOnly,
+            provider,
+            geometry,
+            self.search_cache(),
+        )
+        .with_offline(self.connection().is_offline());
+        picker.use_input(input, ctx);
+        self.popup = Some(Box::new(picker));
+    }
+
+    fn paste_entry_image(&mut self, category: String, entry: String, ctx: &egui::Context) {
+        match image_input::clipboard_input() {
+            Ok(input) => self.open_image_picker_with(category, entry, input, ctx),
+            Err(message) => self.show_error("Nothing To Paste", message),
+        }
+    }
+
+    // Images dropped onto the window or links pasted while the home screen
+    // shows an entry become that entry's image.
+    fn accept_entry_image_input(&mut self, ctx: &egui::Context) {
+        if self.popup.is_some() {
+            return;
+        }
+        let (Some(document), ScreenState::Home(home)) = (self.document.as_ref(), &self.screen)
+        else {
+            return;
+        };
+        let Some((category, entry)) = home.selected_entry(&document.model) else {
+            return;
+        };
+
+        if let Some(result) = image_input::dropped_image(ctx) {
+            match result {
+                Ok(input) => self.open_image_picker_with(category, entry, input, ctx),
+                Err(message) => self.show_error("Could Not Open Image", message),
+            }
+        } else if let Some(url) = image_input::pasted_url(ctx) {
+            self.open_image_picker_with(category, entry, ImageInput::Url(url), ctx);
+        }
+    }
+
+    fn image_provider(&self, category: &str) -> ImageProviderConfig {
+        self.document
+            .as_ref()
+            .map(|document| self.settings.image_provider(document.file_path(), category))
+            .unwrap_or_default()
+    }
+
+    fn start_bulk_fetch(&mut self, ctx: &egui::Context) {
+        if self.connection().is_offline() {
+            self.show_error(
+                "You Are Offline",
+                "Images can be fetched once there is a connection.".to_string(),
+            );
+            return;
+        }
+        let Some(document) = self.document.as_ref() else {
+            return;
+        };
+
+        // Title cards stand in for images too.
+        let placeholders = document.images.placeholder_entries();
+        let mut entries = Vec::new();
+        for category in document.model.get_categories() {
+            for entry in document.model.get_category_entries(&category) {
+                let missing = document
+                    .images
+                    .existing_image_file(&category, entry)
+                    .is_none();
+                let target = (category.clone(), entry.clone());
+                if missing || placeholders.contains(&target) {
+                    entries.push(target);
+                }
+            }
+        }
+
+        let jobs = self.image_fetch_jobs(entries);
+        if jobs.is_empty() {
+            self.show_error(
+                "Nothing To Fetch",
+                "Every entry already has an image.".to_string(),
+            );
+            return;
+        }
 
-impl Default for MediaRatingApp {
-    fn default() -> Self {
-        Self {
-            document: None,
-            screen: ScreenState::Splash(SplashScreen),
-            popup: None,
+        if let Some(document) = self.document.as_mut() {
+            document.bulk_fetch = Some(BulkFetch::start(jobs, bulk_fetch::REQUEST_INTERVAL, ctx));
+            document.fetch_in_undo_step = false;
         }
     }
-}
 
-impl eframe::App for MediaRatingApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        let mut actions = Vec::new();
+    // One job per (category, entry) still in the document.
+    fn image_fetch_jobs(&self, entries: Vec<(String, String)>) -> Vec<BulkFetchJob> {
+        let Some(document) = self.document.as_ref() else {
+            return Vec::new();
+        };
 
-        match (&mut self.screen, self.document.as_mut()) {
-            (ScreenState::Splash(screen), _) => {
-                egui::CentralPanel::default().show(ctx, |ui| {
-                    actions.extend(screen.ui(ui));
-                });
-            }
-            (ScreenState::Home(screen), Some(document)) => {
-                egui::TopBottomPanel::top("Menu").show(ctx, |ui| {
-                    actions.extend(screen.menu_bar(ui, &document.model));
-                });
+        let path = document.file_path();
+        entries
+            .into_iter()
+            .filter(|(category, entry)| document.model.contains_entry(category, entry))
+            .map(|(category, entry)| {
+                let provider = self.settings.image_provider(path, &category);
+                BulkFetchJob {
+                    query: image_picker_popup::default_query(&entry, &category),
+                    provider: provider.build(),
+                    provider_name: provider.name(),
+                    geometry: document.images.geometry(&category),
+                    category,
+                    entry,
+                }
+            })
+            .collect()
+    }
 
-                egui::CentralPanel::default().show(ctx, |ui| {
-                    actions.extend(screen.ui(ctx, ui, &document.model, &mut document.images));
-                });
-            }
-            (ScreenState::Ranking { ranking, .. }, Some(document)) => {
-                egui::TopBottomPanel::top("Menu").show(ctx, |ui| {
-                    actions.extend(ranking.menu_bar(ui));
-                });
+    // Once there is a connection again, offer to replace the title cards
+    // saved while offline.
+    fn fetch_placeholders_when_online(&mut self) {
+        if self.connection().is_offline() {
+            self.refetch_placeholders = true;
+            return;
+        }
+        let Some(document) = self.document.as_ref() else {
+            return;
+        };
+        if !self.refetch_placeholders
+            || self.popup.is_some()
+            || document
+                .bulk_fetch
+                .as_ref()
+                .is_some_and(|bulk_fetch| !bulk_fetch.is_finished())
+        {
+            return;
+        }
 
-                egui::CentralPanel::default().show(ctx, |ui| {
-                    actions.extend(ranking.ui(ctx, ui, &mut document.images));
-                });
-            }
-            (_, None) => actions.push(AppAction::ReturnToSplash),
+        self.refetch_placeholders = false;
+        let count = document.images.placeholder_entries().len();
+        if count > 0 {
+            self.popup = Some(Box::new(FetchPlaceholdersPopup::new(count)));
         }
+    }
 
-        if let Some(active_popup) = self.popup.as_mut() {
-            match popup::show_modal(ctx, active_popup.as_mut()) {
-                PopupResponse::KeepOpen => {}
-                PopupResponse::Close => self.popup = None,
-                PopupResponse::Action(action) => {
-                    self.popup = None;
-                    actions.push(action);
-                }
-            }
+    fn fetch_placeholders(&mut self, ctx: &egui::Context) {
+        let Some(document) = self.document.as_ref() else {
+            return;
+        };
+        let jobs = self.image_fetch_jobs(document.images.placeholder_entries());
+        if jobs.is_empty() {
+            return;
+        }
+        if let Some(document) = self.document.as_mut() {
+            document.bulk_fetch = Some(BulkFetch::start(jobs, bulk_fetch::REQUEST_INTERVAL, ctx));
+            document.fetch_in_undo_step = false;
         }
+    }
 
-        for action in actions {
-            self.handle_action(action, ctx);
+    fn cancel_bulk_fetch(&mut self) {
+        if let Some(bulk_fetch) = self
+            .document
+            .as_mut()
+            .and_then(|document| document.bulk_fetch.as_mut())
+        {
+            bulk_fetch.cancel();
         }
     }
-}
 
-impl MediaRatingApp {
-    fn handle_action(&mut self, action: AppAction, ctx: &egui::Context) {
-        match action {
-            AppAction::OpenSpreadsheet(path) => self.open_document(path, false),
-            AppAction::CreateSpreadsheet(path) => self.open_document(path, true),
-            AppAction::ReturnToSplash => self.return_to_splash(),
-            AppAction::RequestDeleteCategory { category } => {
-                self.popup = Some(Box::new(ConfirmDeleteCategoryPopup::new(category)));
-            }
-            AppAction::DeleteCategory { category } => self.delete_category(category),
-            AppAction::CreateCategory { name } => self.create_category(name),
-            AppAction::StartAddEntry { category, entry } => self.start_add_entry(category, entry),
-            AppAction::StartRerankEntry { category, index } => {
-                self.start_rerank_entry(category, index);
-            }
-            AppAction::StartSwitchCategory {
-                from_category,
-                from_index,
-                to_category,
-                entry,
-            } => self.start_switch_category(from_category, from_index, to_category, entry),
-            AppAction::RenameEntry {
-                category,
-                index,
-                new_name,
-            } => self.rename_entry(category, index, new_name),
-            AppAction::DeleteEntry { category, index } => self.delete_entry(category, index),
-            AppAction::DeleteEntryAndStartRerank {
-                delete_category,
-                delete_index,
-                rerank_category,
-                rerank_index,
-            } => self.delete_entry_and_start_rerank(
-                delete_category,
-                delete_index,
-                rerank_category,
-                rerank_index,
-            ),
-            AppAction::RefreshImage { category, entry } => {
-                self.open_image_picker(category, entry, ImagePickPurpose::RefreshOnly);
-            }
-            AppAction::SetEntryImage {
+    fn review_bulk_fetch(&mut self) {
+        let Some(bulk_fetch) = self
+            .document
+            .as_ref()
+            .and_then(|document| document.bulk_fetch.as_ref())
+        else {
+            return;
+        };
+
+        let fetched = bulk_fetch
+            .fetched
+            .iter()
+            .map(|fetched| {
+                (
+                    fetched.category.clone(),
+                    fetched.entry.clone(),
+                    fetched.thumbnail.clone(),
+                )
+            })
+            .collect();
+        self.popup = Some(Box::new(BulkFetchReviewPopup::new(
+            fetched,
+            bulk_fetch.failed.clone(),
+        )));
+    }
+
+    // Save images as the bulk fetch delivers them.
+    fn poll_bulk_fetch(&mut self, ctx: &egui::Context) {
+        let Some(document) = self.document.as_mut() else {
+            return;
+        };
+        let Some(bulk_fetch) = document.bulk_fetch.as_mut() else {
+            return;
+        };
+
+        for event in bulk_fetch.poll() {
+            let BulkFetchEvent::Fetched {
                 category,
                 entry,
                 image,
-                purpose,
-            } => {
-                self.set_entry_image(category, entry, image, purpose, ctx);
+                provenance,
+            } = event
+            else {
+                continue;
+            };
+            // The entry may have been renamed or deleted since it was queued.
+            if !document.model.contains_entry(&category, &entry) {
+                continue;
+            }
+
+            match document
+                .images
+                .set_entry_image(&category, &entry, image.clone(), provenance, ctx)
+            {
+                Ok(()) => {
+                    if !document.fetch_in_undo_step {
+                        document.undo.forget_if_image_changed(&category, &entry);
+                    }
+                    bulk_fetch.record_fetched(category, entry, &image, ctx);
+                }
+                Err(e) => bulk_fetch.failed.push((category, entry, e.details)),
             }
-            AppAction::RankingFinished(outcome) => self.finish_ranking(outcome, ctx),
-            AppAction::CancelRanking => self.cancel_ranking(),
         }
-    }
 
-    fn open_document(&mut self, path: PathBuf, create_new: bool) {
-        if create_new {
-            if let Err(e) = spreadsheet::create_empty(&path) {
-                eprintln!("Could not create new spreadsheet: {e}");
-                return;
+        if bulk_fetch.just_ended() {
+            if let Err(e) = document.repack_if_bundle() {
+                self.show_error("Save Failed", e);
             }
         }
+    }
 
-        let Some(parent) = path.parent() else {
-            eprintln!("Spreadsheet path has no parent directory");
+    fn check_images(&mut self, ctx: &egui::Context) {
+        let Some(document) = self.document.as_ref() else {
             return;
         };
-        let document_directory = parent.to_path_buf();
-        let image_directory = document_directory.join("images");
-        if let Err(e) = fs::create_dir_all(&image_directory) {
-            eprintln!("Could not create image directory: {e}");
-            return;
-        }
 
-        let model = match spreadsheet::load(&path) {
-            Ok(model) => model,
-            Err(e) => {
-                eprintln!("Could not open spreadsheet: {e}");
-                return;
+        match image_maintenance::scan(document.images.image_directory(), &document.model) {
+            Ok(report) => {
+                let unhashed = document.images.unhashed_images(&document.model);
+                if unhashed.is_empty() {
+                    self.finish_image_check(report, Vec::new());
+                } else {
+                    self.popup = Some(Box::new(ImageHashingPopup::start(report, unhashed, ctx)));
+                }
             }
-        };
-
-        let selected_category = model.get_categories().first().cloned();
-        self.document = Some(DocumentContext {
-            spreadsheet_path: path,
-            model,
-            images: ImageStore::new(document_directory),
-        });
-        self.screen = ScreenState::Home(HomeScreen::new(selected_category));
+            Err(e) => self.show_error("Could Not Check Images", e.to_string()),
+        }
     }
 
-    fn return_to_splash(&mut self) {
-        self.document = None;
-        self.popup = None;
-        self.screen = ScreenState::Splash(SplashScreen);
-    }
+    fn finish_image_check(&mut self, mut report: ImageReport, hashes: Vec<(String, String, u64)>) {
+        let Some(document) = self.document.as_mut() else {
+            return;
+        };
 
-    fn create_category(&mut self, name: String) {
-        if let Some(document) = self.document.as_mut() {
-            document.model.create_category(name);
-            document.save();
+        for (category, entry, hash) in hashes {
+            if document.model.contains_entry(&category, &entry) {
+                document.images.record_hash(&category, &entry, hash);
+            }
         }
+        report.duplicates = document.images.near_duplicates(&document.model);
+        self.popup = Some(Box::new(ImageMaintenancePopup::new(report)));
     }
 
-    fn delete_category(&mut self, category: String) {
-        if let Some(document) = self.document.as_mut() {
-            document.model.delete_category(&category);
-            document.save();
+    fn apply_image_maintenance(&mut self, plan: MaintenancePlan) {
+        let Some(document) = self.document.as_mut() else {
+            return;
+        };
+
+        let mut errors =
+            image_maintenance::apply(document.images.image_directory(), &document.model, &plan);
+        document.images.clear_textures();
+        // A reattached file is not the image the record describes.
+        for (_, category, entry) in &plan.reattach {
+            document.images.forget_provenance(category, entry);
+        }
+        if let Err(e) = document.repack_if_bundle() {
+            errors.push(e);
         }
 
-        if let Some(home) = self.home_screen_mut() {
-            home.category_deleted(&category);
+        if !errors.is_empty() {
+            self.show_error("Some Images Could Not Be Fixed", errors.join("\n"));
         }
     }
 
-    fn rename_entry(&mut self, category: String, index: usize, new_name: String) {
-        if let Some(document) = self.document.as_mut() {
-            if let Some(old_name) = document
-                .model
-                .rename_entry(&category, index, new_name.clone())
-            {
-                document
-                    .images
-                    .rename_image(&category, &old_name, &new_name);
-                document.save();
-            }
-        }
+    fn image_geometry(&self, category: &str) -> ImageGeometry {
+        self.document
+            .as_ref()
+            .map(|document| document.images.geometry(category))
+            .unwrap_or_default()
     }
 
-    fn delete_entry(&mut self, category: String, index: usize) {
-        if let Some(document) = self.document.as_mut() {
-            if let Some(entry) = document.model.delete_entry(&category, index) {
-                document.images.delete_image(&category, &entry);
-                document.save();
-            }
-        }
+    fn edit_image_provider(&mut self, category: String) {
+        let provider = self.image_provider(&category);
+        let geometry = self.image_geometry(&category);
+        self.popup = Some(Box::new(ImageProviderPopup::new(
+            category, provider, geometry,
+        )));
     }
 
-    fn delete_entry_and_start_rerank(
+    fn set_image_provider(
         &mut self,
-        delete_category: String,
-        delete_index: usize,
-        rerank_category: String,
-        rerank_index: usize,
+        category: String,
+        provider: ImageProviderConfig,
+        geometry: ImageGeometry,
     ) {
-        self.delete_entry(delete_category, delete_index);
-        self.start_rerank_entry(rerank_category, rerank_index);
+        let Some(document) = self.document.as_mut() else {
+            return;
+        };
+
+        let path = document.file_path().to_path_buf();
+        self.settings.set_image_provider(&path, &category, provider);
+        self.settings.save();
+        self.probe_addresses_outdated = true;
+        if document.model.image_geometry(&category) != Some(geometry) {
+            document.model.set_image_geometry(&category, geometry);
+            document.images.set_geometries(&document.model);
+            self.save_document();
+        }
     }
 
-    fn open_image_picker(&mut self, category: String, entry: String, purpose: ImagePickPurpose) {
-        self.popup = Some(Box::new(ImagePickerPopup::new(category, entry, purpose)));
+    fn look_up_metadata(&mut self, category: String, entry: String) {
+        let Some(document) = self.document.as_ref() else {
+            return;
+        };
+
+        let catalogue = self.settings.catalogue(document.file_path(), &category);
+        let current = document.model.entry_metadata(&category, &entry).cloned();
+        self.popup = Some(Box::new(MetadataPopup::new(
+            category, entry, catalogue, current,
+        )));
+    }
+
+    fn set_entry_metadata(
+        &mut self,
+        category: String,
+        entry: String,
+        choice: MetadataChoice,
+        ctx: &egui::Context,
+    ) {
+        let Some(document) = self.document.as_mut() else {
+            return;
+        };
+        let MetadataChoice {
+            metadata,
+            poster,
+            catalogue,
+        } = choice;
+
+        self.settings
+            .set_catalogue(document.file_path(), &category, catalogue);
+        self.settings.save();
+
+        let provenance = metadata
+            .as_ref()
+            .zip(poster.as_ref())
+            .map(|(metadata, poster)| {
+                ImageProvenance::new(
+                    metadata.source.clone(),
+                    metadata.poster_url.clone(),
+                    Some(metadata.title.clone()),
+                    poster,
+                )
+            });
+        document
+            .model
+            .set_entry_metadata(&category, entry.clone(), metadata);
+        self.save_document();
+
+        if let (Some(poster), Some(provenance)) = (poster, provenance) {
+            self.set_entry_image(
+                category,
+                entry,
+                poster,
+                provenance,
+                ImagePickPurpose::RefreshOnly,
+                ctx,
+            );
+        }
     }
 
     fn set_entry_image(
@@ -314,6 +1749,7 @@ impl MediaRatingApp {
         category: String,
         entry: String,
         image: image::DynamicImage,
+        provenance: ImageProvenance,
         purpose: ImagePickPurpose,
         ctx: &egui::Context,
     ) {
@@ -323,14 +1759,22 @@ impl MediaRatingApp {
 
         if let Err(e) = document
             .images
-            .set_entry_image(&category, &entry, image, ctx)
+            .set_entry_image(&category, &entry, image, provenance, ctx)
         {
-            eprintln!("Could not save selected image: {e}");
+            self.show_error("Could Not Save Image", e.details);
             return;
         }
+        document.undo.forget_if_image_changed(&category, &entry);
+        if let Some(bulk_fetch) = document.bulk_fetch.as_mut() {
+            bulk_fetch.forget(&category, &entry);
+        }
 
         match purpose {
-            ImagePickPurpose::RefreshOnly => {}
+            ImagePickPurpose::RefreshOnly => {
+                if let Err(e) = document.repack_if_bundle() {
+                    self.show_error("Save Failed", e);
+                }
+            }
             ImagePickPurpose::AddEntry => self.continue_add_entry(category, entry),
             ImagePickPurpose::SwitchCategory {
                 from_category,
@@ -339,7 +1783,7 @@ impl MediaRatingApp {
         }
     }
 
-    fn start_add_entry(&mut self, category: String, entry: String) {
+    fn start_add_entry(&mut self, category: String, entry: String, ctx: &egui::Context) {
         let Some(document) = self.document.as_ref() else {
             return;
         };
@@ -348,6 +1792,22 @@ impl MediaRatingApp {
             return;
         }
 
+        // Offline, the entry gets a title card so ranking need not wait.
+        if self.connection().is_offline() {
+            let card = title_card::render(&entry, &category, self.image_geometry(&category));
+            let provenance = ImageProvenance::new(PLACEHOLDER_PROVIDER, None, None, &card);
+            self.refetch_placeholders = true;
+            self.set_entry_image(
+                category,
+                entry,
+                card,
+                provenance,
+                ImagePickPurpose::AddEntry,
+                ctx,
+            );
+            return;
+        }
+
         self.open_image_picker(category, entry, ImagePickPurpose::AddEntry);
     }
 
@@ -359,7 +1819,7 @@ impl MediaRatingApp {
         let entries = document.model.get_category_entries(&category).to_vec();
         if entries.is_empty() {
             document.model.insert_entry_at(&category, entry, 0);
-            document.save();
+            self.save_document();
             return;
         }
 
@@ -512,6 +1972,9 @@ impl MediaRatingApp {
                     from_index,
                     original_entry,
                 } => {
+                    let metadata = document
+                        .model
+                        .take_entry_metadata(&from_category, &original_entry);
                     document.model.delete_entry(&from_category, from_index);
                     document.images.replace_for_category_switch(
                         &from_category,
@@ -520,16 +1983,25 @@ impl MediaRatingApp {
                         &outcome.entry,
                         ctx,
                     );
+                    document.model.insert_entry_at(
+                        &outcome.category,
+                        outcome.entry.clone(),
+                        outcome.index,
+                    );
                     document
                         .model
-                        .insert_entry_at(&outcome.category, outcome.entry, outcome.index);
+                        .set_entry_metadata(&outcome.category, outcome.entry, metadata);
                 }
             }
 
-            document.save();
+            if std::mem::take(&mut document.ranking_queued) {
+                document.undo.update_after(&document.model);
+            }
+            self.save_document();
         }
 
         self.return_to_home();
+        self.start_next_queued_rerank();
     }
 
     fn transition_home_to_ranking(&mut self, ranking: RankingScreen) {
@@ -559,6 +2031,11 @@ impl MediaRatingApp {
                 document.images.delete_image(category, entry);
             }
         }
+        // Cancelling one ranking of a bulk move stops the rest too.
+        if let Some(document) = self.document.as_mut() {
+            document.rerank_queue.clear();
+            document.ranking_queued = false;
+        }
 
         self.return_to_home();
     }
@@ -0,0 +1,192 @@
+use chrono::{Local, NaiveDateTime, TimeDelta};
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+pub const DEFAULT_BACKUP_COUNT: usize = 10;
+const BACKUP_DIRECTORY: &str = "backups";
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H-%M-%S-%3f";
+// Saves closer together than this share one backup, so the kept backups
+// reach back over a useful stretch of time rather than the last few edits.
+const BACKUP_INTERVAL: TimeDelta = TimeDelta::minutes(15);
+
+#[derive(Debug, Clone)]
+pub struct Backup {
+    pub path: PathBuf,
+    pub created: NaiveDateTime,
+}
+
+// Write to a temporary sibling first and rename it over the target, so a
+// crash or full disk never leaves a half-written file behind.
+pub fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let temporary_path = temporary_path(path);
+    let result = File::create(&temporary_path).and_then(|mut file| {
+        file.write_all(contents)?;
+        file.sync_all()
+    });
+
+    match result.and_then(|()| fs::rename(&temporary_path, path)) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            fs::remove_file(&temporary_path).ok();
+            Err(e)
+        }
+    }
+}
+
+// Back up `contents` as a version of `path`, but only if the newest backup
+// is older than the backup interval. Used for saves, which happen after every
+// edit; `contents` is only read when a backup is due.
+pub fn backup_if_due(
+    path: &Path,
+    contents: impl FnOnce() -> io::Result<Option<Vec<u8>>>,
+    keep: usize,
+) -> io::Result<()> {
+    let now = Local::now().naive_local();
+    let recent = list_backups(path)
+        .first()
+        .is_some_and(|newest| now - newest.created < BACKUP_INTERVAL);
+    if keep == 0 || recent {
+        return Ok(());
+    }
+    if let Some(contents) = contents()? {
+        add_backup(path, &contents)?;
+        prune_backups(path, keep);
+    }
+    Ok(())
+}
+
+// All backups of the file, newest first.
+pub fn list_backups(path: &Path) -> Vec<Backup> {
+    let Ok(read_dir) = fs::read_dir(backup_directory(path)) else {
+        return Vec::new();
+    };
+
+    let prefix = format!("{} ", file_stem(path));
+    let suffix = extension(path);
+    let mut backups: Vec<Backup> = read_dir
+        .filter_map(Result::ok)
+        .filter_map(|dir_entry| {
+            let file_name = dir_entry.file_name().into_string().ok()?;
+            let timestamp = file_name.strip_prefix(&prefix)?.strip_suffix(&suffix)?;
+            let created = NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT).ok()?;
+            Some(Backup {
+                path: dir_entry.path(),
+                created,
+            })
+        })
+        .collect();
+
+    backups.sort_by_key(|backup| std::cmp::Reverse(backup.created));
+    backups
+}
+
+// Replace the file with a backup, keeping the replaced version as a backup too.
+pub fn restore_backup(backup: &Path, path: &Path, keep: usize) -> io::Result<()> {
+    let contents = fs::read(backup)?;
+    if path.exists() {
+        keep_replaced(path, &fs::read(path)?, keep)?;
+    }
+    write_atomically(path, &contents)
+}
+
+// Keep a version about to be replaced. It is always kept, but with backups
+// turned off nothing older is pruned.
+pub fn keep_replaced(path: &Path, contents: &[u8], keep: usize) -> io::Result<()> {
+    add_backup(path, contents)?;
+    if keep > 0 {
+        prune_backups(path, keep);
+    }
+    Ok(())
+}
+
+fn add_backup(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let directory = backup_directory(path);
+    fs::create_dir_all(&directory)?;
+
+    let timestamp = Local::now().format(TIMESTAMP_FORMAT);
+    let backup_path = directory.join(format!(
+        "{} {timestamp}{}",
+        file_stem(path),
+        extension(path)
+    ));
+    write_atomically(&backup_path, contents)
+}
+
+fn prune_backups(path: &Path, keep: usize) {
+    for backup in list_backups(path).into_iter().skip(keep) {
+        fs::remove_file(backup.path).ok();
+    }
+}
+
+fn backup_directory(path: &Path) -> PathBuf {
+    path.parent()
+        .unwrap_or_else(|| Path::new("."))
+        .join(BACKUP_DIRECTORY)
+}
+
+fn temporary_path(path: &Path) -> PathBuf {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{file_name}.tmp"))
+}
+
+fn file_stem(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn backups_keep_only_newest_copies() {
+        let root =
+            env::temp_dir().join(format!("media-rating-backups-test-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let path = root.join("Ratings.xlsx");
+
+        for version in 0..4 {
+            write_atomically(&path, format!("version {version}").as_bytes()).unwrap();
+            keep_replaced(&path, &fs::read(&path).unwrap(), 2).unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+
+        let backups = list_backups(&path);
+        assert_eq!(backups.len(), 2);
+        assert_eq!(fs::read(&backups[0].path).unwrap(), b"version 3");
+        assert_eq!(fs::read(&backups[1].path).unwrap(), b"version 2");
+
+        restore_backup(&backups[1].path, &path, 2).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"version 2");
+        assert!(!temporary_path(&path).exists());
+
+        // Saves soon after a backup do not make another.
+        write_atomically(&path, b"version 4").unwrap();
+        backup_if_due(&path, || fs::read(&path).map(Some), 2).unwrap();
+        assert_eq!(
+            fs::read(&list_backups(&path)[0].path).unwrap(),
+            b"version 3"
+        );
+
+        // With backups turned off a restore keeps every existing backup.
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        restore_backup(&list_backups(&path)[1].path, &path, 0).unwrap();
+        assert_eq!(list_backups(&path).len(), 3);
+
+        fs::remove_dir_all(root).ok();
+    }
+}
@@ -0,0 +1,305 @@
+use eframe::egui;
+use std::{
+    collections::HashSet,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    image_provenance::ImageProvenance,
+    image_provider::ImageProvider,
+    image_search::{self, SearchRequest},
+    image_store::ImageGeometry,
+};
+
+// Pause between searches so a long run does not get us rate limited.
+pub const REQUEST_INTERVAL: Duration = Duration::from_millis(1500);
+
+pub struct BulkFetchJob {
+    pub category: String,
+    pub entry: String,
+    pub query: String,
+    pub provider: Box<dyn ImageProvider>,
+    // Recorded with each fetched image.
+    pub provider_name: &'static str,
+    pub geometry: ImageGeometry,
+}
+
+pub enum BulkFetchEvent {
+    Fetched {
+        category: String,
+        entry: String,
+        image: image::DynamicImage,
+        provenance: ImageProvenance,
+    },
+    Failed {
+        category: String,
+        entry: String,
+        message: String,
+    },
+}
+
+// An entry whose image came from the bulk fetch, kept for review.
+pub struct FetchedEntry {
+    pub category: String,
+    pub entry: String,
+    pub thumbnail: egui::TextureHandle,
+}
+
+// Fetches the top search result for each job on a background thread, one at
+// a time. Results are collected by `poll` on the UI thread.
+pub struct BulkFetch {
+    receiver: Receiver<BulkFetchEvent>,
+    cancelled: Arc<AtomicBool>,
+    total: usize,
+    done: usize,
+    // Entries given an image some other way since the run started, whose
+    // results are dropped when they arrive.
+    forgotten: HashSet<(String, String)>,
+    // Whether `just_ended` has reported the end of the run.
+    end_reported: bool,
+    pub fetched: Vec<FetchedEntry>,
+    pub failed: Vec<(String, String, String)>,
+}
+
+impl BulkFetch {
+    pub fn start(jobs: Vec<BulkFetchJob>, interval: Duration, ctx: &egui::Context) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let total = jobs.len();
+
+        let worker_cancelled = Arc::clone(&cancelled);
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            let mut last_request: Option<Instant> = None;
+            for job in jobs {
+                if let Some(wait) = last_request
+                    .map(|last| interval.saturating_sub(last.elapsed()))
+                    .filter(|wait| !wait.is_zero())
+                {
+                    thread::sleep(wait);
+                }
+                if worker_cancelled.load(Ordering::SeqCst) {
+                    return;
+                }
+                last_request = Some(Instant::now());
+
+                let event = match image_search::search_many(
+                    job.provider.as_ref(),
+                    &SearchRequest::new(&job.query, job.geometry, 1),
+                ) {
+                    Ok(mut results) if !results.is_empty() => {
+                        let result = results.swap_remove(0);
+                        let provenance = ImageProvenance::new(
+                            job.provider_name,
+                            Some(result.source),
+                            Some(job.query),
+                            &result.image,
+                        )
+                        .with_original_size(result.original_size);
+                        BulkFetchEvent::Fetched {
+                            category: job.category,
+                            entry: job.entry,
+                            image: result.image,
+                            provenance,
+                        }
+                    }
+                    Ok(_) => BulkFetchEvent::Failed {
+                        category: job.category,
+                        entry: job.entry,
+                        message: "No usable image found".to_string(),
+                    },
+                    Err(e) => BulkFetchEvent::Failed {
+                        category: job.category,
+                        entry: job.entry,
+                        message: e.details,
+                    },
+                };
+
+                if sender.send(event).is_err() {
+                    return;
+                }
+                ctx.request_repaint();
+            }
+        });
+
+        Self {
+            receiver,
+            cancelled,
+            total,
+            done: 0,
+            forgotten: HashSet::new(),
+            end_reported: false,
+            fetched: Vec::new(),
+            failed: Vec::new(),
+        }
+    }
+
+    // Events that arrived since the last call. Failures are recorded here;
+    // fetched images are for the caller to save and then `record_fetched`.
+    // Nothing is delivered once the run is cancelled.
+    pub fn poll(&mut self) -> Vec<BulkFetchEvent> {
+        let events: Vec<BulkFetchEvent> = self.receiver.try_iter().collect();
+        self.done += events.len();
+        if self.cancelled.load(Ordering::SeqCst) {
+            return Vec::new();
+        }
+
+        let mut delivered = Vec::new();
+        for event in events {
+            match event {
+                BulkFetchEvent::Failed {
+                    category,
+                    entry,
+                    message,
+                } => self.failed.push((category, entry, message)),
+                BulkFetchEvent::Fetched {
+                    ref category,
+                    ref entry,
+                    ..
+                } if self.forgotten.contains(&(category.clone(), entry.clone())) => {}
+                event => delivered.push(event),
+            }
+        }
+        delivered
+    }
+
+    pub fn record_fetched(
+        &mut self,
+        category: String,
+        entry: String,
+        image: &image::DynamicImage,
+        ctx: &egui::Context,
+    ) {
+        let thumbnail = image.thumbnail(190, 190);
+        let thumbnail = ctx.load_texture(
+            format!("bulk-fetch-{entry} {category}"),
+            egui::ColorImage::from_rgba_unmultiplied(
+                [thumbnail.width() as usize, thumbnail.height() as usize],
+                &thumbnail.to_rgba8(),
+            ),
+            egui::TextureOptions::LINEAR,
+        );
+        self.fetched.push(FetchedEntry {
+            category,
+            entry,
+            thumbnail,
+        });
+    }
+
+    // Drop an entry from review and ignore its result if it is still to come,
+    // e.g. once the user picked another image.
+    pub fn forget(&mut self, category: &str, entry: &str) {
+        self.fetched
+            .retain(|fetched| fetched.category != category || fetched.entry != entry);
+        self.forgotten
+            .insert((category.to_string(), entry.to_string()));
+    }
+
+    pub fn cancel(&mut self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn progress(&self) -> (usize, usize) {
+        (self.done, self.total)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.done >= self.total || self.cancelled.load(Ordering::SeqCst)
+    }
+
+    // True once, on the first call after the run finished or was cancelled.
+    pub fn just_ended(&mut self) -> bool {
+        if !self.is_finished() || self.end_reported {
+            return false;
+        }
+        self.end_reported = true;
+        true
+    }
+}
+
+impl Drop for BulkFetch {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image_provider::LocalFolderProvider;
+    use std::{env, fs};
+
+    #[test]
+    fn bulk_fetch_reports_each_entry_and_finishes() {
+        let directory = env::temp_dir().join(format!(
+            "media-rating-bulk-fetch-test-{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&directory).unwrap();
+        image::DynamicImage::new_rgb8(200, 250)
+            .save(directory.join("Alien.png"))
+            .unwrap();
+
+        let job = |entry: &str| BulkFetchJob {
+            category: "Movies:".to_string(),
+            entry: entry.to_string(),
+            query: entry.to_string(),
+            provider: Box::new(LocalFolderProvider {
+                directory: directory.clone(),
+            }),
+            provider_name: "Local Folder",
+            geometry: ImageGeometry::default(),
+        };
+        let ctx = egui::Context::default();
+        let finish = |mut fetch: BulkFetch| {
+            let mut events = Vec::new();
+            let deadline = Instant::now() + Duration::from_secs(10);
+            while !fetch.is_finished() && Instant::now() < deadline {
+                events.extend(fetch.poll());
+                thread::sleep(Duration::from_millis(10));
+            }
+            (fetch, events)
+        };
+
+        let (mut fetch, events) = finish(BulkFetch::start(
+            vec![job("Alien"), job("Dune")],
+            Duration::ZERO,
+            &ctx,
+        ));
+        assert_eq!(fetch.progress(), (2, 2));
+        assert!(matches!(
+            &events[0],
+            BulkFetchEvent::Fetched { entry, provenance, .. }
+                if entry == "Alien" && provenance.original_size == [200, 250]
+        ));
+        assert_eq!(fetch.failed.len(), 1);
+        assert_eq!(fetch.failed[0].1, "Dune");
+        assert!(fetch.just_ended());
+        assert!(!fetch.just_ended());
+
+        // A forgotten entry's result is dropped when it arrives.
+        let mut fetch = BulkFetch::start(vec![job("Alien")], Duration::ZERO, &ctx);
+        fetch.forget("Movies:", "Alien");
+        let (fetch, events) = finish(fetch);
+        assert_eq!(fetch.progress(), (1, 1));
+        assert!(events.is_empty());
+
+        // A cancelled run ends straight away, and still reports its end.
+        let mut fetch = BulkFetch::start(
+            vec![job("Alien"), job("Dune")],
+            Duration::from_secs(60),
+            &ctx,
+        );
+        fetch.cancel();
+        assert!(fetch.is_finished());
+        assert!(fetch.just_ended());
+
+        fs::remove_dir_all(directory).ok();
+    }
+}
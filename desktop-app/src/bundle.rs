@@ -0,0 +1,313 @@
+use std::{
+    fs::{self, File},
+    io::{Cursor, Read, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::backups;
+
+pub const BUNDLE_EXTENSION: &str = "mediarating";
+const MANIFEST_FILE: &str = "manifest.json";
+const DATA_FILE: &str = "data.xlsx";
+const IMAGE_DIRECTORY: &str = "images";
+const FORMAT_VERSION: u64 = 1;
+
+pub fn is_bundle(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case(BUNDLE_EXTENSION))
+}
+
+// A bundle unpacked into a private working directory. The app edits the loose
+// files there and packs them back into the bundle on every save; the working
+// directory is removed when the bundle is closed.
+pub struct OpenBundle {
+    pub path: PathBuf,
+    working_directory: PathBuf,
+    // The size and modification time of each image as last packed, so saves
+    // that only change the data can copy the packed images over as they are.
+    packed_images: Option<Vec<(PathBuf, u64, Option<SystemTime>)>>,
+}
+
+impl OpenBundle {
+    pub fn open(path: PathBuf) -> Result<Self, String> {
+        let bundle = Self::with_working_directory(path)?;
+        unpack(&bundle.path, &bundle.working_directory, DATA_FILE)?;
+        Ok(bundle)
+    }
+
+    pub fn create(
+        path: PathBuf,
+        create_spreadsheet: impl FnOnce(&Path) -> Result<(), String>,
+    ) -> Result<Self, String> {
+        let mut bundle = Self::with_working_directory(path)?;
+        create_spreadsheet(&bundle.spreadsheet_path())?;
+        bundle.save(&[])?;
+        Ok(bundle)
+    }
+
+    pub fn spreadsheet_path(&self) -> PathBuf {
+        self.working_directory.join(DATA_FILE)
+    }
+
+    pub fn save(&mut self, images: &[PathBuf]) -> Result<(), String> {
+        let stamps = image_stamps(images);
+        if self.packed_images.as_ref() == Some(&stamps) {
+            let data = fs::read(self.spreadsheet_path()).map_err(|e| e.to_string())?;
+            return replace_data(&self.path, &data);
+        }
+
+        pack(&self.spreadsheet_path(), images, &self.path)?;
+        self.packed_images = Some(stamps);
+        Ok(())
+    }
+
+    // Replace the working spreadsheet with the one in the bundle on disk,
+    // leaving the working images alone. The bundle's images may have changed
+    // too, so the next save packs ours again.
+    pub fn reload_data(&mut self) -> Result<(), String> {
+        self.packed_images = None;
+        let mut archive = open_archive(&self.path)?;
+        let data_file = read_manifest(&mut archive)?;
+        let contents = read_file(&mut archive, &data_file)?;
+        backups::write_atomically(&self.spreadsheet_path(), &contents).map_err(|e| e.to_string())
+    }
+
+    fn with_working_directory(path: PathBuf) -> Result<Self, String> {
+        let stamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_nanos());
+        let working_directory = std::env::temp_dir()
+            .join("media-rating-bundles")
+            .join(format!("{}-{stamp}", std::process::id()));
+        fs::create_dir_all(working_directory.join(IMAGE_DIRECTORY))
+            .map_err(|e| format!("Could not create bundle working directory: {e}"))?;
+
+        Ok(Self {
+            path,
+            working_directory,
+            packed_images: None,
+        })
+    }
+}
+
+impl Drop for OpenBundle {
+    fn drop(&mut self) {
+        fs::remove_dir_all(&self.working_directory).ok();
+    }
+}
+
+// Write a workbook and the given image files into a bundle. Images are stored
+// under `images/` by file name.
+pub fn pack(spreadsheet: &Path, images: &[PathBuf], bundle: &Path) -> Result<(), String> {
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    let compressed = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    // PNGs are already compressed.
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+
+    let image_names: Vec<String> = images
+        .iter()
+        .filter_map(|image| image.file_name())
+        .map(|name| name.to_string_lossy().into_owned())
+        .collect();
+    let manifest = serde_json::json!({
+        "format_version": FORMAT_VERSION,
+        "data_file": DATA_FILE,
+        "images": image_names,
+    });
+
+    writer
+        .start_file(MANIFEST_FILE, compressed)
+        .map_err(|e| e.to_string())?;
+    writer
+        .write_all(manifest.to_string().as_bytes())
+        .map_err(|e| e.to_string())?;
+
+    writer
+        .start_file(DATA_FILE, compressed)
+        .map_err(|e| e.to_string())?;
+    writer
+        .write_all(&fs::read(spreadsheet).map_err(|e| e.to_string())?)
+        .map_err(|e| e.to_string())?;
+
+    for (image, name) in images.iter().zip(&image_names) {
+        writer
+            .start_file(format!("{IMAGE_DIRECTORY}/{name}"), stored)
+            .map_err(|e| e.to_string())?;
+        writer
+            .write_all(&fs::read(image).map_err(|e| e.to_string())?)
+            .map_err(|e| e.to_string())?;
+    }
+
+    let contents = writer.finish().map_err(|e| e.to_string())?.into_inner();
+    backups::write_atomically(bundle, &contents).map_err(|e| e.to_string())
+}
+
+// Rewrite a bundle with new data, copying every other file over still
+// compressed rather than packing the images again.
+pub fn replace_data(bundle: &Path, data: &[u8]) -> Result<(), String> {
+    let mut archive = open_archive(bundle)?;
+    let data_file = read_manifest(&mut archive)?;
+    let compressed = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    for index in 0..archive.len() {
+        let file = archive.by_index_raw(index).map_err(|e| e.to_string())?;
+        if file.name() == data_file {
+            drop(file);
+            writer
+                .start_file(data_file.as_str(), compressed)
+                .map_err(|e| e.to_string())?;
+            writer.write_all(data).map_err(|e| e.to_string())?;
+        } else {
+            writer.raw_copy_file(file).map_err(|e| e.to_string())?;
+        }
+    }
+
+    let contents = writer.finish().map_err(|e| e.to_string())?.into_inner();
+    backups::write_atomically(bundle, &contents).map_err(|e| e.to_string())
+}
+
+pub fn read_data(bundle: &Path) -> Result<Vec<u8>, String> {
+    let mut archive = open_archive(bundle)?;
+    let data_file = read_manifest(&mut archive)?;
+    read_file(&mut archive, &data_file)
+}
+
+// Backups of a bundle hold only its data, named as if it were a workbook
+// beside the bundle.
+pub fn data_backup_path(bundle: &Path) -> PathBuf {
+    bundle.with_extension(format!("{BUNDLE_EXTENSION}.xlsx"))
+}
+
+fn image_stamps(images: &[PathBuf]) -> Vec<(PathBuf, u64, Option<SystemTime>)> {
+    images
+        .iter()
+        .map(|image| {
+            let metadata = fs::metadata(image).ok();
+            (
+                image.clone(),
+                metadata.as_ref().map_or(0, fs::Metadata::len),
+                metadata.and_then(|metadata| metadata.modified().ok()),
+            )
+        })
+        .collect()
+}
+
+// Unpack a bundle into `directory` as a loose workbook named `spreadsheet_name`
+// next to an `images/` folder, refusing to overwrite any existing file.
+pub fn unpack(bundle: &Path, directory: &Path, spreadsheet_name: &str) -> Result<PathBuf, String> {
+    let mut archive = open_archive(bundle)?;
+    let data_file = read_manifest(&mut archive)?;
+
+    let image_directory = directory.join(IMAGE_DIRECTORY);
+    let mut files = vec![(data_file, directory.join(spreadsheet_name))];
+    for index in 0..archive.len() {
+        let file = archive.by_index(index).map_err(|e| e.to_string())?;
+        let Some(name) = file.enclosed_name() else {
+            continue;
+        };
+        let mut components = name.components();
+        let (Some(folder), Some(file_name), None) =
+            (components.next(), components.next(), components.next())
+        else {
+            continue;
+        };
+        if folder.as_os_str() == IMAGE_DIRECTORY && !file.is_dir() {
+            files.push((file.name().to_string(), image_directory.join(file_name)));
+        }
+    }
+
+    let existing = files
+        .iter()
+        .filter(|(_, destination)| destination.exists())
+        .count();
+    if existing > 0 {
+        return Err(format!(
+            "{existing} file(s) from the bundle already exist in {}",
+            directory.display()
+        ));
+    }
+
+    fs::create_dir_all(&image_directory).map_err(|e| e.to_string())?;
+    for (name, destination) in &files {
+        let contents = read_file(&mut archive, name)?;
+        backups::write_atomically(destination, &contents).map_err(|e| e.to_string())?;
+    }
+
+    Ok(directory.join(spreadsheet_name))
+}
+
+fn open_archive(bundle: &Path) -> Result<ZipArchive<File>, String> {
+    let file = File::open(bundle).map_err(|e| format!("Could not open bundle: {e}"))?;
+    ZipArchive::new(file).map_err(|e| format!("Bundle is not a valid archive: {e}"))
+}
+
+// Check the manifest's format version and return the name of the data file.
+fn read_manifest(archive: &mut ZipArchive<File>) -> Result<String, String> {
+    let manifest: serde_json::Value = serde_json::from_slice(&read_file(archive, MANIFEST_FILE)?)
+        .map_err(|e| format!("Bundle manifest is invalid: {e}"))?;
+    let version = manifest
+        .get("format_version")
+        .and_then(serde_json::Value::as_u64)
+        .unwrap_or_default();
+    if version == 0 || version > FORMAT_VERSION {
+        return Err(format!("Unsupported bundle format version {version}"));
+    }
+
+    Ok(manifest
+        .get("data_file")
+        .and_then(serde_json::Value::as_str)
+        .unwrap_or(DATA_FILE)
+        .to_string())
+}
+
+fn read_file(archive: &mut ZipArchive<File>, name: &str) -> Result<Vec<u8>, String> {
+    let mut file = archive
+        .by_name(name)
+        .map_err(|e| format!("Bundle is missing {name}: {e}"))?;
+    let mut contents = Vec::new();
+    file.read_to_end(&mut contents).map_err(|e| e.to_string())?;
+    Ok(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn pack_and_unpack_round_trip_workbook_and_images() {
+        let root = env::temp_dir().join(format!("media-rating-bundle-test-{}", std::process::id()));
+        let source = root.join("source");
+        fs::create_dir_all(source.join(IMAGE_DIRECTORY)).unwrap();
+        let spreadsheet = source.join("Ratings.xlsx");
+        let image = source.join(IMAGE_DIRECTORY).join("Alien Movies.png");
+        fs::write(&spreadsheet, b"workbook bytes").unwrap();
+        fs::write(&image, b"image bytes").unwrap();
+
+        let bundle = root.join("Ratings.mediarating");
+        pack(&spreadsheet, &[image], &bundle).unwrap();
+
+        let destination = root.join("destination");
+        let unpacked = unpack(&bundle, &destination, "Imported.xlsx").unwrap();
+        assert_eq!(fs::read(unpacked).unwrap(), b"workbook bytes");
+        assert_eq!(
+            fs::read(destination.join(IMAGE_DIRECTORY).join("Alien Movies.png")).unwrap(),
+            b"image bytes"
+        );
+        assert!(unpack(&bundle, &destination, "Imported.xlsx").is_err());
+
+        replace_data(&bundle, b"new workbook bytes").unwrap();
+        assert_eq!(read_data(&bundle).unwrap(), b"new workbook bytes");
+        let replaced = root.join("replaced");
+        unpack(&bundle, &replaced, "Imported.xlsx").unwrap();
+        assert_eq!(
+            fs::read(replaced.join(IMAGE_DIRECTORY).join("Alien Movies.png")).unwrap(),
+            b"image bytes"
+        );
+
+        fs::remove_dir_all(root).ok();
+    }
+}
@@ -0,0 +1,144 @@
+use eframe::egui;
+use std::{
+    net::{TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Connection {
+    Online,
+    // No network was found.
+    Offline,
+    // The user chose to work offline.
+    WorkingOffline,
+}
+
+impl Connection {
+    pub fn is_offline(self) -> bool {
+        self != Connection::Online
+    }
+}
+
+// Checks for a network connection on a background thread every so often.
+// Reaching the image providers in use is what matters, so their hosts are
+// what it tries; with none to reach it counts as online.
+pub struct ConnectivityMonitor {
+    online: Arc<AtomicBool>,
+    stopped: Arc<AtomicBool>,
+    probe_addresses: Arc<Mutex<Vec<String>>>,
+    // Set when the addresses change, so they are checked without waiting.
+    recheck: Arc<AtomicBool>,
+    // Nothing is probed while the user works offline.
+    paused: Arc<AtomicBool>,
+}
+
+impl ConnectivityMonitor {
+    pub fn start(ctx: &egui::Context) -> Self {
+        // Assume online until the first check says otherwise.
+        let online = Arc::new(AtomicBool::new(true));
+        let stopped = Arc::new(AtomicBool::new(false));
+        let probe_addresses: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(Vec::new()));
+        let recheck = Arc::new(AtomicBool::new(false));
+        let paused = Arc::new(AtomicBool::new(false));
+
+        let thread_online = Arc::clone(&online);
+        let thread_stopped = Arc::clone(&stopped);
+        let thread_addresses = Arc::clone(&probe_addresses);
+        let thread_recheck = Arc::clone(&recheck);
+        let thread_paused = Arc::clone(&paused);
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            while !thread_stopped.load(Ordering::SeqCst) {
+                thread_recheck.store(false, Ordering::SeqCst);
+                if !thread_paused.load(Ordering::SeqCst) {
+                    let addresses = thread_addresses.lock().unwrap().clone();
+                    let reachable = addresses.is_empty()
+                        || addresses
+                            .iter()
+                            .any(|address| probe(address, PROBE_TIMEOUT));
+                    if thread_online.swap(reachable, Ordering::SeqCst) != reachable {
+                        ctx.request_repaint();
+                    }
+                }
+
+                let next_check = Instant::now() + CHECK_INTERVAL;
+                while Instant::now() < next_check
+                    && !thread_stopped.load(Ordering::SeqCst)
+                    && !thread_recheck.load(Ordering::SeqCst)
+                {
+                    thread::sleep(STOP_POLL_INTERVAL);
+                }
+            }
+        });
+
+        Self {
+            online,
+            stopped,
+            probe_addresses,
+            recheck,
+            paused,
+        }
+    }
+
+    // Resuming checks straight away rather than trusting the last result.
+    pub fn set_paused(&self, paused: bool) {
+        if self.paused.swap(paused, Ordering::SeqCst) && !paused {
+            self.recheck.store(true, Ordering::SeqCst);
+        }
+    }
+
+    // The `host:port` addresses of the providers in use.
+    pub fn set_probe_addresses(&self, addresses: Vec<String>) {
+        let mut current = self.probe_addresses.lock().unwrap();
+        if *current != addresses {
+            *current = addresses;
+            self.recheck.store(true, Ordering::SeqCst);
+        }
+    }
+
+    pub fn is_online(&self) -> bool {
+        self.online.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for ConnectivityMonitor {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+    }
+}
+
+// Whether a TCP connection to `address` can be opened in time.
+fn probe(address: &str, timeout: Duration) -> bool {
+    let Ok(addresses) = address.to_socket_addrs() else {
+        return false;
+    };
+    addresses
+        .into_iter()
+        .any(|address| TcpStream::connect_timeout(&address, timeout).is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn probe_tells_open_ports_from_closed_ones() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        assert!(probe(&address, PROBE_TIMEOUT));
+
+        drop(listener);
+        assert!(!probe(&address, PROBE_TIMEOUT));
+        assert!(!probe("not a host name", PROBE_TIMEOUT));
+    }
+}
@@ -0,0 +1,120 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs,
+    hash::{Hash, Hasher},
+    io,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+pub const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct FileStamp {
+    modified: Option<SystemTime>,
+    hash: u64,
+}
+
+impl FileStamp {
+    fn read(path: &Path) -> io::Result<Self> {
+        let modified = fs::metadata(path)?.modified().ok();
+        let mut hasher = DefaultHasher::new();
+        fs::read(path)?.hash(&mut hasher);
+        Ok(Self {
+            modified,
+            hash: hasher.finish(),
+        })
+    }
+}
+
+// Tracks the last version of a file the app read or wrote, so edits made by
+// other programs can be noticed before they are overwritten.
+pub struct FileWatcher {
+    path: PathBuf,
+    stamp: Option<FileStamp>,
+    last_poll: Instant,
+}
+
+impl FileWatcher {
+    pub fn new(path: PathBuf) -> Self {
+        let stamp = FileStamp::read(&path).ok();
+        Self {
+            path,
+            stamp,
+            last_poll: Instant::now(),
+        }
+    }
+
+    // Record the file's current contents as the app's own version.
+    pub fn refresh(&mut self) {
+        self.stamp = FileStamp::read(&self.path).ok();
+    }
+
+    // Like `has_changed`, but checks at most once per `POLL_INTERVAL`.
+    pub fn poll(&mut self) -> bool {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return false;
+        }
+
+        self.last_poll = Instant::now();
+        self.has_changed()
+    }
+
+    pub fn has_changed(&mut self) -> bool {
+        let Some(stamp) = self.stamp else {
+            return false;
+        };
+
+        let modified = fs::metadata(&self.path)
+            .ok()
+            .and_then(|metadata| metadata.modified().ok());
+        if modified == stamp.modified {
+            return false;
+        }
+
+        match FileStamp::read(&self.path) {
+            // Touched but identical, e.g. a sync client rewriting the file.
+            Ok(current) if current.hash == stamp.hash => {
+                self.stamp = Some(current);
+                false
+            }
+            Ok(_) => true,
+            // Missing or unreadable mid-sync; check again on the next poll.
+            Err(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn has_changed_ignores_rewrites_with_identical_contents() {
+        let path = env::temp_dir().join(format!(
+            "media-rating-file-watch-test-{}.xlsx",
+            std::process::id()
+        ));
+        fs::write(&path, b"original").unwrap();
+        let mut watcher = FileWatcher::new(path.clone());
+
+        let later = SystemTime::now() + Duration::from_secs(5);
+        fs::write(&path, b"original").unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert!(!watcher.has_changed());
+
+        fs::write(&path, b"edited elsewhere").unwrap();
+        assert!(watcher.has_changed());
+
+        watcher.refresh();
+        assert!(!watcher.has_changed());
+
+        fs::remove_file(path).ok();
+    }
+}
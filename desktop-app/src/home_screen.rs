@@ -1,7 +1,33 @@
 use eframe::egui;
+use native_dialog::FileDialog;
 use rand::{seq::SliceRandom, thread_rng};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
 
-use crate::{app::AppAction, image_store::ImageStore, model::Model};
+use crate::{
+    app::AppAction, bulk_fetch::BulkFetch, bundle::BUNDLE_EXTENSION, connectivity::Connection,
+    image_input, image_store::ImageStore, model::Model,
+};
+
+// Entries on each side of the selection whose images are preloaded.
+const NEIGHBOUR_PREFETCH: usize = 2;
+const LIST_THUMBNAIL_SIZE: egui::Vec2 = egui::vec2(32.0, 40.0);
+const GALLERY_TILE_SIZE: egui::Vec2 = egui::vec2(95.0, 118.0);
+const GALLERY_TITLE_HEIGHT: f32 = 18.0;
+// How close to the edge of the list a dragged entry starts scrolling it.
+const DRAG_SCROLL_MARGIN: f32 = 30.0;
+const DRAG_SCROLL_SPEED: f32 = 8.0;
+
+// The drag-and-drop payload: the index of the entry being dragged.
+struct DraggedEntry(usize);
+
+// How the entries of a category are laid out on the left.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HomeView {
+    #[default]
+    List,
+    Gallery,
+}
 
 pub struct HomeScreen {
     selected_category: Option<String>,
@@ -9,25 +35,52 @@ pub struct HomeScreen {
     previous_selected_category: Option<String>,
     new_entry_box: String,
     selected_entry: Option<usize>,
+    // Every selected entry, including the one shown on the right, for bulk
+    // actions.
+    selection: BTreeSet<usize>,
+    // Where a shift-click selection runs from.
+    selection_anchor: Option<usize>,
     search_entry_box: String,
     rename_entry_box: String,
     focus_index: Option<usize>,
+    view: HomeView,
+    // Tiles per gallery row when last drawn, for moving up and down.
+    gallery_columns: usize,
+    focus_rename: bool,
 }
 
 impl HomeScreen {
-    pub fn new(selected_category: Option<String>) -> Self {
+    pub fn new(selected_category: Option<String>, view: HomeView) -> Self {
         Self {
             previous_selected_category: selected_category.clone(),
             selected_category,
             selected_switch_category: None,
             new_entry_box: String::new(),
             selected_entry: None,
+            selection: BTreeSet::new(),
+            selection_anchor: None,
             search_entry_box: String::new(),
             rename_entry_box: String::new(),
             focus_index: None,
+            view,
+            gallery_columns: 1,
+            focus_rename: false,
         }
     }
 
+    pub fn selected_category(&self) -> Option<&str> {
+        self.selected_category.as_deref()
+    }
+
+    // The category and name of the entry shown on the right, if any.
+    pub fn selected_entry(&self, model: &Model) -> Option<(String, String)> {
+        let category = self.selected_category.clone()?;
+        let entry = model
+            .get_entry(&category, self.selected_entry?)?
+            .to_string();
+        Some((category, entry))
+    }
+
     pub fn category_deleted(&mut self, category: &str) {
         if self.selected_category.as_deref() == Some(category) {
             self.selected_category = None;
@@ -40,7 +93,28 @@ impl HomeScreen {
         }
     }
 
-    pub fn menu_bar(&mut self, ui: &mut egui::Ui, model: &Model) -> Vec<AppAction> {
+    pub fn retain_categories(&mut self, categories: &[String]) {
+        for category in [
+            self.selected_category.clone(),
+            self.selected_switch_category.clone(),
+        ]
+        .into_iter()
+        .flatten()
+        {
+            if !categories.contains(&category) {
+                self.category_deleted(&category);
+            }
+        }
+    }
+
+    pub fn menu_bar(
+        &mut self,
+        ui: &mut egui::Ui,
+        model: &Model,
+        bulk_fetch: Option<&BulkFetch>,
+        connection: Connection,
+        undo: Option<&str>,
+    ) -> Vec<AppAction> {
         let mut actions = Vec::new();
 
         ui.horizontal(|ui| {
@@ -70,6 +144,16 @@ impl HomeScreen {
                     self.previous_selected_category
                         .clone_from(&self.selected_category);
                 }
+
+                if let Some(description) = undo {
+                    if ui
+                        .button("Undo")
+                        .on_hover_text(format!("Undo {description}"))
+                        .clicked()
+                    {
+                        actions.push(AppAction::Undo);
+                    }
+                }
             });
 
             ui.add_space(10.0);
@@ -105,6 +189,74 @@ impl HomeScreen {
                     self.new_entry_box.clear();
                 }
             });
+
+            ui.vertical(|ui| {
+                if ui.button("Export Bundle").clicked() {
+                    if let Some(path) = FileDialog::new()
+                        .add_filter("Media Rating bundle", &[BUNDLE_EXTENSION])
+                        .set_filename("Media Ratings.mediarating")
+                        .show_save_single_file()
+                        .ok()
+                        .flatten()
+                    {
+                        actions.push(AppAction::ExportBundle(path));
+                    }
+                }
+
+                ui.horizontal(|ui| {
+                    if ui.button("Image Settings").clicked() {
+                        if let Some(category) = self.selected_category.clone() {
+                            actions.push(AppAction::EditImageProvider { category });
+                        }
+                    }
+                    if ui.button("Check Images").clicked() {
+                        actions.push(AppAction::CheckImages);
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    let mut work_offline = connection == Connection::WorkingOffline;
+                    if ui
+                        .checkbox(&mut work_offline, "Work Offline")
+                        .on_hover_text("Add entries with title cards and fetch their images later")
+                        .changed()
+                    {
+                        actions.push(AppAction::SetWorkOffline(work_offline));
+                    }
+                    if connection == Connection::Offline {
+                        ui.label(egui::RichText::new("No connection").weak());
+                    }
+                });
+
+                match bulk_fetch {
+                    Some(bulk_fetch) if !bulk_fetch.is_finished() => {
+                        ui.horizontal(|ui| {
+                            let (done, total) = bulk_fetch.progress();
+                            ui.add(
+                                egui::ProgressBar::new(done as f32 / total as f32)
+                                    .desired_width(120.0)
+                                    .text(format!("{done}/{total}")),
+                            );
+                            if ui.small_button("Cancel").clicked() {
+                                actions.push(AppAction::CancelBulkFetch);
+                            }
+                        });
+                    }
+                    _ => {
+                        ui.horizontal(|ui| {
+                            if ui.button("Fetch Missing Images").clicked() {
+                                actions.push(AppAction::StartBulkFetch);
+                            }
+                            if bulk_fetch.is_some_and(|bulk_fetch| {
+                                !bulk_fetch.fetched.is_empty() || !bulk_fetch.failed.is_empty()
+                            }) && ui.button("Review").clicked()
+                            {
+                                actions.push(AppAction::ReviewBulkFetch);
+                            }
+                        });
+                    }
+                }
+            });
         });
 
         ui.add_space(10.0);
@@ -128,36 +280,48 @@ impl HomeScreen {
         ui.columns(2, |columns| {
             columns[0].set_width(370.0);
             columns[0].vertical(|ui| {
-                ui.text_edit_singleline(&mut self.search_entry_box);
-
-                ui.with_layout(egui::Layout::top_down_justified(egui::Align::LEFT), |ui| {
-                    egui::ScrollArea::vertical().show(ui, |ui| {
-                        for (index, entry) in
-                            model.get_category_entries(&category).iter().enumerate()
-                        {
-                            if entry
-                                .to_lowercase()
-                                .contains(&self.search_entry_box.to_lowercase())
-                            {
-                                let label = ui.selectable_label(
-                                    self.selected_entry == Some(index),
-                                    format!("{:>3}\t\t{}", index + 1, entry),
-                                );
+                ui.horizontal(|ui| {
+                    let view = self.view;
+                    ui.selectable_value(&mut self.view, HomeView::List, "List");
+                    ui.selectable_value(&mut self.view, HomeView::Gallery, "Gallery");
+                    if self.view != view {
+                        actions.push(AppAction::SetHomeView(self.view));
+                        self.focus_index = self.selected_entry;
+                    }
+                    ui.text_edit_singleline(&mut self.search_entry_box);
+                });
 
-                                if Some(index) == self.focus_index {
-                                    let rect = label.rect;
-                                    ui.scroll_to_rect(rect, Some(egui::Align::Center));
-                                    self.focus_index = None;
-                                }
+                if self.selection.len() > 1 {
+                    self.bulk_actions(ui, model, &category, &mut actions);
+                }
 
-                                if label.clicked() {
-                                    self.selected_entry = Some(index);
-                                    self.rename_entry_box.clone_from(entry);
-                                }
-                            }
-                        }
-                    });
-                });
+                let stats = images.cache_stats();
+                ui.label(
+                    egui::RichText::new(format!(
+                        "Image cache: {:.1} / {} MB, {} evicted",
+                        stats.used_bytes as f32 / (1024.0 * 1024.0),
+                        stats.budget_bytes / (1024 * 1024),
+                        stats.evictions
+                    ))
+                    .small()
+                    .weak(),
+                );
+
+                let visible = self.visible_entries(model.get_category_entries(&category));
+
+                match self.view {
+                    HomeView::List => {
+                        self.entry_list(ui, model, &category, &visible, images, &mut actions)
+                    }
+                    HomeView::Gallery => self.entry_gallery(
+                        ui,
+                        model,
+                        &category,
+                        &visible,
+                        images,
+                        &mut actions,
+                    ),
+                }
             });
 
             columns[1].vertical(|ui| {
@@ -171,7 +335,34 @@ impl HomeScreen {
                 };
 
                 let texture = images.get_entry_texture(&entry, &category, ctx);
-                ui.image(&texture);
+                ui.add(egui::Image::new(&texture).max_width(ui.available_width()));
+                if let Some(metadata) = model.entry_metadata(&category, &entry) {
+                    ui.label(format!("{} - {}", metadata.summary(), metadata.source));
+                }
+                if let Some(provenance) = images.provenance(&category, &entry) {
+                    let summary = format!("Image from {}", provenance.summary());
+                    ui.label(egui::RichText::new(summary).small().weak());
+                    match &provenance.source {
+                        Some(source) if image_input::is_web_url(source) => {
+                            ui.hyperlink_to(egui::RichText::new(source).small(), source);
+                        }
+                        Some(source) => {
+                            ui.label(egui::RichText::new(source).small().weak());
+                        }
+                        None => {}
+                    }
+                }
+
+                let entries = model.get_category_entries(&category);
+                let neighbours = entry_index.saturating_sub(NEIGHBOUR_PREFETCH)
+                    ..(entry_index + NEIGHBOUR_PREFETCH + 1).min(entries.len());
+                images.prefetch(
+                    &category,
+                    neighbours
+                        .filter(|index| *index != entry_index)
+                        .map(|index| entries[index].as_str()),
+                    ctx,
+                );
 
                 ui.horizontal(|ui| {
                     if ui.button("Get New Image").clicked()
@@ -184,6 +375,24 @@ impl HomeScreen {
                         self.focus_index = self.selected_entry;
                     }
 
+                    if ui
+                        .button("Paste Image")
+                        .on_hover_text("Use an image or image link from the clipboard. Images can also be dropped onto the window.")
+                        .clicked()
+                    {
+                        actions.push(AppAction::PasteEntryImage {
+                            category: category.clone(),
+                            entry: entry.clone(),
+                        });
+                    }
+
+                    if ui.button("Look Up Details").clicked() {
+                        actions.push(AppAction::LookUpMetadata {
+                            category: category.clone(),
+                            entry: entry.clone(),
+                        });
+                    }
+
                     if ui.button("Delete Entry").clicked() {
                         actions.push(AppAction::DeleteEntry {
                             category: category.clone(),
@@ -216,7 +425,10 @@ impl HomeScreen {
                 });
 
                 ui.horizontal(|ui| {
-                    ui.text_edit_singleline(&mut self.rename_entry_box);
+                    let rename = ui.text_edit_singleline(&mut self.rename_entry_box);
+                    if std::mem::take(&mut self.focus_rename) {
+                        rename.request_focus();
+                    }
 
                     ui.vertical(|ui| {
                         ui.horizontal(|ui| {
@@ -244,6 +456,242 @@ impl HomeScreen {
         actions
     }
 
+    fn entry_list(
+        &mut self,
+        ui: &mut egui::Ui,
+        model: &Model,
+        category: &str,
+        visible: &[usize],
+        images: &mut ImageStore,
+        actions: &mut Vec<AppAction>,
+    ) {
+        let ctx = ui.ctx().clone();
+        let entries = model.get_category_entries(category);
+        let row_height = LIST_THUMBNAIL_SIZE.y;
+        let mut scroll_area = egui::ScrollArea::vertical();
+        if let Some(focus_index) = self.focus_index.take() {
+            if let Some(row) = visible.iter().position(|index| *index == focus_index) {
+                let row_stride = row_height + ui.spacing().item_spacing.y;
+                let offset =
+                    row as f32 * row_stride + row_height / 2.0 - ui.available_height() / 2.0;
+                scroll_area = scroll_area.vertical_scroll_offset(offset.max(0.0));
+            }
+        }
+
+        // Only rows in view are laid out, so only their thumbnails load.
+        scroll_area.show_rows(ui, row_height, visible.len(), |ui, rows| {
+            for index in visible[rows].iter().copied() {
+                let entry = &entries[index];
+                ui.horizontal(|ui| {
+                    let thumbnail = images.get_entry_thumbnail(entry, category, &ctx);
+                    // Centred in a fixed slot so titles line up whatever
+                    // the category's aspect ratio.
+                    ui.allocate_ui_with_layout(
+                        LIST_THUMBNAIL_SIZE,
+                        egui::Layout::centered_and_justified(egui::Direction::TopDown),
+                        |ui| {
+                            ui.add(egui::Image::new(&thumbnail).max_size(LIST_THUMBNAIL_SIZE));
+                        },
+                    );
+
+                    let label = ui
+                        .add_sized(
+                            [ui.available_width(), row_height],
+                            egui::SelectableLabel::new(
+                                self.selection.contains(&index),
+                                format!("{:>3}\t\t{}", index + 1, entry),
+                            ),
+                        )
+                        .interact(egui::Sense::drag());
+
+                    if label.clicked() {
+                        self.click_entry(ui, model, category, index);
+                    }
+                    drag_and_drop(ui, &label, category, index, entry, false, actions);
+                    label.context_menu(|ui| self.entry_menu(ui, model, category, index, actions));
+                });
+            }
+            scroll_while_dragging(ui);
+        });
+    }
+
+    fn entry_gallery(
+        &mut self,
+        ui: &mut egui::Ui,
+        model: &Model,
+        category: &str,
+        visible: &[usize],
+        images: &mut ImageStore,
+        actions: &mut Vec<AppAction>,
+    ) {
+        let spacing = ui.spacing().item_spacing;
+        let columns = ((ui.available_width() + spacing.x) / (GALLERY_TILE_SIZE.x + spacing.x))
+            .floor()
+            .max(1.0) as usize;
+        self.gallery_columns = columns;
+
+        let row_height = GALLERY_TILE_SIZE.y + GALLERY_TITLE_HEIGHT;
+        let rows = visible.len().div_ceil(columns);
+        let mut scroll_area = egui::ScrollArea::vertical();
+        if let Some(focus_index) = self.focus_index.take() {
+            if let Some(position) = visible.iter().position(|index| *index == focus_index) {
+                let row_stride = row_height + spacing.y;
+                let offset = (position / columns) as f32 * row_stride + row_height / 2.0
+                    - ui.available_height() / 2.0;
+                scroll_area = scroll_area.vertical_scroll_offset(offset.max(0.0));
+            }
+        }
+
+        // As with the list, only rows in view load their thumbnails.
+        scroll_area.show_rows(ui, row_height, rows, |ui, rows| {
+            for row in rows {
+                ui.horizontal(|ui| {
+                    for index in visible.iter().skip(row * columns).take(columns).copied() {
+                        self.gallery_tile(ui, model, category, index, images, actions);
+                    }
+                });
+            }
+            scroll_while_dragging(ui);
+        });
+    }
+
+    // A thumbnail with the entry's rank in a badge and its title beneath.
+    fn gallery_tile(
+        &mut self,
+        ui: &mut egui::Ui,
+        model: &Model,
+        category: &str,
+        index: usize,
+        images: &mut ImageStore,
+        actions: &mut Vec<AppAction>,
+    ) {
+        let Some(entry) = model.get_entry(category, index) else {
+            return;
+        };
+        let (rect, response) = ui.allocate_exact_size(
+            egui::vec2(
+                GALLERY_TILE_SIZE.x,
+                GALLERY_TILE_SIZE.y + GALLERY_TITLE_HEIGHT,
+            ),
+            egui::Sense::click_and_drag(),
+        );
+
+        if ui.is_rect_visible(rect) {
+            let thumbnail = images.get_entry_thumbnail(entry, category, ui.ctx());
+            let image_size = thumbnail.size_vec2();
+            let scale =
+                (GALLERY_TILE_SIZE.x / image_size.x).min(GALLERY_TILE_SIZE.y / image_size.y);
+            let slot = egui::Rect::from_min_size(rect.min, GALLERY_TILE_SIZE);
+            let image_rect = egui::Rect::from_center_size(slot.center(), image_size * scale);
+            egui::Image::new(&thumbnail).paint_at(ui, image_rect);
+
+            let visuals = ui.visuals();
+            let painter = ui.painter();
+            if self.selection.contains(&index) {
+                painter.rect_stroke(image_rect.expand(2.0), 3.0, visuals.selection.stroke);
+            } else if response.hovered() {
+                painter.rect_stroke(
+                    image_rect.expand(1.0),
+                    3.0,
+                    visuals.widgets.hovered.fg_stroke,
+                );
+            }
+
+            let font = egui::FontId::proportional(12.0);
+            let rank =
+                painter.layout_no_wrap((index + 1).to_string(), font.clone(), egui::Color32::WHITE);
+            let badge = egui::Rect::from_min_size(
+                image_rect.min + egui::vec2(3.0, 3.0),
+                rank.size() + egui::vec2(8.0, 4.0),
+            );
+            painter.rect_filled(badge, 4.0, egui::Color32::from_black_alpha(180));
+            painter.galley(badge.min + egui::vec2(4.0, 2.0), rank, egui::Color32::WHITE);
+
+            let mut title = egui::text::LayoutJob::simple_singleline(
+                entry.to_string(),
+                font,
+                visuals.text_color(),
+            );
+            title.wrap = egui::text::TextWrapping::truncate_at_width(rect.width());
+            let title = painter.layout_job(title);
+            let title_pos = egui::pos2(
+                rect.center().x - title.size().x / 2.0,
+                slot.bottom() + (GALLERY_TITLE_HEIGHT - title.size().y) / 2.0,
+            );
+            painter.galley(title_pos, title, visuals.text_color());
+        }
+
+        let response = response.on_hover_text(entry);
+        if response.clicked() {
+            self.click_entry(ui, model, category, index);
+        }
+        drag_and_drop(ui, &response, category, index, entry, true, actions);
+        response.context_menu(|ui| self.entry_menu(ui, model, category, index, actions));
+    }
+
+    // The entry actions of the detail panel, for a right-clicked row or tile.
+    fn entry_menu(
+        &mut self,
+        ui: &mut egui::Ui,
+        model: &Model,
+        category: &str,
+        index: usize,
+        actions: &mut Vec<AppAction>,
+    ) {
+        let Some(entry) = model.get_entry(category, index) else {
+            return;
+        };
+
+        if ui.button("Rename").clicked() {
+            self.select_entry(index, entry);
+            self.focus_rename = true;
+            ui.close_menu();
+        }
+
+        if ui.button("Get New Image").clicked() {
+            actions.push(AppAction::RefreshImage {
+                category: category.to_string(),
+                entry: entry.to_string(),
+            });
+            self.focus_index = Some(index);
+            ui.close_menu();
+        }
+
+        if ui.button("Rerank").clicked() {
+            actions.push(AppAction::StartRerankEntry {
+                category: category.to_string(),
+                index,
+            });
+            self.clear_entry_selection();
+            ui.close_menu();
+        }
+
+        ui.menu_button("Switch To", |ui| {
+            for other in model.get_categories() {
+                if other != category && ui.button(&other).clicked() {
+                    actions.push(AppAction::StartSwitchCategory {
+                        from_category: category.to_string(),
+                        from_index: index,
+                        to_category: other,
+                        entry: entry.to_string(),
+                    });
+                    self.clear_entry_selection();
+                    ui.close_menu();
+                }
+            }
+        });
+
+        ui.separator();
+        if ui.button("Delete").clicked() {
+            actions.push(AppAction::DeleteEntry {
+                category: category.to_string(),
+                index,
+            });
+            self.clear_entry_selection();
+            ui.close_menu();
+        }
+    }
+
     fn handle_keyboard(
         &mut self,
         ctx: &egui::Context,
@@ -251,30 +699,48 @@ impl HomeScreen {
         category: &str,
         actions: &mut Vec<AppAction>,
     ) {
-        if ctx.input(|i| i.key_pressed(egui::Key::ArrowDown)) {
-            let len = model.get_category_entries(category).len();
-            if len > 0 {
-                let next = match self.selected_entry {
-                    Some(i) => (i + 1).min(len - 1),
-                    None => 0,
-                };
-                self.selected_entry = Some(next);
-                self.rename_entry_box = model.get_category_entries(category)[next].clone();
+        // Up and down move a whole row of the gallery; left and right only
+        // apply there, and not while typing.
+        let row = match self.view {
+            HomeView::List => 1,
+            HomeView::Gallery => self.gallery_columns as isize,
+        };
+        let typing = ctx.memory(|memory| memory.focused().is_some());
+        let mut delta = 0;
+        ctx.input(|i| {
+            if i.key_pressed(egui::Key::ArrowDown) {
+                delta += row;
+            }
+            if i.key_pressed(egui::Key::ArrowUp) {
+                delta -= row;
+            }
+            if self.view == HomeView::Gallery && !typing {
+                if i.key_pressed(egui::Key::ArrowRight) {
+                    delta += 1;
+                }
+                if i.key_pressed(egui::Key::ArrowLeft) {
+                    delta -= 1;
+                }
+            }
+        });
+        if delta != 0 {
+            let entries = model.get_category_entries(category);
+            let visible = self.visible_entries(entries);
+            if let Some(next) = step_selection(self.selected_entry, delta, &visible) {
+                // Shift extends the selection as with clicks.
+                match self.selection_anchor {
+                    Some(anchor) if ctx.input(|i| i.modifiers.shift) => {
+                        self.select_range(model, category, anchor, next);
+                        self.show_entry(next, &entries[next]);
+                    }
+                    _ => self.select_entry(next, &entries[next]),
+                }
                 self.focus_index = Some(next);
             }
         }
 
-        if ctx.input(|i| i.key_pressed(egui::Key::ArrowUp)) {
-            let len = model.get_category_entries(category).len();
-            if len > 0 {
-                let prev = match self.selected_entry {
-                    Some(i) => i.saturating_sub(1),
-                    None => 0,
-                };
-                self.selected_entry = Some(prev);
-                self.rename_entry_box = model.get_category_entries(category)[prev].clone();
-                self.focus_index = Some(prev);
-            }
+        if !typing && ctx.input(|i| i.modifiers.command && i.key_pressed(egui::Key::Z)) {
+            actions.push(AppAction::Undo);
         }
 
         if ctx.input(|i| i.key_pressed(egui::Key::Enter)) {
@@ -305,10 +771,316 @@ impl HomeScreen {
         }
     }
 
-    fn clear_entry_selection(&mut self) {
+    // Each acts on every selected entry at once and can be undone as one.
+    fn bulk_actions(
+        &mut self,
+        ui: &mut egui::Ui,
+        model: &Model,
+        category: &str,
+        actions: &mut Vec<AppAction>,
+    ) {
+        let entries: Vec<String> = self
+            .selection
+            .iter()
+            .filter_map(|index| model.get_entry(category, *index))
+            .map(str::to_string)
+            .collect();
+
+        ui.horizontal_wrapped(|ui| {
+            ui.label(format!("{} selected", entries.len()));
+
+            if ui.button("Delete").clicked() {
+                actions.push(AppAction::RequestDeleteEntries {
+                    category: category.to_string(),
+                    entries: entries.clone(),
+                });
+            }
+
+            ui.menu_button("Move To", |ui| {
+                for other in model.get_categories() {
+                    if other == category {
+                        continue;
+                    }
+                    ui.menu_button(&other, |ui| {
+                        for (label, rank) in [("Add At The End", false), ("Rank Each", true)] {
+                            if ui.button(label).clicked() {
+                                actions.push(AppAction::MoveEntries {
+                                    from_category: category.to_string(),
+                                    entries: entries.clone(),
+                                    to_category: other.clone(),
+                                    rank,
+                                });
+                                ui.close_menu();
+                            }
+                        }
+                    });
+                }
+            });
+
+            if ui.button("Refresh Images").clicked() {
+                actions.push(AppAction::RefreshImages {
+                    category: category.to_string(),
+                    entries: entries.clone(),
+                });
+            }
+
+            if ui.button("Export").clicked() {
+                if let Some(path) = FileDialog::new()
+                    .add_filter("Media Rating bundle", &[BUNDLE_EXTENSION])
+                    .set_filename("Selection.mediarating")
+                    .show_save_single_file()
+                    .ok()
+                    .flatten()
+                {
+                    actions.push(AppAction::ExportEntries {
+                        category: category.to_string(),
+                        entries: entries.clone(),
+                        path,
+                    });
+                }
+            }
+
+            if ui.button("Clear").clicked() {
+                self.entries_changed();
+            }
+        });
+    }
+
+    // Keeps the selection on the same entries after a drag moved one.
+    pub fn entry_moved(&mut self, category: &str, from_index: usize, to_index: usize) {
+        if self.selected_category.as_deref() != Some(category) {
+            return;
+        }
+        if let Some(selected) = self.selected_entry {
+            let selected = index_after_move(selected, from_index, to_index);
+            self.selected_entry = Some(selected);
+            self.focus_index = Some(selected);
+        }
+        self.selection = self
+            .selection
+            .iter()
+            .map(|index| index_after_move(*index, from_index, to_index))
+            .collect();
+        self.selection_anchor = self
+            .selection_anchor
+            .map(|index| index_after_move(index, from_index, to_index));
+    }
+
+    // After entries were deleted or moved in bulk the indices no longer hold.
+    pub fn entries_changed(&mut self) {
         self.selected_entry = None;
+        self.selection.clear();
+        self.selection_anchor = None;
         self.rename_entry_box.clear();
+    }
+
+    // Plain clicks select one entry, command-clicks add or remove one and
+    // shift-clicks select the run from the last entry clicked.
+    fn click_entry(&mut self, ui: &egui::Ui, model: &Model, category: &str, index: usize) {
+        let Some(entry) = model.get_entry(category, index) else {
+            return;
+        };
+        let modifiers = ui.input(|i| i.modifiers);
+
+        if modifiers.shift {
+            let anchor = self.selection_anchor.unwrap_or(index);
+            self.select_range(model, category, anchor, index);
+            self.show_entry(index, entry);
+        } else if modifiers.command {
+            self.selection_anchor = Some(index);
+            if self.selection.insert(index) {
+                self.show_entry(index, entry);
+            } else {
+                self.selection.remove(&index);
+                // The panel moves on to another selected entry, if any.
+                match self.selection.first().copied() {
+                    Some(first) if self.selected_entry == Some(index) => {
+                        self.show_entry(first, model.get_entry(category, first).unwrap_or(""));
+                    }
+                    Some(_) => {}
+                    None => {
+                        self.selected_entry = None;
+                        self.rename_entry_box.clear();
+                    }
+                }
+            }
+        } else {
+            self.select_entry(index, entry);
+        }
+    }
+
+    // Indices of the entries that match the search, in order.
+    fn visible_entries(&self, entries: &[String]) -> Vec<usize> {
+        let search = self.search_entry_box.to_lowercase();
+        (0..entries.len())
+            .filter(|index| entries[*index].to_lowercase().contains(&search))
+            .collect()
+    }
+
+    // Entries between the two, both included, that match the search.
+    fn select_range(&mut self, model: &Model, category: &str, anchor: usize, index: usize) {
+        let search = self.search_entry_box.to_lowercase();
+        let entries = model.get_category_entries(category);
+        self.selection = (anchor.min(index)..=anchor.max(index).min(entries.len() - 1))
+            .filter(|index| entries[*index].to_lowercase().contains(&search))
+            .collect();
+    }
+
+    fn select_entry(&mut self, index: usize, entry: &str) {
+        self.selection = BTreeSet::from([index]);
+        self.selection_anchor = Some(index);
+        self.show_entry(index, entry);
+    }
+
+    // Shows the entry on the right without changing the rest of the selection.
+    fn show_entry(&mut self, index: usize, entry: &str) {
+        self.selected_entry = Some(index);
+        self.rename_entry_box = entry.to_string();
+    }
+
+    fn clear_entry_selection(&mut self) {
+        self.entries_changed();
         self.search_entry_box.clear();
         self.focus_index = None;
     }
 }
+
+// Makes a row or tile draggable onto the others. While one is held over it, a
+// line shows which side the dragged entry will land on.
+fn drag_and_drop(
+    ui: &egui::Ui,
+    response: &egui::Response,
+    category: &str,
+    index: usize,
+    entry: &str,
+    horizontal: bool,
+    actions: &mut Vec<AppAction>,
+) {
+    response.dnd_set_drag_payload(DraggedEntry(index));
+    if response.dragged() {
+        ui.ctx().set_cursor_icon(egui::CursorIcon::Grabbing);
+        egui::show_tooltip_at_pointer(ui.ctx(), ui.layer_id(), response.id.with("drag"), |ui| {
+            ui.label(entry);
+        });
+    }
+
+    let Some(dragged) = response.dnd_hover_payload::<DraggedEntry>() else {
+        return;
+    };
+    let from_index = dragged.0;
+    if from_index == index {
+        return;
+    }
+
+    // An entry dragged up lands before this one, dragged down after it.
+    let rect = response.rect;
+    let stroke = egui::Stroke::new(2.0, ui.visuals().selection.bg_fill);
+    let before = from_index > index;
+    let edge = match (horizontal, before) {
+        (false, true) => [rect.left_top(), rect.right_top()],
+        (false, false) => [rect.left_bottom(), rect.right_bottom()],
+        (true, true) => [rect.left_top(), rect.left_bottom()],
+        (true, false) => [rect.right_top(), rect.right_bottom()],
+    };
+    ui.painter().line_segment(edge, stroke);
+
+    if response.dnd_release_payload::<DraggedEntry>().is_some() {
+        actions.push(AppAction::RequestMoveEntry {
+            category: category.to_string(),
+            from_index,
+            to_index: index,
+        });
+    }
+}
+
+// Scrolls while an entry is dragged near the top or bottom of the view, so it
+// can be dropped on rows not yet shown.
+fn scroll_while_dragging(ui: &mut egui::Ui) {
+    if !egui::DragAndDrop::has_payload_of_type::<DraggedEntry>(ui.ctx()) {
+        return;
+    }
+    let Some(pointer) = ui.ctx().pointer_hover_pos() else {
+        return;
+    };
+    let view = ui.clip_rect();
+    if !view.x_range().contains(pointer.x)
+        || pointer.y < view.top() - DRAG_SCROLL_MARGIN
+        || pointer.y > view.bottom() + DRAG_SCROLL_MARGIN
+    {
+        return;
+    }
+
+    let delta = if pointer.y < view.top() + DRAG_SCROLL_MARGIN {
+        DRAG_SCROLL_SPEED
+    } else if pointer.y > view.bottom() - DRAG_SCROLL_MARGIN {
+        -DRAG_SCROLL_SPEED
+    } else {
+        return;
+    };
+    ui.scroll_with_delta(egui::vec2(0.0, delta));
+    ui.ctx().request_repaint();
+}
+
+// Where the entry at `index` ends up after another moves from `from_index` to
+// `to_index`, as `Model::move_entry` does it.
+fn index_after_move(index: usize, from_index: usize, to_index: usize) -> usize {
+    if index == from_index {
+        to_index
+    } else if from_index < index && index <= to_index {
+        index - 1
+    } else if to_index <= index && index < from_index {
+        index + 1
+    } else {
+        index
+    }
+}
+
+// The entry `delta` places from the selection among the visible entries,
+// kept within them. With nothing visible selected the first one is chosen.
+fn step_selection(selected: Option<usize>, delta: isize, visible: &[usize]) -> Option<usize> {
+    let last = visible.len().checked_sub(1)?;
+    let position = match selected.and_then(|index| visible.iter().position(|v| *v == index)) {
+        Some(position) => position.saturating_add_signed(delta).min(last),
+        None => 0,
+    };
+    Some(visible[position])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selection_steps_stay_within_the_category() {
+        let all: Vec<usize> = (0..10).collect();
+        assert_eq!(step_selection(None, 4, &all), Some(0));
+        assert_eq!(step_selection(Some(2), 4, &all), Some(6));
+        assert_eq!(step_selection(Some(8), 4, &all), Some(9));
+        assert_eq!(step_selection(Some(2), -4, &all), Some(0));
+        assert_eq!(step_selection(Some(3), -1, &all), Some(2));
+        assert_eq!(step_selection(Some(3), 1, &[]), None);
+
+        // With a search, steps go through the matching entries only.
+        let matching = [1, 4, 5, 8, 9];
+        assert_eq!(step_selection(Some(4), 2, &matching), Some(8));
+        assert_eq!(step_selection(Some(9), -3, &matching), Some(4));
+        assert_eq!(step_selection(Some(8), 4, &matching), Some(9));
+        assert_eq!(step_selection(Some(3), 1, &matching), Some(1));
+    }
+
+    #[test]
+    fn indices_follow_a_moved_entry() {
+        let mut entries = vec!["A", "B", "C", "D", "E"];
+        for (from_index, to_index) in [(1, 3), (4, 0), (2, 2)] {
+            let before = entries.clone();
+            let moved = entries.remove(from_index);
+            entries.insert(to_index, moved);
+            for (index, entry) in before.iter().enumerate() {
+                assert_eq!(
+                    entries[index_after_move(index, from_index, to_index)],
+                    *entry
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use image::{imageops::FilterType, DynamicImage, Rgba, RgbaImage};
+
+pub const MAX_ZOOM: f32 = 4.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CropMode {
+    // Cut a frame of the target aspect out of the image. `zoom` 1.0 is the
+    // largest frame that fits; `center` is the frame centre as a fraction of
+    // the image size.
+    Fill { zoom: f32, center: [f32; 2] },
+    // Fit the whole image inside the target and pad the rest.
+    Letterbox { background: [u8; 3] },
+}
+
+impl Default for CropMode {
+    fn default() -> Self {
+        CropMode::Fill {
+            zoom: 1.0,
+            center: [0.5, 0.5],
+        }
+    }
+}
+
+// A pixel rectangle within the source image.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CropFrame {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+// The part of an image `CropMode::Fill` keeps, clamped to stay inside it.
+pub fn fill_frame(
+    [image_width, image_height]: [u32; 2],
+    [target_width, target_height]: [u32; 2],
+    zoom: f32,
+    center: [f32; 2],
+) -> CropFrame {
+    let (image_width, image_height) = (image_width as f32, image_height as f32);
+    let target_aspect = target_width as f32 / target_height as f32;
+
+    let (mut width, mut height) = if image_width / image_height > target_aspect {
+        (image_height * target_aspect, image_height)
+    } else {
+        (image_width, image_width / target_aspect)
+    };
+    let zoom = zoom.clamp(1.0, MAX_ZOOM);
+    width /= zoom;
+    height /= zoom;
+
+    let x = (center[0] * image_width - width / 2.0).clamp(0.0, image_width - width);
+    let y = (center[1] * image_height - height / 2.0).clamp(0.0, image_height - height);
+    CropFrame {
+        x,
+        y,
+        width,
+        height,
+    }
+}
+
+// Produce an image of exactly the target size without distorting the source.
+pub fn apply_crop(image: &DynamicImage, mode: CropMode, target: [u32; 2]) -> DynamicImage {
+    let [target_width, target_height] = target;
+    match mode {
+        CropMode::Fill { zoom, center } => {
+            let frame = fill_frame([image.width(), image.height()], target, zoom, center);
+            image
+                .crop_imm(
+                    frame.x.round() as u32,
+                    frame.y.round() as u32,
+                    (frame.width.round() as u32).max(1),
+                    (frame.height.round() as u32).max(1),
+                )
+                .resize_exact(target_width, target_height, FilterType::CatmullRom)
+        }
+        CropMode::Letterbox { background } => {
+            let fitted = image.resize(target_width, target_height, FilterType::CatmullRom);
+            let [red, green, blue] = background;
+            let mut canvas =
+                RgbaImage::from_pixel(target_width, target_height, Rgba([red, green, blue, 255]));
+            image::imageops::overlay(
+                &mut canvas,
+                &fitted.to_rgba8(),
+                ((target_width - fitted.width()) / 2).into(),
+                ((target_height - fitted.height()) / 2).into(),
+            );
+            DynamicImage::ImageRgba8(canvas)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fill_frame_keeps_target_aspect_inside_image() {
+        // A landscape cover cropped to a portrait frame.
+        let frame = fill_frame([1600, 900], [380, 475], 1.0, [0.5, 0.5]);
+        assert_eq!(frame.height, 900.0);
+        assert_eq!(frame.width, 720.0);
+        assert_eq!(frame.x, 440.0);
+
+        // Panning past the edge stops at the edge.
+        let frame = fill_frame([1600, 900], [380, 475], 2.0, [1.0, 0.0]);
+        assert_eq!((frame.width, frame.height), (360.0, 450.0));
+        assert_eq!((frame.x, frame.y), (1240.0, 0.0));
+    }
+
+    #[test]
+    fn letterbox_pads_with_background_colour() {
+        let square =
+            DynamicImage::ImageRgba8(RgbaImage::from_pixel(100, 100, Rgba([255, 255, 255, 255])));
+        let result = apply_crop(
+            &square,
+            CropMode::Letterbox {
+                background: [10, 20, 30],
+            },
+            [40, 80],
+        )
+        .to_rgba8();
+
+        assert_eq!(result.dimensions(), (40, 80));
+        assert_eq!(result.get_pixel(20, 2), &Rgba([10, 20, 30, 255]));
+        assert_eq!(result.get_pixel(20, 40), &Rgba([255, 255, 255, 255]));
+    }
+}
@@ -0,0 +1,113 @@
+use eframe::egui;
+use image::DynamicImage;
+
+use crate::image_provenance::ImageProvenance;
+
+// An image handed to the app other than through a search.
+pub enum ImageInput {
+    Image(DynamicImage, ImageProvenance),
+    // A link still to be downloaded.
+    Url(String),
+}
+
+pub fn is_web_url(text: &str) -> bool {
+    let text = text.trim();
+    (text.starts_with("http://") || text.starts_with("https://"))
+        && !text.contains(char::is_whitespace)
+}
+
+// The first file dropped onto the window this frame, decoded.
+pub fn dropped_image(ctx: &egui::Context) -> Option<Result<ImageInput, String>> {
+    let file = ctx.input(|input| input.raw.dropped_files.first().cloned())?;
+    let source = match &file.path {
+        Some(path) => Some(path.display().to_string()),
+        None => Some(file.name.clone()).filter(|name| !name.is_empty()),
+    };
+    Some(decode_dropped_file(&file).map(|image| {
+        let provenance = ImageProvenance::new("Dropped File", source, None, &image);
+        ImageInput::Image(image, provenance)
+    }))
+}
+
+pub fn files_hovered(ctx: &egui::Context) -> bool {
+    ctx.input(|input| !input.raw.hovered_files.is_empty())
+}
+
+// A link pasted with the keyboard while no text field has focus.
+pub fn pasted_url(ctx: &egui::Context) -> Option<String> {
+    if ctx.memory(|memory| memory.focused().is_some()) {
+        return None;
+    }
+    ctx.input(|input| {
+        input.events.iter().find_map(|event| match event {
+            egui::Event::Paste(text) if is_web_url(text) => Some(text.trim().to_string()),
+            _ => None,
+        })
+    })
+}
+
+// Image data on the clipboard, or failing that a link to an image.
+pub fn clipboard_input() -> Result<ImageInput, String> {
+    let mut clipboard = arboard::Clipboard::new().map_err(|e| e.to_string())?;
+    if let Ok(data) = clipboard.get_image() {
+        let image = image::RgbaImage::from_raw(
+            data.width as u32,
+            data.height as u32,
+            data.bytes.into_owned(),
+        )
+        .ok_or("The clipboard image could not be read")?;
+        let image = DynamicImage::ImageRgba8(image);
+        let provenance = ImageProvenance::new("Clipboard", None, None, &image);
+        return Ok(ImageInput::Image(image, provenance));
+    }
+
+    match clipboard.get_text() {
+        Ok(text) if is_web_url(&text) => Ok(ImageInput::Url(text.trim().to_string())),
+        _ => Err("The clipboard has no image or image link.".to_string()),
+    }
+}
+
+// Web pages hand over the file's contents, desktop drops only its path.
+fn decode_dropped_file(file: &egui::DroppedFile) -> Result<DynamicImage, String> {
+    if let Some(bytes) = &file.bytes {
+        return image::load_from_memory(bytes).map_err(|e| e.to_string());
+    }
+    match &file.path {
+        Some(path) => image::open(path).map_err(|e| format!("{}: {e}", path.display())),
+        None => Err("The dropped item is not a file".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs};
+
+    #[test]
+    fn dropped_files_decode_from_path_or_bytes() {
+        let path = env::temp_dir().join(format!(
+            "media-rating-dropped-image-{}.png",
+            std::process::id()
+        ));
+        DynamicImage::new_rgb8(6, 4).save(&path).unwrap();
+
+        let by_path = egui::DroppedFile {
+            path: Some(path.clone()),
+            ..Default::default()
+        };
+        assert_eq!(decode_dropped_file(&by_path).unwrap().width(), 6);
+
+        let by_bytes = egui::DroppedFile {
+            bytes: Some(fs::read(&path).unwrap().into()),
+            ..Default::default()
+        };
+        assert_eq!(decode_dropped_file(&by_bytes).unwrap().height(), 4);
+        assert!(decode_dropped_file(&egui::DroppedFile::default()).is_err());
+
+        assert!(is_web_url(" https://example.com/poster.jpg\n"));
+        assert!(!is_web_url("Dune (1984)"));
+        assert!(!is_web_url("https://example.com/a b.jpg"));
+
+        fs::remove_file(path).ok();
+    }
+}
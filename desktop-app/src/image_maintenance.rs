@@ -0,0 +1,255 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    image_provenance::PROVENANCE_FILE,
+    image_store::{image_path, image_path_candidates},
+    model::Model,
+};
+
+// What a scan of the images directory found. Entries are (category, entry).
+#[derive(Debug, Default)]
+pub struct ImageReport {
+    // Image files no entry uses.
+    pub orphans: Vec<PathBuf>,
+    // Entries without an image file.
+    pub missing: Vec<(String, String)>,
+    // Entries whose only image has the old name, with that file.
+    pub legacy: Vec<(String, String, PathBuf)>,
+    // Entries that map to the same file name and so share one image.
+    pub collisions: Vec<(PathBuf, Vec<(String, String)>)>,
+    // Groups of entries with separate files showing the same picture, filled
+    // in from `ImageStore::near_duplicates`.
+    pub duplicates: Vec<Vec<(String, String)>>,
+}
+
+impl ImageReport {
+    pub fn is_clean(&self) -> bool {
+        self.orphans.is_empty()
+            && self.missing.is_empty()
+            && self.legacy.is_empty()
+            && self.collisions.is_empty()
+            && self.duplicates.is_empty()
+    }
+}
+
+// The fixes to apply in one pass.
+#[derive(Debug, Default)]
+pub struct MaintenancePlan {
+    pub migrate_legacy: Vec<(String, String, PathBuf)>,
+    pub delete: Vec<PathBuf>,
+    // Orphaned files to rename into place for an entry missing its image.
+    pub reattach: Vec<(PathBuf, String, String)>,
+}
+
+pub fn scan(image_directory: &Path, model: &Model) -> io::Result<ImageReport> {
+    let mut report = ImageReport::default();
+    let files: HashSet<PathBuf> = match fs::read_dir(image_directory) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file())
+            // The provenance manifest lives beside the images.
+            .filter(|path| !path.ends_with(PROVENANCE_FILE))
+            .collect(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => HashSet::new(),
+        Err(e) => return Err(e),
+    };
+
+    let mut used = HashSet::new();
+    let mut owners: HashMap<PathBuf, Vec<(String, String)>> = HashMap::new();
+    let mut categories: Vec<_> = model.categories().collect();
+    categories.sort_by_key(|(category, _)| *category);
+
+    for (category, entries) in categories {
+        for entry in entries {
+            let candidates = image_path_candidates(image_directory, category, entry);
+            let primary = candidates[0].clone();
+            owners
+                .entry(primary.clone())
+                .or_default()
+                .push((category.to_string(), entry.clone()));
+
+            match candidates.into_iter().find(|path| files.contains(path)) {
+                Some(path) if path == primary => {
+                    used.insert(path);
+                }
+                Some(path) => {
+                    used.insert(path.clone());
+                    report
+                        .legacy
+                        .push((category.to_string(), entry.clone(), path));
+                }
+                None => report.missing.push((category.to_string(), entry.clone())),
+            }
+        }
+    }
+
+    // A legacy file next to its renamed copy is an orphan too.
+    report.orphans = files.difference(&used).cloned().collect();
+    report.orphans.sort();
+    report.collisions = owners
+        .into_iter()
+        .filter(|(_, entries)| entries.len() > 1)
+        .collect();
+    report.collisions.sort();
+
+    Ok(report)
+}
+
+// Returns a description of every step that failed; the rest still run.
+pub fn apply(image_directory: &Path, model: &Model, plan: &MaintenancePlan) -> Vec<String> {
+    let mut errors = Vec::new();
+
+    // An old name can be shared by several entries, so every owner but the
+    // last gets a copy and the last one takes the file. A file that is still
+    // another entry's current name, or that some owner failed to copy, stays.
+    let current: HashSet<PathBuf> = model
+        .categories()
+        .flat_map(|(category, entries)| {
+            entries
+                .iter()
+                .map(move |entry| image_path(image_directory, category, entry))
+        })
+        .collect();
+    let mut owners_left: HashMap<&PathBuf, usize> = HashMap::new();
+    for (_, _, legacy) in &plan.migrate_legacy {
+        *owners_left.entry(legacy).or_default() += 1;
+    }
+    let mut failed = HashSet::new();
+    for (category, entry, legacy) in &plan.migrate_legacy {
+        let destination = image_path(image_directory, category, entry);
+        let owners = owners_left.get_mut(legacy).expect("counted above");
+        *owners -= 1;
+
+        let last_owner = *owners == 0 && !current.contains(legacy) && !failed.contains(legacy);
+        let result = if last_owner {
+            fs::rename(legacy, &destination)
+        } else {
+            fs::copy(legacy, &destination).map(|_| ())
+        };
+        if let Err(e) = result {
+            errors.push(format!("Could not rename {}: {e}", legacy.display()));
+            failed.insert(legacy);
+        }
+    }
+
+    for (orphan, category, entry) in &plan.reattach {
+        let destination = image_path(image_directory, category, entry);
+        if destination.exists() {
+            errors.push(format!("{} already has an image", entry));
+            continue;
+        }
+        if let Err(e) = fs::rename(orphan, &destination) {
+            errors.push(format!("Could not reattach {}: {e}", orphan.display()));
+        }
+    }
+
+    for path in &plan.delete {
+        if let Err(e) = fs::remove_file(path) {
+            errors.push(format!("Could not delete {}: {e}", path.display()));
+        }
+    }
+
+    errors
+}
+
+// Move images still under an older naming scheme to their current names.
+pub fn migrate_file_names(image_directory: &Path, model: &Model) -> Vec<String> {
+    match scan(image_directory, model) {
+        Ok(report) if !report.legacy.is_empty() => apply(
+            image_directory,
+            model,
+            &MaintenancePlan {
+                migrate_legacy: report.legacy,
+                ..MaintenancePlan::default()
+            },
+        ),
+        Ok(_) => Vec::new(),
+        Err(e) => vec![e.to_string()],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image_store::legacy_image_path;
+    use std::env;
+
+    #[test]
+    fn scan_finds_each_problem_and_apply_fixes_them() {
+        let image_directory = env::temp_dir().join(format!(
+            "media-rating-image-maintenance-test-{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&image_directory).unwrap();
+
+        let mut model = Model::default();
+        model.create_category("Movies:".to_string());
+        for (index, entry) in ["Alien", "Alien (1979)", "Dune", "Heat"]
+            .into_iter()
+            .enumerate()
+        {
+            model.insert_entry_at("Movies:", entry.to_string(), index);
+        }
+        // "Alien (1979)" still has the name it shared with "Alien".
+        for file in ["Alien Movies.png", "Dune Movies.png", "Deleted Books.png"] {
+            fs::write(image_directory.join(file), b"image").unwrap();
+        }
+        // An old-style name from before category names were cleaned up.
+        let legacy = legacy_image_path(&image_directory, "Movies: ", "Ran");
+        model.create_category("Movies: ".to_string());
+        model.insert_entry_at("Movies: ", "Ran".to_string(), 0);
+        // Both owners of the old name keep the image.
+        model.insert_entry_at("Movies: ", "Ran (1985)".to_string(), 1);
+        fs::write(&legacy, b"image").unwrap();
+        // Categories differing only by the colon used to share file names.
+        model.create_category("Movies".to_string());
+        model.insert_entry_at("Movies", "Dune".to_string(), 0);
+
+        let report = scan(&image_directory, &model).unwrap();
+        assert_eq!(
+            report.orphans,
+            vec![image_directory.join("Deleted Books.png")]
+        );
+        assert_eq!(
+            report.missing,
+            vec![("Movies:".to_string(), "Heat".to_string())]
+        );
+        assert_eq!(report.legacy.len(), 4);
+        assert!(report.collisions.is_empty());
+
+        let errors = apply(
+            &image_directory,
+            &model,
+            &MaintenancePlan {
+                migrate_legacy: report.legacy.clone(),
+                delete: Vec::new(),
+                reattach: vec![(
+                    report.orphans[0].clone(),
+                    "Movies:".to_string(),
+                    "Heat".to_string(),
+                )],
+            },
+        );
+        assert!(errors.is_empty());
+        assert!(image_directory.join("Alien Movies.png").exists());
+        assert!(image_path(&image_directory, "Movies:", "Alien (1979)").exists());
+        assert!(image_directory.join("Dune Movies.png").exists());
+        let separate_dune = image_path(&image_directory, "Movies", "Dune");
+        assert!(separate_dune.exists());
+        assert_ne!(separate_dune, image_directory.join("Dune Movies.png"));
+        assert!(image_path(&image_directory, "Movies: ", "Ran").exists());
+        assert!(image_path(&image_directory, "Movies: ", "Ran (1985)").exists());
+        assert!(!legacy.exists());
+
+        let report = scan(&image_directory, &model).unwrap();
+        assert!(report.orphans.is_empty());
+        assert!(report.legacy.is_empty());
+        assert!(report.missing.is_empty());
+
+        fs::remove_dir_all(image_directory).ok();
+    }
+}
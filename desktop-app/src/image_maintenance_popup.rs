@@ -0,0 +1,268 @@
+use eframe::egui;
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+use crate::{
+    app::AppAction,
+    image_maintenance::{ImageReport, MaintenancePlan},
+    perceptual_hash,
+    popup::{Popup, PopupResponse},
+};
+
+// What to do with a file no entry uses.
+#[derive(Clone, PartialEq)]
+enum OrphanFix {
+    Keep,
+    Delete,
+    // Index into the report's missing entries.
+    Reattach(usize),
+}
+
+// Shows the result of an image check and lets the user pick the fixes to
+// apply together.
+pub struct ImageMaintenancePopup {
+    report: ImageReport,
+    migrate_legacy: bool,
+    orphan_fixes: Vec<OrphanFix>,
+}
+
+impl ImageMaintenancePopup {
+    pub fn new(report: ImageReport) -> Self {
+        let orphan_fixes = vec![OrphanFix::Keep; report.orphans.len()];
+        Self {
+            migrate_legacy: !report.legacy.is_empty(),
+            report,
+            orphan_fixes,
+        }
+    }
+
+    fn plan(&self) -> MaintenancePlan {
+        let mut plan = MaintenancePlan::default();
+        if self.migrate_legacy {
+            plan.migrate_legacy.clone_from(&self.report.legacy);
+        }
+
+        for (orphan, fix) in self.report.orphans.iter().zip(&self.orphan_fixes) {
+            match fix {
+                OrphanFix::Keep => {}
+                OrphanFix::Delete => plan.delete.push(orphan.clone()),
+                OrphanFix::Reattach(index) => {
+                    let (category, entry) = &self.report.missing[*index];
+                    plan.reattach
+                        .push((orphan.clone(), category.clone(), entry.clone()));
+                }
+            }
+        }
+
+        plan
+    }
+
+    fn orphans(&mut self, ui: &mut egui::Ui) {
+        ui.label(format!(
+            "Unused image files ({}):",
+            self.report.orphans.len()
+        ));
+        let missing = &self.report.missing;
+        for (index, (orphan, fix)) in self
+            .report
+            .orphans
+            .iter()
+            .zip(&mut self.orphan_fixes)
+            .enumerate()
+        {
+            ui.horizontal(|ui| {
+                ui.label(file_name(orphan));
+                egui::ComboBox::from_id_source(("orphan-fix", index))
+                    .selected_text(match fix {
+                        OrphanFix::Keep => "Keep".to_string(),
+                        OrphanFix::Delete => "Delete".to_string(),
+                        OrphanFix::Reattach(missing_index) => {
+                            let (category, entry) = &missing[*missing_index];
+                            format!("Use for {entry} ({category})")
+                        }
+                    })
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(fix, OrphanFix::Keep, "Keep");
+                        ui.selectable_value(fix, OrphanFix::Delete, "Delete");
+                        for (missing_index, (category, entry)) in missing.iter().enumerate() {
+                            ui.selectable_value(
+                                fix,
+                                OrphanFix::Reattach(missing_index),
+                                format!("Use for {entry} ({category})"),
+                            );
+                        }
+                    });
+            });
+        }
+    }
+}
+
+fn entry_names(entries: &[(String, String)]) -> String {
+    entries
+        .iter()
+        .map(|(category, entry)| format!("{entry} ({category})"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+impl Popup for ImageMaintenancePopup {
+    fn title(&self) -> &str {
+        "Check Images"
+    }
+
+    fn show_body(&mut self, ui: &mut egui::Ui) -> PopupResponse {
+        let mut response = PopupResponse::KeepOpen;
+
+        if self.report.is_clean() {
+            ui.label("Every entry has its own image and every image belongs to an entry.");
+            ui.add_space(8.0);
+            if ui.button("Done").clicked() {
+                response = PopupResponse::Close;
+            }
+            return response;
+        }
+
+        egui::ScrollArea::vertical()
+            .max_height(420.0)
+            .show(ui, |ui| {
+                if !self.report.legacy.is_empty() {
+                    ui.checkbox(
+                        &mut self.migrate_legacy,
+                        format!(
+                            "Rename {} image(s) saved under the old naming scheme",
+                            self.report.legacy.len()
+                        ),
+                    );
+                    ui.add_space(8.0);
+                }
+
+                if !self.report.orphans.is_empty() {
+                    self.orphans(ui);
+                    ui.add_space(8.0);
+                }
+
+                if !self.report.missing.is_empty() {
+                    ui.label(format!(
+                        "Entries without an image ({}):",
+                        self.report.missing.len()
+                    ));
+                    for (category, entry) in &self.report.missing {
+                        ui.label(format!("    {entry} ({category})"));
+                    }
+                    ui.add_space(8.0);
+                }
+
+                if !self.report.collisions.is_empty() {
+                    ui.label("Entries sharing one image file (rename one to separate them):");
+                    for (path, entries) in &self.report.collisions {
+                        ui.label(format!("    {}: {}", file_name(path), entry_names(entries)));
+                    }
+                    ui.add_space(8.0);
+                }
+
+                if !self.report.duplicates.is_empty() {
+                    ui.label(
+                        "Entries showing the same picture (get a new image for the wrong one):",
+                    );
+                    for group in &self.report.duplicates {
+                        ui.label(format!("    {}", entry_names(group)));
+                    }
+                }
+            });
+
+        ui.add_space(8.0);
+        ui.horizontal(|ui| {
+            if ui.button("Apply").clicked() {
+                response = PopupResponse::Action(AppAction::ApplyImageMaintenance(self.plan()));
+            }
+            if ui.button("Cancel").clicked() {
+                response = PopupResponse::Close;
+            }
+        });
+
+        response
+    }
+}
+
+// Reads the images without a stored hash on a background thread before the
+// check is shown, so the first check of a large library keeps the window
+// responsive.
+pub struct ImageHashingPopup {
+    report: ImageReport,
+    total: usize,
+    received: usize,
+    hashes: Vec<(String, String, u64)>,
+    results: Receiver<(String, String, Option<u64>)>,
+}
+
+impl ImageHashingPopup {
+    pub fn start(
+        report: ImageReport,
+        images: Vec<(String, String, PathBuf)>,
+        ctx: &egui::Context,
+    ) -> Self {
+        let total = images.len();
+        let (sender, results) = mpsc::channel();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            for (category, entry, path) in images {
+                let hash = image::open(&path)
+                    .ok()
+                    .map(|image| perceptual_hash::dhash(&image));
+                // Closing the popup stops the rest.
+                if sender.send((category, entry, hash)).is_err() {
+                    return;
+                }
+                ctx.request_repaint();
+            }
+        });
+
+        Self {
+            report,
+            total,
+            received: 0,
+            hashes: Vec::new(),
+            results,
+        }
+    }
+}
+
+impl Popup for ImageHashingPopup {
+    fn title(&self) -> &str {
+        "Check Images"
+    }
+
+    fn show_body(&mut self, ui: &mut egui::Ui) -> PopupResponse {
+        for (category, entry, hash) in self.results.try_iter() {
+            self.received += 1;
+            if let Some(hash) = hash {
+                self.hashes.push((category, entry, hash));
+            }
+        }
+        if self.received >= self.total {
+            return PopupResponse::Action(AppAction::FinishImageCheck {
+                report: Box::new(std::mem::take(&mut self.report)),
+                hashes: std::mem::take(&mut self.hashes),
+            });
+        }
+
+        ui.label("Reading images to compare them...");
+        ui.add(
+            egui::ProgressBar::new(self.received as f32 / self.total as f32)
+                .text(format!("{}/{}", self.received, self.total)),
+        );
+        if ui.button("Cancel").clicked() {
+            return PopupResponse::Close;
+        }
+        PopupResponse::KeepOpen
+    }
+}
@@ -1,29 +1,57 @@
 use eframe::egui;
 use native_dialog::FileDialog;
 use std::{
-    sync::mpsc::{self, Receiver},
+    collections::HashSet,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver},
+        Arc,
+    },
     thread,
 };
 
 use crate::{
     app::{AppAction, ImagePickPurpose},
-    image_search,
-    image_store::{dynamic_image_to_color_image, ENTRY_IMAGE_HEIGHT, ENTRY_IMAGE_WIDTH},
+    image_crop::{self, CropMode, MAX_ZOOM},
+    image_input::{self, ImageInput},
+    image_provenance::ImageProvenance,
+    image_provider::ImageProviderConfig,
+    image_search::{self, Image, SearchOutcome, SearchRequest, SearchResult},
+    image_store::ImageGeometry,
+    perceptual_hash,
     popup::{Popup, PopupResponse},
+    search_cache::SearchCache,
 };
 
+// Results fetched per page.
 const SEARCH_RESULT_COUNT: usize = 18;
+const RESULT_THUMBNAIL_SIZE: egui::Vec2 = egui::vec2(114.0, 142.0);
+const CROP_VIEW_SIZE: f32 = 420.0;
+
+enum SearchEvent {
+    Found(SearchResult),
+    Finished(Result<SearchOutcome, String>),
+}
+
+// Stops the search thread once the search is replaced or the popup closes.
+struct CancelOnDrop(Arc<AtomicBool>);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
 
 enum SearchState {
     Idle,
+    // Results are shown as they arrive.
     Loading {
         query: String,
-        receiver: Receiver<Result<Vec<image::DynamicImage>, String>>,
+        receiver: Receiver<SearchEvent>,
+        _cancel: CancelOnDrop,
     },
     Loaded {
         query: String,
-        images: Vec<image::DynamicImage>,
-        textures: Vec<egui::TextureHandle>,
     },
     Failed {
         query: String,
@@ -31,34 +59,192 @@ enum SearchState {
     },
 }
 
+// Everything shown for the current query, across the pages loaded so far.
+#[derive(Default)]
+struct ShownResults {
+    images: Vec<(image::DynamicImage, ImageProvenance)>,
+    textures: Vec<egui::TextureHandle>,
+    sources: HashSet<String>,
+    hashes: Vec<u64>,
+    next_page: usize,
+    // Whether the provider may have another page.
+    more: bool,
+    // Candidates from the pages searched so far that are not downloaded yet,
+    // shown before the next page is asked for.
+    candidates: Vec<Image>,
+}
+
+impl ShownResults {
+    fn has_more(&self) -> bool {
+        self.more || !self.candidates.is_empty()
+    }
+}
+
+// The chosen image, framed before it is saved. Fill and letterbox settings
+// are both kept so switching between them loses neither.
+struct CropStep {
+    image: image::DynamicImage,
+    provenance: ImageProvenance,
+    texture: egui::TextureHandle,
+    zoom: f32,
+    center: [f32; 2],
+    letterbox: bool,
+    background: [u8; 3],
+    letterbox_preview: Option<egui::TextureHandle>,
+}
+
+impl CropStep {
+    fn mode(&self) -> CropMode {
+        if self.letterbox {
+            CropMode::Letterbox {
+                background: self.background,
+            }
+        } else {
+            CropMode::Fill {
+                zoom: self.zoom,
+                center: self.center,
+            }
+        }
+    }
+}
+
 pub struct ImagePickerPopup {
     category: String,
     entry: String,
     query: String,
     purpose: ImagePickPurpose,
+    provider: ImageProviderConfig,
+    geometry: ImageGeometry,
+    cache: Option<SearchCache>,
     search_state: SearchState,
+    results: ShownResults,
+    url: String,
+    // A linked image being downloaded.
+    download: Option<Receiver<Result<ImageInput, String>>>,
+    // Why the last dropped, pasted or opened image could not be used.
+    input_error: Option<String>,
+    crop: Option<CropStep>,
+    // Offline, the provider is only searched when asked to.
+    offline: bool,
 }
 
 impl ImagePickerPopup {
-    pub fn new(category: String, entry: String, purpose: ImagePickPurpose) -> Self {
+    pub fn new(
+        category: String,
+        entry: String,
+        purpose: ImagePickPurpose,
+        provider: ImageProviderConfig,
+        geometry: ImageGeometry,
+        cache: Option<SearchCache>,
+    ) -> Self {
         let query = default_query(&entry, &category);
         Self {
             category,
             entry,
             query,
             purpose,
+            provider,
+            geometry,
+            cache,
             search_state: SearchState::Idle,
+            results: ShownResults::default(),
+            url: String::new(),
+            download: None,
+            input_error: None,
+            crop: None,
+            offline: false,
+        }
+    }
+
+    pub fn with_offline(mut self, offline: bool) -> Self {
+        self.offline = offline && self.provider.needs_network();
+        self
+    }
+
+    // Start from an image given some other way than searching.
+    pub fn use_input(&mut self, input: ImageInput, ctx: &egui::Context) {
+        match input {
+            ImageInput::Image(image, provenance) => self.start_crop(image, provenance, ctx),
+            ImageInput::Url(url) => self.start_download(url, ctx),
+        }
+    }
+
+    fn start_download(&mut self, url: String, ctx: &egui::Context) {
+        let (sender, receiver) = mpsc::channel();
+        let repaint_ctx = ctx.clone();
+        self.url.clone_from(&url);
+        thread::spawn(move || {
+            let result = image_search::download_image(&url).map(|(image, original_size)| {
+                let provenance = ImageProvenance::new("Link", Some(url), None, &image)
+                    .with_original_size(original_size);
+                ImageInput::Image(image, provenance)
+            });
+            let _ = sender.send(result.map_err(|e| e.details));
+            repaint_ctx.request_repaint();
+        });
+        self.input_error = None;
+        self.download = Some(receiver);
+    }
+
+    fn poll_download(&mut self, ctx: &egui::Context) {
+        let Some(result) = self
+            .download
+            .as_ref()
+            .and_then(|receiver| receiver.try_recv().ok())
+        else {
+            return;
+        };
+
+        self.download = None;
+        match result {
+            Ok(input) => self.use_input(input, ctx),
+            Err(message) => {
+                self.input_error = Some(format!("Could not load the linked image: {message}"));
+            }
+        }
+    }
+
+    // Dropped files and pasted links, handled on either step.
+    fn accept_input(&mut self, ctx: &egui::Context) {
+        match image_input::dropped_image(ctx) {
+            Some(Ok(input)) => {
+                self.input_error = None;
+                self.use_input(input, ctx);
+            }
+            Some(Err(message)) => {
+                self.input_error = Some(format!("Could not open the dropped image: {message}"));
+            }
+            None => {}
+        }
+        if let Some(url) = image_input::pasted_url(ctx) {
+            self.crop = None;
+            self.start_download(url, ctx);
+        }
+    }
+
+    fn paste(&mut self, ctx: &egui::Context) {
+        match image_input::clipboard_input() {
+            Ok(input) => {
+                self.input_error = None;
+                self.use_input(input, ctx);
+            }
+            Err(message) => self.input_error = Some(message),
         }
     }
 
     fn ensure_search_started(&mut self, ctx: &egui::Context) {
-        if matches!(self.search_state, SearchState::Idle) {
-            self.start_search(ctx);
+        if matches!(self.search_state, SearchState::Idle)
+            && self.download.is_none()
+            && !self.offline
+        {
+            self.start_search(ctx, false);
         }
     }
 
-    fn start_search(&mut self, ctx: &egui::Context) {
+    // `refresh` searches again even if the query's results are cached.
+    fn start_search(&mut self, ctx: &egui::Context, refresh: bool) {
         let query = self.query.trim().to_string();
+        self.results = ShownResults::default();
         if query.is_empty() {
             self.search_state = SearchState::Failed {
                 query,
@@ -67,67 +253,121 @@ impl ImagePickerPopup {
             return;
         }
 
+        self.search_page(ctx, query, refresh);
+    }
+
+    fn load_more(&mut self, ctx: &egui::Context) {
+        let query = self.active_query();
+        self.search_page(ctx, query, false);
+    }
+
+    fn search_page(&mut self, ctx: &egui::Context, query: String, refresh: bool) {
         let (sender, receiver) = mpsc::channel();
         let repaint_ctx = ctx.clone();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let thread_cancelled = Arc::clone(&cancelled);
         let search_query = query.clone();
+        let provider = self.provider.clone();
+        let geometry = self.geometry;
+        let cache = self.cache.clone();
+        let page = self.results.next_page;
+        let skip = self.results.sources.clone();
+        let candidates = std::mem::take(&mut self.results.candidates);
         thread::spawn(move || {
-            let result = image_search::search_many(
-                &search_query,
-                ENTRY_IMAGE_WIDTH,
-                ENTRY_IMAGE_HEIGHT,
-                SEARCH_RESULT_COUNT,
-            )
-            .map_err(|e| e.to_string());
-            let _ = sender.send(result);
+            let request = SearchRequest {
+                query: &search_query,
+                geometry,
+                count: SEARCH_RESULT_COUNT,
+                refresh,
+                page,
+                skip: &skip,
+                candidates: &candidates,
+            };
+            let result = image_search::search_incremental(
+                &provider,
+                cache.as_ref(),
+                &request,
+                &thread_cancelled,
+                &mut |result| {
+                    let _ = sender.send(SearchEvent::Found(result));
+                    repaint_ctx.request_repaint();
+                },
+            );
+            let _ = sender.send(SearchEvent::Finished(result.map_err(|e| e.to_string())));
             repaint_ctx.request_repaint();
         });
 
-        self.search_state = SearchState::Loading { query, receiver };
+        self.search_state = SearchState::Loading {
+            query,
+            receiver,
+            _cancel: CancelOnDrop(cancelled),
+        };
     }
 
     fn poll_search(&mut self, ctx: &egui::Context) {
-        let result = match &self.search_state {
-            SearchState::Loading { receiver, .. } => receiver.try_recv().ok(),
-            _ => None,
-        };
-
-        let Some(result) = result else {
+        let SearchState::Loading {
+            query, receiver, ..
+        } = &mut self.search_state
+        else {
             return;
         };
 
-        match result {
-            Ok(images) if images.is_empty() => {
-                let query = self.active_query();
-                self.search_state = SearchState::Failed {
-                    query,
-                    message: "No usable images found.".to_string(),
-                };
-            }
-            Ok(images) => {
-                let query = self.active_query();
-                let textures = images
-                    .iter()
-                    .enumerate()
-                    .map(|(index, image)| {
-                        ctx.load_texture(
-                            format!("image-picker-{query}-{index}"),
-                            dynamic_image_to_color_image(image),
-                            egui::TextureOptions::LINEAR,
-                        )
-                    })
-                    .collect();
-
-                self.search_state = SearchState::Loaded {
-                    query,
-                    images,
-                    textures,
-                };
-            }
-            Err(message) => {
-                let query = self.active_query();
-                self.search_state = SearchState::Failed { query, message };
+        let results = &mut self.results;
+        let provider = self.provider.name();
+        let mut finished = None;
+        for event in receiver.try_iter() {
+            match event {
+                // The same image may come from several sources or pages.
+                SearchEvent::Found(result) => {
+                    let new_source = results.sources.insert(result.source.clone());
+                    let seen = results
+                        .hashes
+                        .iter()
+                        .any(|hash| perceptual_hash::is_near_duplicate(*hash, result.hash));
+                    if !new_source || seen {
+                        continue;
+                    }
+                    results.hashes.push(result.hash);
+                    let provenance = ImageProvenance::new(
+                        provider,
+                        Some(result.source),
+                        Some(query.clone()),
+                        &result.image,
+                    )
+                    .with_original_size(result.original_size);
+                    results.textures.push(ctx.load_texture(
+                        format!("image-picker-{query}-{}", results.textures.len()),
+                        fitted_color_image(&result.image, 2.0 * RESULT_THUMBNAIL_SIZE),
+                        egui::TextureOptions::LINEAR,
+                    ));
+                    results.images.push((result.image, provenance));
+                }
+                SearchEvent::Finished(result) => finished = Some(result),
             }
         }
+
+        let Some(result) = finished else {
+            return;
+        };
+        let query = std::mem::take(query);
+        self.search_state = match result {
+            Ok(outcome) => {
+                if let Some(more) = outcome.more {
+                    results.next_page += 1;
+                    results.more = more;
+                }
+                results.candidates = outcome.leftover;
+                if results.images.is_empty() && !results.has_more() {
+                    SearchState::Failed {
+                        query,
+                        message: "No usable images found.".to_string(),
+                    }
+                } else {
+                    SearchState::Loaded { query }
+                }
+            }
+            Err(message) => SearchState::Failed { query, message },
+        };
     }
 
     fn active_query(&self) -> String {
@@ -139,7 +379,7 @@ impl ImagePickerPopup {
         }
     }
 
-    fn pick_local_file(&mut self) -> PopupResponse {
+    fn pick_local_file(&mut self, ctx: &egui::Context) -> PopupResponse {
         let Some(path) = FileDialog::new()
             .add_filter("Image", &["png", "jpg", "jpeg", "webp", "bmp", "gif"])
             .show_open_single_file()
@@ -149,23 +389,104 @@ impl ImagePickerPopup {
             return PopupResponse::KeepOpen;
         };
 
-        match image::open(path) {
-            Ok(image) => self.select_image(image),
-            Err(e) => {
-                self.search_state = SearchState::Failed {
-                    query: self.active_query(),
-                    message: format!("Could not open local image: {e}"),
-                };
-                PopupResponse::KeepOpen
+        match image::open(&path) {
+            Ok(image) => {
+                let source = Some(path.display().to_string());
+                let provenance = ImageProvenance::new("Local File", source, None, &image);
+                self.input_error = None;
+                self.start_crop(image, provenance, ctx);
             }
+            Err(e) => self.input_error = Some(format!("Could not open local image: {e}")),
         }
+        PopupResponse::KeepOpen
     }
 
-    fn select_image(&self, image: image::DynamicImage) -> PopupResponse {
+    fn start_crop(
+        &mut self,
+        image: image::DynamicImage,
+        provenance: ImageProvenance,
+        ctx: &egui::Context,
+    ) {
+        let texture = ctx.load_texture(
+            "image-picker-crop",
+            fitted_color_image(&image, egui::Vec2::splat(2.0 * CROP_VIEW_SIZE)),
+            egui::TextureOptions::LINEAR,
+        );
+        self.crop = Some(CropStep {
+            image,
+            provenance,
+            texture,
+            zoom: 1.0,
+            center: [0.5, 0.5],
+            letterbox: false,
+            background: [0, 0, 0],
+            letterbox_preview: None,
+        });
+    }
+
+    fn show_crop_step(&mut self, ui: &mut egui::Ui) -> PopupResponse {
+        let Some(crop) = self.crop.as_mut() else {
+            return PopupResponse::KeepOpen;
+        };
+        let target = self.geometry.size();
+
+        ui.horizontal(|ui| {
+            ui.radio_value(&mut crop.letterbox, false, "Crop");
+            ui.radio_value(&mut crop.letterbox, true, "Fit");
+            if crop.letterbox {
+                if ui.color_edit_button_srgb(&mut crop.background).changed() {
+                    crop.letterbox_preview = None;
+                }
+            } else {
+                ui.add(egui::Slider::new(&mut crop.zoom, 1.0..=MAX_ZOOM).text("Zoom"));
+            }
+        });
+
+        if crop.letterbox {
+            let mode = crop.mode();
+            let preview = crop.letterbox_preview.get_or_insert_with(|| {
+                let result = image_crop::apply_crop(&crop.image, mode, target);
+                ui.ctx().load_texture(
+                    "image-picker-letterbox",
+                    fitted_color_image(&result, egui::vec2(target[0] as f32, target[1] as f32)),
+                    egui::TextureOptions::LINEAR,
+                )
+            });
+            ui.add(egui::Image::new(&*preview).max_size(egui::Vec2::splat(CROP_VIEW_SIZE)));
+        } else {
+            show_crop_frame(ui, crop, target);
+        }
+
+        let mut chosen = None;
+        let mut back = false;
+        ui.add_space(8.0);
+        ui.horizontal(|ui| {
+            if ui.button("Use Image").clicked() {
+                let image = image_crop::apply_crop(&crop.image, crop.mode(), target);
+                chosen = Some((image, crop.provenance.clone()));
+            }
+            back = ui.button("Back").clicked();
+        });
+
+        if back {
+            self.crop = None;
+        }
+        match chosen {
+            Some((image, provenance)) => self.select_image(image, provenance),
+            None => PopupResponse::KeepOpen,
+        }
+    }
+
+    fn select_image(
+        &self,
+        image: image::DynamicImage,
+        provenance: ImageProvenance,
+    ) -> PopupResponse {
         PopupResponse::Action(AppAction::SetEntryImage {
             category: self.category.clone(),
             entry: self.entry.clone(),
             image,
+            provenance: Box::new(provenance),
             purpose: self.purpose.clone(),
         })
     }
@@ -177,12 +498,27 @@ impl Popup for ImagePickerPopup {
     }
 
     fn show_body(&mut self, ui: &mut egui::Ui) -> PopupResponse {
+        self.accept_input(ui.ctx());
+        self.poll_download(ui.ctx());
+        if self.crop.is_some() {
+            return self.show_crop_step(ui);
+        }
+
         self.ensure_search_started(ui.ctx());
         self.poll_search(ui.ctx());
 
         let mut response = PopupResponse::KeepOpen;
 
         ui.label(format!("{} - {}", self.entry, self.category));
+        let status = if self.offline {
+            format!(
+                "Offline: {} is searched only when you press Search. Local files, pasted and dropped images still work.",
+                self.provider.name()
+            )
+        } else {
+            format!("Searching {}", self.provider.name())
+        };
+        ui.label(egui::RichText::new(status).small().weak());
 
         ui.horizontal(|ui| {
             let query_response =
@@ -192,42 +528,89 @@ impl Popup for ImagePickerPopup {
                 && ui.input(|input| input.key_pressed(egui::Key::Enter));
 
             if search_clicked || enter_pressed {
-                self.start_search(ui.ctx());
+                self.start_search(ui.ctx(), false);
             }
 
             if ui.button("Default").clicked() {
                 self.query = default_query(&self.entry, &self.category);
-                self.start_search(ui.ctx());
+                self.start_search(ui.ctx(), false);
+            }
+
+            if ui
+                .button("Refresh")
+                .on_hover_text("Search again instead of using saved results")
+                .clicked()
+            {
+                self.start_search(ui.ctx(), true);
             }
 
             if ui.button("Local File").clicked() {
-                response = self.pick_local_file();
+                response = self.pick_local_file(ui.ctx());
+            }
+        });
+
+        ui.horizontal(|ui| {
+            let url_response = ui.add_sized(
+                [360.0, 24.0],
+                egui::TextEdit::singleline(&mut self.url).hint_text("Image link"),
+            );
+            let enter_pressed =
+                url_response.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter));
+            if (ui.button("Load Link").clicked() || enter_pressed)
+                && image_input::is_web_url(&self.url)
+            {
+                self.start_download(self.url.trim().to_string(), ui.ctx());
+            }
+            if ui.button("Paste").clicked() {
+                self.paste(ui.ctx());
             }
         });
 
+        let hint = if image_input::files_hovered(ui.ctx()) {
+            "Drop to use this image"
+        } else {
+            "Or drop an image file onto the window"
+        };
+        ui.label(egui::RichText::new(hint).small().weak());
+        if self.download.is_some() {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label("Downloading the linked image...");
+            });
+        }
+        if let Some(message) = &self.input_error {
+            ui.label(message);
+        }
+
         ui.add_space(8.0);
 
+        let mut chosen = None;
+        let mut load_more = false;
         match &self.search_state {
-            SearchState::Idle => {}
+            SearchState::Idle | SearchState::Loaded { .. } => {}
             SearchState::Loading { query, .. } => {
-                ui.label(format!("Searching for \"{query}\"..."));
-                ui.spinner();
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label(format!("Searching for \"{query}\"..."));
+                });
             }
             SearchState::Failed { message, .. } => {
                 ui.label(message);
             }
-            SearchState::Loaded {
-                images, textures, ..
-            } => {
+        }
+
+        let results = &self.results;
+        egui::ScrollArea::vertical()
+            .max_height(4.0 * (RESULT_THUMBNAIL_SIZE.y + 16.0))
+            .show(ui, |ui| {
                 egui::Grid::new("image-picker-results")
                     .num_columns(4)
                     .spacing([8.0, 8.0])
                     .show(ui, |ui| {
-                        for (index, texture) in textures.iter().enumerate() {
-                            let image = egui::Image::new(texture)
-                                .fit_to_exact_size(egui::vec2(114.0, 142.0));
+                        for (index, texture) in results.textures.iter().enumerate() {
+                            let image = egui::Image::new(texture).max_size(RESULT_THUMBNAIL_SIZE);
                             if ui.add(egui::ImageButton::new(image)).clicked() {
-                                response = self.select_image(images[index].clone());
+                                chosen = Some(results.images[index].clone());
                             }
 
                             if (index + 1) % 4 == 0 {
@@ -235,7 +618,22 @@ impl Popup for ImagePickerPopup {
                             }
                         }
                     });
-            }
+
+                let searching = matches!(self.search_state, SearchState::Loading { .. });
+                if !searching && results.next_page > 0 {
+                    if results.has_more() {
+                        load_more = ui.button("Load More").clicked();
+                    } else {
+                        ui.label(egui::RichText::new("No more results.").weak());
+                    }
+                }
+            });
+        if load_more {
+            self.load_more(ui.ctx());
+        }
+
+        if let Some((image, provenance)) = chosen {
+            self.start_crop(image, provenance, ui.ctx());
         }
 
         ui.add_space(8.0);
@@ -247,7 +645,66 @@ impl Popup for ImagePickerPopup {
     }
 }
 
-fn default_query(entry: &str, category: &str) -> String {
+pub fn default_query(entry: &str, category: &str) -> String {
     let category = category.trim().trim_end_matches(':').trim();
     format!("{entry} ({category})")
 }
+
+// Draw the image with the crop frame over it; dragging pans the frame and
+// scrolling zooms.
+fn show_crop_frame(ui: &mut egui::Ui, crop: &mut CropStep, target: [u32; 2]) {
+    let image_size = [crop.image.width(), crop.image.height()];
+    let scale = CROP_VIEW_SIZE / image_size[0].max(image_size[1]) as f32;
+    let view_size = egui::vec2(image_size[0] as f32, image_size[1] as f32) * scale;
+    let (rect, response) = ui.allocate_exact_size(view_size, egui::Sense::drag());
+    egui::Image::new(&crop.texture).paint_at(ui, rect);
+
+    if response.hovered() {
+        let scroll = ui.input(|input| input.smooth_scroll_delta.y);
+        crop.zoom = (crop.zoom * (1.0 + scroll * 0.002)).clamp(1.0, MAX_ZOOM);
+    }
+
+    let frame = image_crop::fill_frame(image_size, target, crop.zoom, crop.center);
+    let delta = response.drag_delta() / scale;
+    // Re-centre on the clamped frame so dragging back from an edge responds
+    // straight away.
+    crop.center = [
+        (frame.x + frame.width / 2.0 + delta.x) / image_size[0] as f32,
+        (frame.y + frame.height / 2.0 + delta.y) / image_size[1] as f32,
+    ];
+
+    let frame_rect = egui::Rect::from_min_size(
+        rect.min + egui::vec2(frame.x, frame.y) * scale,
+        egui::vec2(frame.width, frame.height) * scale,
+    );
+    let shade = egui::Color32::from_black_alpha(160);
+    let painter = ui.painter_at(rect);
+    for outside in [
+        egui::Rect::from_min_max(rect.min, egui::pos2(rect.max.x, frame_rect.min.y)),
+        egui::Rect::from_min_max(egui::pos2(rect.min.x, frame_rect.max.y), rect.max),
+        egui::Rect::from_min_max(
+            egui::pos2(rect.min.x, frame_rect.min.y),
+            egui::pos2(frame_rect.min.x, frame_rect.max.y),
+        ),
+        egui::Rect::from_min_max(
+            egui::pos2(frame_rect.max.x, frame_rect.min.y),
+            egui::pos2(rect.max.x, frame_rect.max.y),
+        ),
+    ] {
+        painter.rect_filled(outside, 0.0, shade);
+    }
+    painter.rect_stroke(
+        frame_rect,
+        0.0,
+        egui::Stroke::new(2.0, egui::Color32::WHITE),
+    );
+}
+
+// Downscale for display without changing the aspect ratio.
+fn fitted_color_image(image: &image::DynamicImage, max_size: egui::Vec2) -> egui::ColorImage {
+    let fitted = image.thumbnail(max_size.x as u32, max_size.y as u32);
+    egui::ColorImage::from_rgba_unmultiplied(
+        [fitted.width() as usize, fitted.height() as usize],
+        &fitted.to_rgba8(),
+    )
+}
@@ -0,0 +1,247 @@
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::backups;
+
+pub const PROVENANCE_FILE: &str = "provenance.json";
+// Recorded for title cards saved in place of an image, e.g. while offline.
+pub const PLACEHOLDER_PROVIDER: &str = "Placeholder";
+
+// Where an entry's image came from, so it can be credited or fetched again
+// at a better size.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImageProvenance {
+    // How the image was found: a search provider, a catalogue, "Clipboard"...
+    pub provider: String,
+    // The web address or local path it was read from, when known.
+    pub source: Option<String>,
+    pub query: Option<String>,
+    // Seconds since the Unix epoch, set when the image is saved.
+    pub saved: u64,
+    // The size before it was framed and resized for the entry.
+    pub original_size: [u32; 2],
+}
+
+impl ImageProvenance {
+    pub fn new(
+        provider: impl Into<String>,
+        source: Option<String>,
+        query: Option<String>,
+        image: &image::DynamicImage,
+    ) -> Self {
+        Self {
+            provider: provider.into(),
+            source,
+            query,
+            saved: 0,
+            original_size: [image.width(), image.height()],
+        }
+    }
+
+    pub fn with_original_size(mut self, original_size: [u32; 2]) -> Self {
+        self.original_size = original_size;
+        self
+    }
+
+    pub fn summary(&self) -> String {
+        let mut summary = self.provider.clone();
+        if let Some(query) = &self.query {
+            summary.push_str(&format!(", \"{query}\""));
+        }
+        let [width, height] = self.original_size;
+        if width > 0 && height > 0 {
+            summary.push_str(&format!(", {width}×{height}"));
+        }
+        if let Some(saved) =
+            DateTime::from_timestamp(self.saved as i64, 0).filter(|_| self.saved > 0)
+        {
+            summary.push_str(&format!(", saved {}", saved.format("%Y-%m-%d")));
+        }
+        summary
+    }
+}
+
+// The provenance of every entry's image, kept beside the images as
+// category -> entry -> record.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProvenanceManifest {
+    entries: BTreeMap<String, BTreeMap<String, ImageProvenance>>,
+    // Perceptual hashes of the saved files, taken when they are saved or
+    // loaded so image checks need not decode every image. Kept apart, as
+    // images from before the manifest have a hash but no known source.
+    hashes: BTreeMap<String, BTreeMap<String, u64>>,
+}
+
+impl ProvenanceManifest {
+    // A missing or unreadable manifest starts empty; images keep working
+    // without it.
+    pub fn load(image_directory: &Path) -> Self {
+        let Ok(json) = fs::read_to_string(manifest_path(image_directory)) else {
+            return Self::default();
+        };
+        serde_json::from_str(&json).unwrap_or_else(|e| {
+            eprintln!("Ignoring unreadable image provenance: {e}");
+            Self::default()
+        })
+    }
+
+    pub fn save(&self, image_directory: &Path) -> io::Result<()> {
+        fs::create_dir_all(image_directory)?;
+        let json = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        backups::write_atomically(&manifest_path(image_directory), json.as_bytes())
+    }
+
+    pub fn get(&self, category: &str, entry: &str) -> Option<&ImageProvenance> {
+        self.entries.get(category)?.get(entry)
+    }
+
+    // A record for a newly saved image, whose hash is not known yet.
+    pub fn set(&mut self, category: &str, entry: &str, mut provenance: ImageProvenance) {
+        self.remove_hash(category, entry);
+        provenance.saved = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        self.restore(category, entry, provenance);
+    }
+
+    // Puts back a record as it was, keeping when it was saved.
+    pub fn restore(&mut self, category: &str, entry: &str, provenance: ImageProvenance) {
+        self.entries
+            .entry(category.to_string())
+            .or_default()
+            .insert(entry.to_string(), provenance);
+    }
+
+    pub fn remove(&mut self, category: &str, entry: &str) -> Option<ImageProvenance> {
+        remove_from(&mut self.entries, category, entry)
+    }
+
+    // Entries whose image came from `provider`, as (category, entry).
+    pub fn entries_from(&self, provider: &str) -> Vec<(String, String)> {
+        self.entries
+            .iter()
+            .flat_map(|(category, entries)| {
+                entries
+                    .iter()
+                    .filter(|(_, provenance)| provenance.provider == provider)
+                    .map(|(entry, _)| (category.clone(), entry.clone()))
+            })
+            .collect()
+    }
+
+    pub fn hash(&self, category: &str, entry: &str) -> Option<u64> {
+        self.hashes.get(category)?.get(entry).copied()
+    }
+
+    // Returns whether anything changed.
+    pub fn set_hash(&mut self, category: &str, entry: &str, hash: u64) -> bool {
+        self.hashes
+            .entry(category.to_string())
+            .or_default()
+            .insert(entry.to_string(), hash)
+            != Some(hash)
+    }
+
+    pub fn remove_hash(&mut self, category: &str, entry: &str) -> Option<u64> {
+        remove_from(&mut self.hashes, category, entry)
+    }
+
+    // Moves the entry's record and hash. Returns whether it had either.
+    pub fn rename(&mut self, category: &str, old_entry: &str, new_entry: &str) -> bool {
+        let provenance = self.remove(category, old_entry);
+        let hash = self.remove_hash(category, old_entry);
+        if let Some(provenance) = provenance.clone() {
+            self.restore(category, new_entry, provenance);
+        }
+        if let Some(hash) = hash {
+            self.set_hash(category, new_entry, hash);
+        }
+        provenance.is_some() || hash.is_some()
+    }
+}
+
+fn remove_from<T>(
+    map: &mut BTreeMap<String, BTreeMap<String, T>>,
+    category: &str,
+    entry: &str,
+) -> Option<T> {
+    let entries = map.get_mut(category)?;
+    let removed = entries.remove(entry);
+    if entries.is_empty() {
+        map.remove(category);
+    }
+    removed
+}
+
+pub fn manifest_path(image_directory: &Path) -> PathBuf {
+    image_directory.join(PROVENANCE_FILE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn manifest_follows_renames_and_deletes_and_survives_a_reload() {
+        let image_directory = env::temp_dir().join(format!(
+            "media-rating-provenance-test-{}",
+            std::process::id()
+        ));
+        fs::remove_dir_all(&image_directory).ok();
+
+        let poster = image::DynamicImage::new_rgb8(600, 900);
+        let mut manifest = ProvenanceManifest::load(&image_directory);
+        manifest.set(
+            "Movies",
+            "Dune",
+            ImageProvenance::new(
+                "DuckDuckGo",
+                Some("https://example.com/dune.jpg".to_string()),
+                Some("Dune (Movies)".to_string()),
+                &poster,
+            ),
+        );
+        manifest.set(
+            "Movies",
+            "Alien",
+            ImageProvenance::new("Clipboard", None, None, &poster).with_original_size([10, 20]),
+        );
+        assert!(manifest.get("Movies", "Dune").unwrap().saved > 0);
+        assert_eq!(
+            manifest.entries_from("Clipboard"),
+            vec![("Movies".to_string(), "Alien".to_string())]
+        );
+
+        assert!(manifest.rename("Movies", "Dune", "Dune: Part One"));
+        assert_eq!(manifest.get("Movies", "Dune"), None);
+        assert_eq!(
+            manifest
+                .get("Movies", "Dune: Part One")
+                .unwrap()
+                .original_size,
+            [600, 900]
+        );
+        manifest.set_hash("Movies", "Dune: Part One", 7);
+        manifest.set_hash("Movies", "Heat", 9);
+        assert!(manifest.rename("Movies", "Heat", "Heat (1995)"));
+        assert_eq!(manifest.get("Movies", "Heat (1995)"), None);
+        assert_eq!(manifest.hash("Movies", "Heat (1995)"), Some(9));
+        manifest.remove("Movies", "Alien");
+
+        manifest.save(&image_directory).unwrap();
+        let reloaded = ProvenanceManifest::load(&image_directory);
+        assert_eq!(reloaded, manifest);
+        assert_eq!(reloaded.get("Movies", "Alien"), None);
+
+        fs::remove_dir_all(image_directory).ok();
+    }
+}
@@ -0,0 +1,441 @@
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    image_search::{Image, ImageLocation},
+    image_store::ImageFetchError,
+};
+
+const LOCAL_IMAGE_EXTENSIONS: [&str; 6] = ["png", "jpg", "jpeg", "webp", "bmp", "gif"];
+// DuckDuckGo pages by result offset, 100 at a time.
+const DDG_PAGE_SIZE: usize = 100;
+const LOCAL_PAGE_SIZE: usize = 72;
+
+// A source of candidate images for a search query. Candidates are downloaded,
+// ranked and resized by `image_search::search_many`. `page` counts from 0;
+// an empty page means there are no more.
+pub trait ImageProvider: Send {
+    fn find_images(
+        &self,
+        client: &Client,
+        query: &str,
+        page: usize,
+    ) -> Result<Vec<Image>, ImageFetchError>;
+}
+
+// Which provider a category searches, as stored in the settings.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ImageProviderConfig {
+    #[default]
+    DuckDuckGo,
+    LocalFolder {
+        directory: PathBuf,
+    },
+    JsonApi(JsonApiConfig),
+}
+
+impl ImageProviderConfig {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ImageProviderConfig::DuckDuckGo => "DuckDuckGo",
+            ImageProviderConfig::LocalFolder { .. } => "Local Folder",
+            ImageProviderConfig::JsonApi(_) => "JSON API",
+        }
+    }
+
+    pub fn needs_network(&self) -> bool {
+        !matches!(self, ImageProviderConfig::LocalFolder { .. })
+    }
+
+    // Local files are quick to search and may change, so only remote
+    // results are worth keeping in the search cache.
+    pub fn caches_results(&self) -> bool {
+        !matches!(self, ImageProviderConfig::LocalFolder { .. })
+    }
+
+    // The `host:port` to try when checking whether the provider can be
+    // reached, if it needs the network.
+    pub fn probe_address(&self) -> Option<String> {
+        match self {
+            ImageProviderConfig::DuckDuckGo => Some("duckduckgo.com:443".to_string()),
+            ImageProviderConfig::LocalFolder { .. } => None,
+            ImageProviderConfig::JsonApi(config) => {
+                let url = config
+                    .url_template
+                    .replace("{query}", "")
+                    .replace("{page}", "1");
+                let url = reqwest::Url::parse(url.trim()).ok()?;
+                Some(format!(
+                    "{}:{}",
+                    url.host_str()?,
+                    url.port_or_known_default()?
+                ))
+            }
+        }
+    }
+
+    pub fn build(&self) -> Box<dyn ImageProvider> {
+        match self {
+            ImageProviderConfig::DuckDuckGo => Box::new(DuckDuckGoProvider),
+            ImageProviderConfig::LocalFolder { directory } => Box::new(LocalFolderProvider {
+                directory: directory.clone(),
+            }),
+            ImageProviderConfig::JsonApi(config) => Box::new(JsonApiProvider {
+                config: config.clone(),
+            }),
+        }
+    }
+}
+
+// A generic JSON search API. `{query}` in the URL template is replaced by the
+// encoded query and `{page}` by the page number from 1; paths are dot
+// separated, with numbers indexing into arrays.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JsonApiConfig {
+    pub url_template: String,
+    pub results_path: String,
+    pub url_path: String,
+    pub width_path: String,
+    pub height_path: String,
+}
+
+pub struct DuckDuckGoProvider;
+
+impl ImageProvider for DuckDuckGoProvider {
+    fn find_images(
+        &self,
+        client: &Client,
+        query: &str,
+        page: usize,
+    ) -> Result<Vec<Image>, ImageFetchError> {
+        let html = client
+            .get("https://duckduckgo.com/")
+            .query(&[("q", query), ("iax", "images"), ("ia", "images")])
+            .send()?
+            .text()?;
+        let vqd = extract_vqd(&html).ok_or(ImageFetchError {
+            details: "Failed to extract DuckDuckGo vqd token".to_string(),
+        })?;
+
+        let json_text = client
+            .get("https://duckduckgo.com/i.js")
+            .query(&[("q", query), ("vqd", vqd.as_str()), ("o", "js")])
+            .query(&[("s", (page * DDG_PAGE_SIZE).to_string())])
+            .header("Referer", "https://duckduckgo.com/")
+            .send()?
+            .text()?;
+
+        Ok(get_ddg_image_urls(json_text))
+    }
+}
+
+// Images in a folder whose file names share a word with the query, best
+// matches first.
+pub struct LocalFolderProvider {
+    pub directory: PathBuf,
+}
+
+impl ImageProvider for LocalFolderProvider {
+    fn find_images(
+        &self,
+        _client: &Client,
+        query: &str,
+        page: usize,
+    ) -> Result<Vec<Image>, ImageFetchError> {
+        let words = query_words(query);
+        let mut matches: Vec<(usize, PathBuf)> = fs::read_dir(&self.directory)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| is_local_image(path))
+            .filter_map(|path| {
+                let stem = path.file_stem()?.to_string_lossy().to_lowercase();
+                let score = words.iter().filter(|word| stem.contains(*word)).count();
+                (score > 0).then_some((score, path))
+            })
+            .collect();
+        matches.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+
+        Ok(matches
+            .into_iter()
+            .skip(page * LOCAL_PAGE_SIZE)
+            .take(LOCAL_PAGE_SIZE)
+            .map(|(_, path)| {
+                let (width, height) = image::image_dimensions(&path).unwrap_or_default();
+                Image {
+                    location: ImageLocation::File(path),
+                    width: width.into(),
+                    height: height.into(),
+                }
+            })
+            .collect())
+    }
+}
+
+pub struct JsonApiProvider {
+    pub config: JsonApiConfig,
+}
+
+impl ImageProvider for JsonApiProvider {
+    fn find_images(
+        &self,
+        client: &Client,
+        query: &str,
+        page: usize,
+    ) -> Result<Vec<Image>, ImageFetchError> {
+        let config = &self.config;
+        if config.url_template.trim().is_empty() {
+            return Err(ImageFetchError {
+                details: "The JSON API has no URL configured".to_string(),
+            });
+        }
+        // Without a page parameter the API only has the one page.
+        if page > 0 && !config.url_template.contains("{page}") {
+            return Ok(Vec::new());
+        }
+
+        let url = config
+            .url_template
+            .replace("{query}", &encode_query_component(query))
+            .replace("{page}", &(page + 1).to_string());
+        let base = reqwest::Url::parse(&url).map_err(|e| ImageFetchError {
+            details: format!("Invalid JSON API URL: {e}"),
+        })?;
+        let text = client
+            .get(base.clone())
+            .send()?
+            .error_for_status()?
+            .text()?;
+        let json: serde_json::Value = serde_json::from_str(&text).map_err(|e| ImageFetchError {
+            details: format!("The JSON API returned invalid JSON: {e}"),
+        })?;
+
+        let results = json_path(&json, &config.results_path)
+            .and_then(serde_json::Value::as_array)
+            .ok_or(ImageFetchError {
+                details: format!("No array found at \"{}\"", config.results_path),
+            })?;
+
+        Ok(results
+            .iter()
+            .filter_map(|result| {
+                // Relative links are resolved against the API URL.
+                let url = base
+                    .join(json_path(result, &config.url_path)?.as_str()?)
+                    .ok()?;
+                let dimension = |path: &str| {
+                    json_path(result, path)
+                        .and_then(serde_json::Value::as_i64)
+                        .unwrap_or_default()
+                };
+                Some(Image {
+                    location: ImageLocation::Url(url.into()),
+                    width: dimension(&config.width_path),
+                    height: dimension(&config.height_path),
+                })
+            })
+            .collect())
+    }
+}
+
+// Follow a dot separated path such as `data.items.0.url`. An empty path is the
+// value itself.
+pub fn json_path<'a>(value: &'a serde_json::Value, path: &str) -> Option<&'a serde_json::Value> {
+    path.split('.')
+        .filter(|segment| !segment.is_empty())
+        .try_fold(value, |value, segment| match value {
+            serde_json::Value::Array(items) => items.get(segment.parse::<usize>().ok()?),
+            _ => value.get(segment),
+        })
+}
+
+fn encode_query_component(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+fn query_words(query: &str) -> Vec<String> {
+    query
+        .split(|ch: char| !ch.is_alphanumeric())
+        .filter(|word| word.len() > 1)
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn is_local_image(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| {
+        LOCAL_IMAGE_EXTENSIONS
+            .iter()
+            .any(|known| extension.eq_ignore_ascii_case(known))
+    })
+}
+
+fn extract_vqd(html: &str) -> Option<String> {
+    let patterns = ["vqd=\"", "vqd='"];
+
+    for pat in patterns {
+        if let Some(start) = html.find(pat) {
+            let start = start + pat.len();
+            let rest = &html[start..];
+            if let Some(end) = rest.find(['"', '\'']) {
+                return Some(rest[..end].to_string());
+            }
+        }
+    }
+
+    None
+}
+
+fn get_ddg_image_urls(json_response: String) -> Vec<Image> {
+    let json_res: serde_json::Value = match serde_json::from_str(&json_response) {
+        Ok(val) => val,
+        Err(_) => return vec![],
+    };
+
+    json_res
+        .get("results")
+        .and_then(|v| v.as_array())
+        .unwrap_or(&vec![])
+        .iter()
+        .map(|item| Image {
+            location: ImageLocation::Url(
+                item.get("image")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or_default()
+                    .to_string(),
+            ),
+            width: item
+                .get("width")
+                .and_then(|v| v.as_i64())
+                .unwrap_or_default(),
+            height: item
+                .get("height")
+                .and_then(|v| v.as_i64())
+                .unwrap_or_default(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{image_search, image_store::ImageGeometry, test_server::TestServer};
+    use std::{env, io::Cursor};
+
+    fn png_bytes(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        image::DynamicImage::new_rgb8(width, height)
+            .write_to(&mut Cursor::new(&mut bytes), image::ImageFormat::Png)
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn json_api_provider_finds_and_downloads_images_from_configured_paths() {
+        let server = TestServer::start(|path| {
+            if path.starts_with("/search?q=Alien%20%28Movies%29") {
+                let body = serde_json::json!({
+                    "data": {"items": [
+                        {"link": "/wide.png", "size": {"w": 40, "h": 10}},
+                        {"link": "/tall.png", "size": {"w": 8, "h": 10}},
+                        {"title": "no link"},
+                    ]}
+                });
+                Some(("application/json", body.to_string().into_bytes()))
+            } else if path == "/wide.png" {
+                Some(("image/png", png_bytes(40, 10)))
+            } else if path == "/tall.png" {
+                Some(("image/png", png_bytes(8, 10)))
+            } else {
+                None
+            }
+        });
+
+        let config = ImageProviderConfig::JsonApi(JsonApiConfig {
+            url_template: format!("{}/search?q={{query}}", server.url()),
+            results_path: "data.items".to_string(),
+            url_path: "link".to_string(),
+            width_path: "size.w".to_string(),
+            height_path: "size.h".to_string(),
+        });
+        assert_eq!(
+            config.probe_address().as_deref(),
+            server.url().strip_prefix("http://")
+        );
+        let provider = config.build();
+        let client = Client::new();
+
+        let found = provider.find_images(&client, "Alien (Movies)", 0).unwrap();
+        assert_eq!(
+            found
+                .iter()
+                .map(|image| (image.location.clone(), image.width))
+                .collect::<Vec<_>>(),
+            vec![
+                (ImageLocation::Url(format!("{}/wide.png", server.url())), 40),
+                (ImageLocation::Url(format!("{}/tall.png", server.url())), 8),
+            ]
+        );
+
+        let results = image_search::search_many(
+            provider.as_ref(),
+            &image_search::SearchRequest::new(
+                "Alien (Movies)",
+                ImageGeometry {
+                    width: 4,
+                    height: 5,
+                },
+                1,
+            ),
+        )
+        .unwrap();
+        assert_eq!(results.len(), 1);
+        let image = &results[0].image;
+        assert!([(40, 10), (8, 10)].contains(&(image.width(), image.height())));
+        assert!(server.requests().iter().any(|path| path.ends_with(".png")));
+    }
+
+    #[test]
+    fn local_folder_provider_ranks_files_by_matching_words() {
+        let directory = env::temp_dir().join(format!(
+            "media-rating-local-provider-test-{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("Alien Movies.png"), png_bytes(4, 5)).unwrap();
+        fs::write(directory.join("Aliens.jpg"), png_bytes(4, 5)).unwrap();
+        fs::write(directory.join("Dune.png"), png_bytes(4, 5)).unwrap();
+        fs::write(directory.join("Alien notes.txt"), b"not an image").unwrap();
+
+        let provider = LocalFolderProvider {
+            directory: directory.clone(),
+        };
+        let found = provider
+            .find_images(&Client::new(), "Alien (Movies)", 0)
+            .unwrap();
+
+        assert_eq!(
+            found
+                .iter()
+                .map(|image| image.location.clone())
+                .collect::<Vec<_>>(),
+            vec![
+                ImageLocation::File(directory.join("Alien Movies.png")),
+                ImageLocation::File(directory.join("Aliens.jpg")),
+            ]
+        );
+        assert_eq!((found[0].width, found[0].height), (4, 5));
+
+        fs::remove_dir_all(directory).ok();
+    }
+}
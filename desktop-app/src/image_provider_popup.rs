@@ -0,0 +1,174 @@
+use eframe::egui;
+use native_dialog::FileDialog;
+use std::path::PathBuf;
+
+use crate::{
+    app::AppAction,
+    image_provider::{ImageProviderConfig, JsonApiConfig},
+    image_store::ImageGeometry,
+    popup::{Popup, PopupResponse},
+};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ProviderKind {
+    DuckDuckGo,
+    LocalFolder,
+    JsonApi,
+}
+
+// Chooses where the image picker searches for one category and the size its
+// images are stored at. Every kind keeps its fields while switching, so
+// flipping back and forth loses nothing.
+pub struct ImageProviderPopup {
+    category: String,
+    kind: ProviderKind,
+    directory: Option<PathBuf>,
+    json_api: JsonApiConfig,
+    geometry: ImageGeometry,
+}
+
+impl ImageProviderPopup {
+    pub fn new(category: String, provider: ImageProviderConfig, geometry: ImageGeometry) -> Self {
+        let mut popup = Self {
+            category,
+            kind: ProviderKind::DuckDuckGo,
+            directory: None,
+            json_api: JsonApiConfig::default(),
+            geometry,
+        };
+
+        match provider {
+            ImageProviderConfig::DuckDuckGo => {}
+            ImageProviderConfig::LocalFolder { directory } => {
+                popup.kind = ProviderKind::LocalFolder;
+                popup.directory = Some(directory);
+            }
+            ImageProviderConfig::JsonApi(config) => {
+                popup.kind = ProviderKind::JsonApi;
+                popup.json_api = config;
+            }
+        }
+
+        popup
+    }
+
+    fn provider(&self) -> Option<ImageProviderConfig> {
+        match self.kind {
+            ProviderKind::DuckDuckGo => Some(ImageProviderConfig::DuckDuckGo),
+            ProviderKind::LocalFolder => self
+                .directory
+                .clone()
+                .map(|directory| ImageProviderConfig::LocalFolder { directory }),
+            ProviderKind::JsonApi => (!self.json_api.url_template.trim().is_empty())
+                .then(|| ImageProviderConfig::JsonApi(self.json_api.clone())),
+        }
+    }
+}
+
+impl Popup for ImageProviderPopup {
+    fn title(&self) -> &str {
+        "Image Settings"
+    }
+
+    fn show_body(&mut self, ui: &mut egui::Ui) -> PopupResponse {
+        let mut response = PopupResponse::KeepOpen;
+
+        ui.label(format!("Where to search for images in {}", self.category));
+        ui.horizontal(|ui| {
+            ui.radio_value(&mut self.kind, ProviderKind::DuckDuckGo, "DuckDuckGo");
+            ui.radio_value(&mut self.kind, ProviderKind::LocalFolder, "Local Folder");
+            ui.radio_value(&mut self.kind, ProviderKind::JsonApi, "JSON API");
+        });
+        ui.add_space(8.0);
+
+        match self.kind {
+            ProviderKind::DuckDuckGo => {}
+            ProviderKind::LocalFolder => {
+                ui.horizontal(|ui| {
+                    match &self.directory {
+                        Some(directory) => ui.label(directory.display().to_string()),
+                        None => ui.label("No folder chosen"),
+                    };
+                    if ui.button("Browse").clicked() {
+                        if let Some(directory) =
+                            FileDialog::new().show_open_single_dir().ok().flatten()
+                        {
+                            self.directory = Some(directory);
+                        }
+                    }
+                });
+            }
+            ProviderKind::JsonApi => {
+                egui::Grid::new("json-api-provider")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        let config = &mut self.json_api;
+                        for (label, value, hint) in [
+                            (
+                                "URL",
+                                &mut config.url_template,
+                                "https://example.com/search?q={query}&page={page}",
+                            ),
+                            ("Results", &mut config.results_path, "data.items"),
+                            ("Image URL", &mut config.url_path, "link"),
+                            ("Width", &mut config.width_path, "image.width"),
+                            ("Height", &mut config.height_path, "image.height"),
+                        ] {
+                            ui.label(label);
+                            ui.add_sized(
+                                [320.0, 20.0],
+                                egui::TextEdit::singleline(value).hint_text(hint),
+                            );
+                            ui.end_row();
+                        }
+                    });
+            }
+        }
+
+        ui.add_space(8.0);
+        ui.label("Image size");
+        ui.horizontal(|ui| {
+            for (name, preset) in ImageGeometry::PRESETS {
+                ui.radio_value(&mut self.geometry, preset, name);
+            }
+        });
+        ui.horizontal(|ui| {
+            let sides = ImageGeometry::MIN_SIDE..=ImageGeometry::MAX_SIDE;
+            ui.add(egui::DragValue::new(&mut self.geometry.width).range(sides.clone()));
+            ui.label("x");
+            ui.add(egui::DragValue::new(&mut self.geometry.height).range(sides));
+        });
+        ui.label(
+            egui::RichText::new(
+                "Existing images are cropped to fit when shown, and kept as they are on disk.",
+            )
+            .small()
+            .weak(),
+        );
+
+        ui.add_space(8.0);
+        let provider = self.provider();
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(provider.is_some(), egui::Button::new("Save"))
+                .clicked()
+            {
+                if let Some(provider) = provider {
+                    response = PopupResponse::Action(AppAction::SetImageProvider {
+                        category: self.category.clone(),
+                        provider,
+                        geometry: self.geometry,
+                    });
+                }
+            }
+
+            ui.add_space(50.0);
+
+            if ui.button("Cancel").clicked() {
+                response = PopupResponse::Close;
+            }
+        });
+
+        response
+    }
+}
@@ -1,156 +1,758 @@
-use crate::image_store::ImageFetchError;
+use crate::{
+    image_provider::{ImageProvider, ImageProviderConfig},
+    image_store::{ImageFetchError, ImageGeometry},
+    perceptual_hash,
+    search_cache::{CachedSearch, SearchCache},
+};
 use core::f32;
 use image::DynamicImage;
 use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, RecvTimeoutError},
+        Arc, Mutex, OnceLock,
+    },
+    thread,
+    time::{Duration, Instant},
+};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ImageLocation {
+    Url(String),
+    File(PathBuf),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Image {
-    pub url: String,
+    pub location: ImageLocation,
+    // Zero when the provider does not know the size.
     pub width: i64,
     pub height: i64,
 }
 
-/// DuckDuckGo image search (HTML + JSON scraping).
-/// Returns usable images closest to the requested aspect ratio and resizes them.
+const MAX_RESULT_SIDE: u32 = 1600;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+// For each request, so one slow host cannot hold up a whole search.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const SEARCH_DEADLINE: Duration = Duration::from_secs(30);
+const DOWNLOAD_WORKERS: usize = 6;
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Searches a provider and downloads the usable images closest to the
+/// requested aspect ratio.
 pub fn search_many(
-    query: &str,
-    width: u32,
-    height: u32,
-    count: usize,
-) -> Result<Vec<DynamicImage>, ImageFetchError> {
-    let client = reqwest::blocking::Client::builder()
-        .cookie_store(true)
-        .user_agent("Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7)")
-        .build()?;
-
-    let html = client
-        .get("https://duckduckgo.com/")
-        .query(&[("q", query), ("iax", "images"), ("ia", "images")])
-        .send()?
-        .text()?;
-    let vqd = extract_vqd(&html).ok_or(ImageFetchError {
-        details: "Failed to extract DuckDuckGo vqd token".to_string(),
-    })?;
-
-    let json_text = client
-        .get("https://duckduckgo.com/i.js")
-        .query(&[("q", query), ("vqd", vqd.as_str()), ("o", "js")])
-        .header("Referer", "https://duckduckgo.com/")
-        .send()?
-        .text()?;
-    let image_urls = get_ddg_image_urls(json_text);
+    provider: &dyn ImageProvider,
+    request: &SearchRequest,
+) -> Result<Vec<SearchResult>, ImageFetchError> {
+    let mut results = Vec::new();
+    download_matches(
+        provider,
+        request,
+        &AtomicBool::new(false),
+        &mut |_, result| results.push(result),
+    )?;
+    Ok(results)
+}
+
+pub struct SearchRequest<'a> {
+    pub query: &'a str,
+    pub geometry: ImageGeometry,
+    pub count: usize,
+    // Search again even if the results are cached.
+    pub refresh: bool,
+    // Which page of the provider's results, from 0.
+    pub page: usize,
+    // Sources already shown, not downloaded again.
+    pub skip: &'a HashSet<String>,
+    // Candidates the previous page did not get to. They are downloaded before
+    // the provider is asked for `page`.
+    pub candidates: &'a [Image],
+}
+
+impl<'a> SearchRequest<'a> {
+    // The first page, from the cache if fresh, skipping nothing.
+    pub fn new(query: &'a str, geometry: ImageGeometry, count: usize) -> Self {
+        static NOTHING: OnceLock<HashSet<String>> = OnceLock::new();
+        Self {
+            query,
+            geometry,
+            count,
+            refresh: false,
+            page: 0,
+            skip: NOTHING.get_or_init(HashSet::new),
+            candidates: &[],
+        }
+    }
+}
+
+// How a page of downloads went.
+struct Downloaded {
+    // Whether the page had any results, as a hint that the next one might too.
+    more: bool,
+    // Whether the deadline cut the downloads short, so the results are not
+    // all the page had to offer.
+    timed_out: bool,
+    leftover: Vec<Image>,
+}
+
+pub struct SearchOutcome {
+    // Whether the provider may have another page, if `page` was searched;
+    // `None` when the candidates left over were enough.
+    pub more: Option<bool>,
+    // Candidates not downloaded yet, for the next request.
+    pub leftover: Vec<Image>,
+}
+
+pub struct SearchResult {
+    pub source: String,
+    // Perceptual hash; copies of one picture hash alike whatever their source.
+    pub hash: u64,
+    pub image: DynamicImage,
+    // The size of the file, before a huge image was scaled down.
+    pub original_size: [u32; 2],
+}
+
+/// Hands each image to `on_image` as soon as it is ready. Candidates left
+/// over from the previous page come first; the provider is only searched
+/// once they run out. Results come from the cache while its entry is fresh,
+/// and from a stale entry when the search fails. Setting `cancelled` stops
+/// the search early without an error.
+pub fn search_incremental(
+    config: &ImageProviderConfig,
+    cache: Option<&SearchCache>,
+    request: &SearchRequest,
+    cancelled: &AtomicBool,
+    on_image: &mut dyn FnMut(SearchResult),
+) -> Result<SearchOutcome, ImageFetchError> {
+    if !request.candidates.is_empty() {
+        // When none of them can be used, the next page is searched instead.
+        if let Ok(downloaded) = download_candidates(
+            &client()?,
+            request.candidates.to_vec(),
+            request,
+            cancelled,
+            &mut |_, result| on_image(result),
+        ) {
+            return Ok(SearchOutcome {
+                more: None,
+                leftover: downloaded.leftover,
+            });
+        }
+    }
+
+    let cache = cache.filter(|_| config.caches_results());
+    let key = SearchCache::key(config, request.query, request.geometry, request.page);
+    if let Some(cached) = cache
+        .filter(|_| !request.refresh)
+        .and_then(|cache| cache.get(&key, false))
+    {
+        if let Some(outcome) = deliver_cached(cached, request, on_image) {
+            return Ok(outcome);
+        }
+    }
+
+    let provider = config.build();
+    let mut downloaded = Vec::new();
+    let result = download_matches(
+        provider.as_ref(),
+        request,
+        cancelled,
+        &mut |bytes, result| {
+            downloaded.push((result.source.clone(), bytes));
+            on_image(result);
+        },
+    );
+
+    match (result, cache) {
+        // Cut short results would stand in for the whole page, so only
+        // complete ones are cached.
+        (Ok(page), Some(cache)) if !page.timed_out && !cancelled.load(Ordering::SeqCst) => {
+            let cached = CachedSearch {
+                results: downloaded,
+                more: page.more,
+                leftover: page.leftover,
+            };
+            if let Err(e) = cache.put(&key, request.query, &cached) {
+                eprintln!("Could not cache search results: {e}");
+            }
+            Ok(SearchOutcome {
+                more: Some(cached.more),
+                leftover: cached.leftover,
+            })
+        }
+        (Ok(page), _) => Ok(SearchOutcome {
+            more: Some(page.more),
+            leftover: page.leftover,
+        }),
+        (Err(e), cache) => cache
+            .and_then(|cache| cache.get(&key, true))
+            .and_then(|cached| deliver_cached(cached, request, on_image))
+            .ok_or(e),
+    }
+}
 
+// The cached page as it was first searched, if any result could be used.
+fn deliver_cached(
+    cached: CachedSearch,
+    request: &SearchRequest,
+    on_image: &mut dyn FnMut(SearchResult),
+) -> Option<SearchOutcome> {
+    let mut delivered = false;
+    let mut seen = Vec::new();
+    for (source, bytes) in cached.results {
+        if request.skip.contains(&source) {
+            continue;
+        }
+        if let Ok((image, original_size)) = usable_image(&bytes) {
+            let hash = perceptual_hash::dhash(&image);
+            if !first_of_its_kind(&mut seen, hash) {
+                continue;
+            }
+            on_image(SearchResult {
+                source,
+                hash,
+                image,
+                original_size,
+            });
+            delivered = true;
+        }
+    }
+    delivered.then_some(SearchOutcome {
+        more: Some(cached.more),
+        leftover: cached.leftover,
+    })
+}
+
+// Images less than half the entry image size would look blurry.
+fn big_enough(width: i64, height: i64, geometry: ImageGeometry) -> bool {
+    width * 2 >= i64::from(geometry.width) && height * 2 >= i64::from(geometry.height)
+}
+
+// Searches one page of the provider, then downloads its best candidates.
+fn download_matches(
+    provider: &dyn ImageProvider,
+    request: &SearchRequest,
+    cancelled: &AtomicBool,
+    on_result: &mut dyn FnMut(Vec<u8>, SearchResult),
+) -> Result<Downloaded, ImageFetchError> {
+    let client = client()?;
+    let image_urls = provider.find_images(&client, request.query, request.page)?;
+    let more = !image_urls.is_empty();
+
+    let candidates = ranked_candidates(image_urls, request.geometry);
+    if candidates.is_empty() {
+        return Ok(Downloaded {
+            more,
+            timed_out: false,
+            leftover: Vec::new(),
+        });
+    }
+    download_candidates(&client, candidates, request, cancelled, on_result)
+        .map(|downloaded| Downloaded { more, ..downloaded })
+}
+
+// Usable candidates, closest to the requested aspect ratio first.
+fn ranked_candidates(image_urls: Vec<Image>, geometry: ImageGeometry) -> Vec<Image> {
+    let target_ratio = geometry.width as f32 / geometry.height as f32;
     let mut images_with_ratio: Vec<(f32, Image)> = image_urls
         .into_iter()
-        .filter(|img| img.width > 0 && img.height > 0 && !img.url.is_empty())
+        .filter(|img| img.location != ImageLocation::Url(String::new()))
+        // Sizes the provider reports spare downloading images too small.
+        .filter(|img| {
+            img.width <= 0 || img.height <= 0 || big_enough(img.width, img.height, geometry)
+        })
         .map(|img| {
-            let ratio_diff =
-                f32::abs(img.width as f32 / img.height as f32 - width as f32 / height as f32);
+            // Images of unknown size rank after every known one.
+            let ratio_diff = if img.width > 0 && img.height > 0 {
+                f32::abs(img.width as f32 / img.height as f32 - target_ratio)
+            } else {
+                f32::MAX
+            };
             (ratio_diff, img)
         })
         .collect();
     images_with_ratio.sort_by(|a, b| a.0.total_cmp(&b.0));
-    let top_matches: Vec<Image> = images_with_ratio
+    images_with_ratio.into_iter().map(|(_, img)| img).collect()
+}
+
+// Downloads the best ranked candidates on several threads and reports each
+// usable one, with its raw bytes, on this thread. Stops once `count` images
+// arrived, at the search deadline or when cancelled; downloads still running
+// then finish in the background. The candidates never started are returned
+// for the next page of results.
+fn download_candidates(
+    client: &reqwest::blocking::Client,
+    candidates: Vec<Image>,
+    request: &SearchRequest,
+    cancelled: &AtomicBool,
+    on_result: &mut dyn FnMut(Vec<u8>, SearchResult),
+) -> Result<Downloaded, ImageFetchError> {
+    let SearchRequest {
+        geometry,
+        count,
+        skip,
+        ..
+    } = *request;
+    let deadline = Instant::now() + SEARCH_DEADLINE;
+
+    let mut candidates: Vec<Image> = candidates
         .into_iter()
-        .take(count.saturating_mul(4).max(count))
-        .map(|(_, img)| img)
+        .filter(|img| !skip.contains(&source_name(&img.location)))
         .collect();
+    // A single image, as for bulk fetches, is the best-ranked one that can be
+    // used. Otherwise the best few are tried in random order, so searching
+    // again can turn up other pictures; the rest wait their turn in rank order.
+    let mut in_rank_order = count == 1;
+    if !in_rank_order {
+        let window = count.saturating_mul(4).max(count).min(candidates.len());
+        candidates[..window].shuffle(&mut rand::thread_rng());
+    }
 
-    let mut rng = rand::thread_rng();
-    let mut candidates = top_matches;
-    candidates.shuffle(&mut rng);
+    let queue = Arc::new(Mutex::new(candidates.into_iter().enumerate()));
+    let stop = Arc::new(AtomicBool::new(false));
+    let (sender, receiver) = mpsc::channel();
+    for _ in 0..DOWNLOAD_WORKERS {
+        let queue = Arc::clone(&queue);
+        let stop = Arc::clone(&stop);
+        let sender = sender.clone();
+        let client = client.clone();
+        thread::spawn(move || {
+            while !stop.load(Ordering::SeqCst) {
+                let Some((rank, image)) = queue.lock().unwrap().next() else {
+                    return;
+                };
+                let result = read_image_bytes(&client, &image.location).and_then(|bytes| {
+                    let (decoded, original_size) = usable_image(&bytes)?;
+                    if !big_enough(decoded.width().into(), decoded.height().into(), geometry) {
+                        return Err("Image is too small".to_string());
+                    }
+                    let result = SearchResult {
+                        source: source_name(&image.location),
+                        hash: perceptual_hash::dhash(&decoded),
+                        image: decoded,
+                        original_size,
+                    };
+                    Ok((bytes, result))
+                });
+                if sender.send((rank, result)).is_err() {
+                    return;
+                }
+            }
+        });
+    }
+    drop(sender);
 
-    let mut results = Vec::new();
+    let mut finished: BTreeMap<usize, Result<(Vec<u8>, SearchResult), String>> = BTreeMap::new();
+    let mut next_rank = 0;
+    let mut delivered = 0;
+    let mut seen = Vec::new();
     let mut last_error = None;
-    for image in candidates {
-        let response = match client.get(&image.url).send() {
-            Ok(response) => response,
-            Err(e) => {
-                last_error = Some(e.to_string());
-                continue;
+    let mut timed_out = false;
+    let mut all_finished = false;
+    loop {
+        while delivered < count {
+            let Some(result) = next_finished(&mut finished, &mut next_rank, in_rank_order) else {
+                break;
+            };
+            match result {
+                // Mirrors of one picture count once.
+                Ok((bytes, result)) if first_of_its_kind(&mut seen, result.hash) => {
+                    on_result(bytes, result);
+                    delivered += 1;
+                }
+                Ok(_) => {}
+                Err(e) => last_error = Some(e),
             }
-        };
+        }
+        if delivered >= count || timed_out || all_finished || cancelled.load(Ordering::SeqCst) {
+            break;
+        }
 
-        if !response.status().is_success() {
-            last_error = Some(format!("image request failed with {}", response.status()));
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            last_error.get_or_insert_with(|| "The search timed out".to_string());
+            timed_out = true;
+            // The best download that finished in time will do.
+            in_rank_order = false;
             continue;
         }
 
-        let img_bytes = match response.bytes() {
-            Ok(bytes) => bytes,
-            Err(e) => {
-                last_error = Some(e.to_string());
-                continue;
-            }
-        };
-
-        match image::load_from_memory(&img_bytes) {
-            Ok(img) => {
-                results.push(img.resize_exact(
-                    width,
-                    height,
-                    image::imageops::FilterType::CatmullRom,
-                ));
-                if results.len() >= count {
-                    return Ok(results);
-                }
-            }
-            Err(e) => {
-                last_error = Some(e.to_string());
+        match receiver.recv_timeout(remaining.min(CANCEL_POLL_INTERVAL)) {
+            Ok((rank, result)) => {
+                finished.insert(rank, result);
             }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => all_finished = true,
         }
     }
+    stop.store(true, Ordering::SeqCst);
+    let leftover = queue
+        .lock()
+        .unwrap()
+        .by_ref()
+        .map(|(_, image)| image)
+        .collect();
 
-    if results.is_empty() {
+    if delivered == 0 && !cancelled.load(Ordering::SeqCst) {
         Err(ImageFetchError {
             details: last_error.unwrap_or_else(|| "No usable image found".to_string()),
         })
     } else {
-        Ok(results)
+        Ok(Downloaded {
+            more: true,
+            timed_out,
+            leftover,
+        })
     }
 }
 
-fn extract_vqd(html: &str) -> Option<String> {
-    let patterns = ["vqd=\"", "vqd='"];
+// The next finished download to look at: in rank order, only once every
+// better-ranked one has been looked at; otherwise any.
+fn next_finished<T>(
+    finished: &mut BTreeMap<usize, T>,
+    next_rank: &mut usize,
+    in_rank_order: bool,
+) -> Option<T> {
+    if !in_rank_order {
+        return finished.pop_first().map(|(_, result)| result);
+    }
 
-    for pat in patterns {
-        if let Some(start) = html.find(pat) {
-            let start = start + pat.len();
-            let rest = &html[start..];
-            if let Some(end) = rest.find(['"', '\'']) {
-                return Some(rest[..end].to_string());
-            }
-        }
+    let result = finished.remove(next_rank)?;
+    *next_rank += 1;
+    Some(result)
+}
+
+/// Downloads and decodes a single image, e.g. from a pasted link. Also
+/// returns the size of the file, before a huge image was scaled down.
+pub fn download_image(url: &str) -> Result<(DynamicImage, [u32; 2]), ImageFetchError> {
+    let bytes = read_image_bytes(&client()?, &ImageLocation::Url(url.to_string()))
+        .map_err(|details| ImageFetchError { details })?;
+    usable_image(&bytes).map_err(|details| ImageFetchError { details })
+}
+
+fn client() -> Result<reqwest::blocking::Client, ImageFetchError> {
+    Ok(reqwest::blocking::Client::builder()
+        .cookie_store(true)
+        .user_agent("Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7)")
+        .connect_timeout(CONNECT_TIMEOUT)
+        .timeout(REQUEST_TIMEOUT)
+        .build()?)
+}
+
+// Records `hash` unless a near-identical image was already seen.
+fn first_of_its_kind(seen: &mut Vec<u64>, hash: u64) -> bool {
+    if seen
+        .iter()
+        .any(|earlier| perceptual_hash::is_near_duplicate(*earlier, hash))
+    {
+        return false;
     }
+    seen.push(hash);
+    true
+}
 
-    None
+fn source_name(location: &ImageLocation) -> String {
+    match location {
+        ImageLocation::Url(url) => url.clone(),
+        ImageLocation::File(path) => path.display().to_string(),
+    }
 }
 
-fn get_ddg_image_urls(json_response: String) -> Vec<Image> {
-    let json_res: serde_json::Value = match serde_json::from_str(&json_response) {
-        Ok(val) => val,
-        Err(_) => return vec![],
+// Kept uncropped so the user can frame it; only huge images are scaled down.
+// Returns the image with its size as decoded.
+fn usable_image(bytes: &[u8]) -> Result<(DynamicImage, [u32; 2]), String> {
+    let img = image::load_from_memory(bytes).map_err(|e| e.to_string())?;
+    let original_size = [img.width(), img.height()];
+    let img = if img.width().max(img.height()) > MAX_RESULT_SIDE {
+        img.resize(
+            MAX_RESULT_SIDE,
+            MAX_RESULT_SIDE,
+            image::imageops::FilterType::CatmullRom,
+        )
+    } else {
+        img
     };
+    Ok((img, original_size))
+}
 
-    json_res
-        .get("results")
-        .and_then(|v| v.as_array())
-        .unwrap_or(&vec![])
-        .iter()
-        .map(|item| Image {
-            url: item
-                .get("image")
-                .and_then(|v| v.as_str())
-                .unwrap_or_default()
-                .to_string(),
-            width: item
-                .get("width")
-                .and_then(|v| v.as_i64())
-                .unwrap_or_default(),
-            height: item
-                .get("height")
-                .and_then(|v| v.as_i64())
-                .unwrap_or_default(),
+fn read_image_bytes(
+    client: &reqwest::blocking::Client,
+    location: &ImageLocation,
+) -> Result<Vec<u8>, String> {
+    match location {
+        ImageLocation::File(path) => fs::read(path).map_err(|e| e.to_string()),
+        ImageLocation::Url(url) => {
+            let response = client.get(url).send().map_err(|e| e.to_string())?;
+            if !response.status().is_success() {
+                return Err(format!("image request failed with {}", response.status()));
+            }
+            Ok(response.bytes().map_err(|e| e.to_string())?.to_vec())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{image_provider::JsonApiConfig, test_server::TestServer};
+    use std::{env, io::Cursor};
+
+    // A block pattern particular to `name`, so each test image is a different
+    // picture.
+    fn png_bytes(name: &str, width: u32, height: u32) -> Vec<u8> {
+        let seed = name.bytes().fold(7u32, |seed, byte| {
+            seed.wrapping_mul(31).wrapping_add(u32::from(byte))
+        });
+        let image = image::RgbImage::from_fn(width, height, |x, y| {
+            let block = (x * 9 / width) + 9 * (y * 8 / height) + seed;
+            let shade = (block.wrapping_mul(2_654_435_761) >> 24) as u8;
+            image::Rgb([shade; 3])
+        });
+        let mut bytes = Vec::new();
+        DynamicImage::ImageRgb8(image)
+            .write_to(&mut Cursor::new(&mut bytes), image::ImageFormat::Png)
+            .unwrap();
+        bytes
+    }
+
+    // Two pages. The first has a candidate on a host that takes five seconds;
+    // "Ranked" has a missing and a too-small image before one that is slow;
+    // the second repeats one image, mirrors another on a second path and has
+    // one too small to use. "Many" has more candidates than are downloaded at
+    // once.
+    fn start_server() -> TestServer {
+        TestServer::start(|path| {
+            let results = if path.starts_with("/search?q=Ranked&page=1") {
+                serde_json::json!([
+                    {"url": "/missing.png"},
+                    {"url": "/tiny.png"},
+                    {"url": "/late.png"},
+                    {"url": "/d.png"}
+                ])
+            } else if path.starts_with("/search?q=Many&page=1") {
+                (0..16)
+                    .map(|number| serde_json::json!({"url": format!("/many-{number}.png")}))
+                    .collect()
+            } else if path.starts_with("/search?q=Alien&page=1") {
+                serde_json::json!([{"url": "/slow.png"}, {"url": "/a.png"}, {"url": "/b.png"}])
+            } else if path.starts_with("/search?q=Alien&page=2") {
+                serde_json::json!([
+                    {"url": "/b.png"},
+                    {"url": "/tiny.png"},
+                    {"url": "/c.png"},
+                    {"url": "/mirror/c.png"}
+                ])
+            } else if path.starts_with("/search") {
+                serde_json::json!([])
+            } else if path == "/slow.png" {
+                thread::sleep(Duration::from_secs(5));
+                return Some(("image/png", png_bytes(path, 200, 250)));
+            } else if path == "/late.png" {
+                thread::sleep(Duration::from_secs(1));
+                return Some(("image/png", png_bytes(path, 200, 250)));
+            } else if path == "/missing.png" {
+                return None;
+            } else if path == "/tiny.png" {
+                return Some(("image/png", png_bytes(path, 20, 25)));
+            } else if let Some(name) = path.strip_suffix(".png") {
+                let name = name.rsplit('/').next().unwrap_or(name);
+                return Some(("image/png", png_bytes(name, 200, 250)));
+            } else {
+                return None;
+            };
+            let body = serde_json::json!({ "results": results });
+            Some(("application/json", body.to_string().into_bytes()))
+        })
+    }
+
+    fn config(server: &TestServer) -> ImageProviderConfig {
+        ImageProviderConfig::JsonApi(JsonApiConfig {
+            url_template: format!("{}/search?q={{query}}&page={{page}}", server.url()),
+            results_path: "results".to_string(),
+            url_path: "url".to_string(),
+            ..JsonApiConfig::default()
         })
-        .collect()
+    }
+
+    fn request(count: usize, page: usize, skip: &HashSet<String>) -> SearchRequest<'_> {
+        SearchRequest {
+            query: "Alien",
+            geometry: ImageGeometry::POSTER,
+            count,
+            refresh: false,
+            page,
+            skip,
+            candidates: &[],
+        }
+    }
+
+    #[test]
+    fn slow_hosts_do_not_hold_up_results_from_quick_ones() {
+        let server = start_server();
+        let no_skip = HashSet::new();
+
+        let started = Instant::now();
+        let mut arrived = Vec::new();
+        search_incremental(
+            &config(&server),
+            None,
+            &request(2, 0, &no_skip),
+            &AtomicBool::new(false),
+            &mut |result| arrived.push((result.source, started.elapsed())),
+        )
+        .unwrap();
+
+        assert_eq!(arrived.len(), 2);
+        assert!(arrived[1].1 < Duration::from_secs(3));
+
+        // Cancelling returns quickly and without an error.
+        let started = Instant::now();
+        search_incremental(
+            &config(&server),
+            None,
+            &request(3, 0, &no_skip),
+            &AtomicBool::new(true),
+            &mut |_| {},
+        )
+        .unwrap();
+        assert!(started.elapsed() < Duration::from_secs(3));
+    }
+
+    #[test]
+    fn a_single_image_is_the_best_ranked_usable_one() {
+        let server = start_server();
+        let no_skip = HashSet::new();
+
+        let results = search_many(
+            config(&server).build().as_ref(),
+            &SearchRequest {
+                query: "Ranked",
+                ..request(1, 0, &no_skip)
+            },
+        )
+        .unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].source.ends_with("/late.png"));
+    }
+
+    #[test]
+    fn later_pages_skip_shown_small_and_mirrored_images() {
+        let server = start_server();
+        let skip: HashSet<String> = ["/a.png", "/b.png"]
+            .map(|path| format!("{}{path}", server.url()))
+            .into();
+
+        let mut sources = Vec::new();
+        let outcome = search_incremental(
+            &config(&server),
+            None,
+            &request(18, 1, &skip),
+            &AtomicBool::new(false),
+            &mut |result| sources.push(result.source),
+        )
+        .unwrap();
+        assert_eq!(outcome.more, Some(true));
+        // Either copy of c.png may arrive first, but only one is kept.
+        assert_eq!(sources.len(), 1);
+        assert!(sources[0].ends_with("c.png"));
+
+        let outcome = search_incremental(
+            &config(&server),
+            None,
+            &request(18, 2, &skip),
+            &AtomicBool::new(false),
+            &mut |_| {},
+        )
+        .unwrap();
+        assert_eq!(outcome.more, Some(false));
+    }
+
+    #[test]
+    fn candidates_left_over_come_before_the_next_page() {
+        let server = start_server();
+        let config = config(&server);
+        let no_skip = HashSet::new();
+        let searches = || {
+            server
+                .requests()
+                .iter()
+                .filter(|path| path.starts_with("/search"))
+                .count()
+        };
+
+        let mut shown = HashSet::new();
+        let first = search_incremental(
+            &config,
+            None,
+            &SearchRequest {
+                query: "Many",
+                ..request(1, 0, &no_skip)
+            },
+            &AtomicBool::new(false),
+            &mut |result| {
+                shown.insert(result.source);
+            },
+        )
+        .unwrap();
+        assert_eq!(first.more, Some(true));
+        assert!(!first.leftover.is_empty());
+        assert_eq!(searches(), 1);
+
+        let mut sources = Vec::new();
+        let second = search_incremental(
+            &config,
+            None,
+            &SearchRequest {
+                query: "Many",
+                candidates: &first.leftover,
+                ..request(18, 1, &shown)
+            },
+            &AtomicBool::new(false),
+            &mut |result| sources.push(result.source),
+        )
+        .unwrap();
+        assert_eq!(second.more, None);
+        assert!(!sources.is_empty());
+        assert!(sources.iter().all(|source| !shown.contains(source)));
+        assert_eq!(searches(), 1);
+    }
+
+    #[test]
+    fn cached_results_are_used_when_the_source_is_gone() {
+        let root = env::temp_dir().join(format!(
+            "media-rating-cached-search-test-{}",
+            std::process::id()
+        ));
+        let cache = SearchCache::new(&root, Duration::from_secs(60), 1 << 20);
+        let server = start_server();
+        let config = config(&server);
+        let no_skip = HashSet::new();
+        let search = |refresh| {
+            let mut count = 0;
+            let result = search_incremental(
+                &config,
+                Some(&cache),
+                &SearchRequest {
+                    refresh,
+                    ..request(2, 0, &no_skip)
+                },
+                &AtomicBool::new(false),
+                &mut |_| count += 1,
+            );
+            result.map(|_| count)
+        };
+        assert_eq!(search(false).unwrap(), 2);
+
+        drop(server);
+        assert_eq!(search(false).unwrap(), 2);
+        // A failed refresh still falls back to what was saved.
+        assert_eq!(search(true).unwrap(), 2);
+
+        fs::remove_dir_all(root).ok();
+    }
 }
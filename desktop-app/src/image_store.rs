@@ -1,15 +1,71 @@
 use eframe::egui;
 use egui::ColorImage;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fmt::Display,
     fs, io,
     path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
 };
 
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    image_crop::{self, CropMode},
+    image_provenance::{self, ImageProvenance, ProvenanceManifest},
+    lru_cache::{CacheStats, LruCache},
+    model::Model,
+    perceptual_hash, stable_hash,
+    texture_loader::{LoadedImage, Priority, TextureLoader},
+    title_card,
+};
+
+// Where images kept for undo wait, inside the image directory.
+const TRASH_DIRECTORY: &str = ".trash";
+static NEXT_TRASH_ID: AtomicU64 = AtomicU64::new(0);
+
 pub const ENTRY_IMAGE_WIDTH: u32 = 380;
 pub const ENTRY_IMAGE_HEIGHT: u32 = 475;
 
+// The size entry images of a category are stored and shown at.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ImageGeometry {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Default for ImageGeometry {
+    fn default() -> Self {
+        ImageGeometry::POSTER
+    }
+}
+
+impl ImageGeometry {
+    pub const POSTER: ImageGeometry = ImageGeometry {
+        width: ENTRY_IMAGE_WIDTH,
+        height: ENTRY_IMAGE_HEIGHT,
+    };
+    pub const SQUARE: ImageGeometry = ImageGeometry {
+        width: 420,
+        height: 420,
+    };
+    pub const BANNER: ImageGeometry = ImageGeometry {
+        width: 640,
+        height: 360,
+    };
+    pub const PRESETS: [(&'static str, ImageGeometry); 3] = [
+        ("Poster 4:5", ImageGeometry::POSTER),
+        ("Square 1:1", ImageGeometry::SQUARE),
+        ("Banner 16:9", ImageGeometry::BANNER),
+    ];
+    pub const MIN_SIDE: u32 = 64;
+    pub const MAX_SIDE: u32 = 1200;
+
+    pub fn size(self) -> [u32; 2] {
+        [self.width, self.height]
+    }
+}
+
 #[derive(Debug)]
 pub struct ImageFetchError {
     pub details: String,
@@ -29,11 +85,18 @@ impl From<image::ImageError> for ImageFetchError {
     }
 }
 
+// Failing to connect usually means there is no network, which the raw error
+// chain does not make clear.
 impl From<reqwest::Error> for ImageFetchError {
     fn from(err: reqwest::Error) -> ImageFetchError {
-        ImageFetchError {
-            details: err.to_string(),
-        }
+        let host = err.url().and_then(|url| url.host_str());
+        let details = match host {
+            Some(host) if err.is_connect() || err.is_timeout() => {
+                format!("Could not reach {host}. Check your internet connection.")
+            }
+            _ => err.to_string(),
+        };
+        ImageFetchError { details }
     }
 }
 
@@ -45,64 +108,432 @@ impl From<io::Error> for ImageFetchError {
     }
 }
 
+pub const DEFAULT_TEXTURE_BUDGET_MB: usize = 256;
+const THUMBNAIL_SCALE: u32 = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TextureSize {
+    Full,
+    // A quarter-size copy for lists, so browsing a long category does not
+    // keep full-size textures alive.
+    Thumbnail,
+}
+
+impl TextureSize {
+    const ALL: [TextureSize; 2] = [TextureSize::Full, TextureSize::Thumbnail];
+
+    fn dimensions(self, geometry: ImageGeometry) -> [usize; 2] {
+        let [width, height] = geometry.size();
+        match self {
+            TextureSize::Full => [width as usize, height as usize],
+            TextureSize::Thumbnail => [
+                (width / THUMBNAIL_SCALE) as usize,
+                (height / THUMBNAIL_SCALE) as usize,
+            ],
+        }
+    }
+
+    // Keys include the geometry so textures of the old size are never served
+    // after a category's geometry changes.
+    fn key(self, entry: &str, category: &str, geometry: ImageGeometry) -> String {
+        let key = format!(
+            "{} {}x{}",
+            texture_key(entry, category),
+            geometry.width,
+            geometry.height
+        );
+        match self {
+            TextureSize::Full => key,
+            TextureSize::Thumbnail => format!("{key} thumbnail"),
+        }
+    }
+}
+
+// An entry's image file as it was, so a change can be taken back. The file
+// waits in the trash until it is put back or the snapshot is dropped.
+pub struct SavedImage {
+    pub category: String,
+    pub title: String,
+    path: PathBuf,
+    trash_path: PathBuf,
+    provenance: Option<ImageProvenance>,
+}
+
+impl Drop for SavedImage {
+    fn drop(&mut self) {
+        fs::remove_file(&self.trash_path).ok();
+    }
+}
+
 pub struct ImageStore {
     image_directory: PathBuf,
-    texture_cache: HashMap<String, egui::TextureHandle>,
+    texture_cache: LruCache<egui::TextureHandle>,
+    loader: Option<TextureLoader>,
+    // Keys queued on the loader whose results are still wanted, with the
+    // category and entry each is for.
+    pending: HashMap<String, (String, String)>,
+    placeholders: HashMap<(TextureSize, ImageGeometry), egui::TextureHandle>,
+    geometries: HashMap<String, ImageGeometry>,
+    provenance: ProvenanceManifest,
+    // Set by every change to the manifest, so a loop over many entries writes
+    // it once, in `save_provenance`.
+    provenance_changed: bool,
+}
+
+impl Drop for ImageStore {
+    fn drop(&mut self) {
+        self.save_provenance();
+    }
 }
 
 impl ImageStore {
-    pub fn new(document_directory: impl Into<PathBuf>) -> Self {
+    pub fn new(document_directory: impl Into<PathBuf>, texture_budget_bytes: usize) -> Self {
+        let image_directory = document_directory.into().join("images");
+        // Snapshots do not outlive the app, so anything in the trash is left
+        // from a run that did not close cleanly.
+        fs::remove_dir_all(image_directory.join(TRASH_DIRECTORY)).ok();
         Self {
-            image_directory: document_directory.into().join("images"),
-            texture_cache: HashMap::new(),
+            provenance: ProvenanceManifest::load(&image_directory),
+            provenance_changed: false,
+            image_directory,
+            texture_cache: LruCache::new(texture_budget_bytes),
+            loader: None,
+            pending: HashMap::new(),
+            placeholders: HashMap::new(),
+            geometries: HashMap::new(),
         }
     }
 
+    pub fn image_directory(&self) -> &Path {
+        &self.image_directory
+    }
+
+    pub fn provenance(&self, category: &str, entry: &str) -> Option<&ImageProvenance> {
+        self.provenance.get(category, entry)
+    }
+
+    // Entries showing a title card until a real image is fetched.
+    pub fn placeholder_entries(&self) -> Vec<(String, String)> {
+        self.provenance
+            .entries_from(image_provenance::PLACEHOLDER_PROVIDER)
+    }
+
+    // The manifest file, if any image has provenance recorded.
+    pub fn provenance_file(&self) -> Option<PathBuf> {
+        Some(image_provenance::manifest_path(&self.image_directory)).filter(|path| path.exists())
+    }
+
+    pub fn geometry(&self, category: &str) -> ImageGeometry {
+        self.geometries.get(category).copied().unwrap_or_default()
+    }
+
+    // Takes the geometries the document keeps, e.g. after it changed.
+    pub fn set_geometries(&mut self, model: &Model) {
+        self.geometries = model
+            .image_geometries()
+            .map(|(category, geometry)| (category.to_string(), geometry))
+            .collect();
+    }
+
+    // Returns the entry's texture, or a placeholder while it loads in the
+    // background.
     pub fn get_entry_texture(
         &mut self,
         entry: &str,
         category: &str,
         ctx: &egui::Context,
     ) -> egui::TextureHandle {
-        let key = texture_key(entry, category);
-        if let Some(texture) = self.texture_cache.get(&key) {
-            return texture.clone();
+        self.texture(entry, category, TextureSize::Full, ctx)
+    }
+
+    pub fn get_entry_thumbnail(
+        &mut self,
+        entry: &str,
+        category: &str,
+        ctx: &egui::Context,
+    ) -> egui::TextureHandle {
+        self.texture(entry, category, TextureSize::Thumbnail, ctx)
+    }
+
+    pub fn cache_stats(&self) -> CacheStats {
+        self.texture_cache.stats()
+    }
+
+    // Start loading textures that are likely to be shown soon.
+    pub fn prefetch<'a>(
+        &mut self,
+        category: &str,
+        entries: impl IntoIterator<Item = &'a str>,
+        ctx: &egui::Context,
+    ) {
+        let geometry = self.geometry(category);
+        for entry in entries {
+            if !self
+                .texture_cache
+                .contains(&TextureSize::Full.key(entry, category, geometry))
+            {
+                self.request_load(entry, category, TextureSize::Full, Priority::Prefetch, ctx);
+            }
+        }
+    }
+
+    fn texture(
+        &mut self,
+        entry: &str,
+        category: &str,
+        size: TextureSize,
+        ctx: &egui::Context,
+    ) -> egui::TextureHandle {
+        self.receive_loaded(ctx);
+
+        let geometry = self.geometry(category);
+        let key = size.key(entry, category, geometry);
+        if let Some(texture) = self.texture_cache.get(&key, ctx.frame_nr()) {
+            return texture;
         }
 
-        let mut texture = ctx.load_texture(
-            key.clone(),
-            egui::ColorImage::new(
-                [ENTRY_IMAGE_WIDTH as usize, ENTRY_IMAGE_HEIGHT as usize],
-                egui::Color32::BLACK,
-            ),
-            egui::TextureOptions::LINEAR,
+        self.request_load(entry, category, size, Priority::Visible, ctx);
+        self.placeholder(size, geometry, ctx)
+    }
+
+    fn request_load(
+        &mut self,
+        entry: &str,
+        category: &str,
+        size: TextureSize,
+        priority: Priority,
+        ctx: &egui::Context,
+    ) {
+        let geometry = self.geometry(category);
+        let key = size.key(entry, category, geometry);
+        let loader = self.loader.get_or_insert_with(|| TextureLoader::new(ctx));
+        if self.pending.contains_key(&key) {
+            if priority == Priority::Visible {
+                loader.prioritize(&key);
+            }
+            return;
+        }
+
+        let entry = entry.to_string();
+        let category = category.to_string();
+        let image_directory = self.image_directory.clone();
+        self.pending
+            .insert(key.clone(), (category.clone(), entry.clone()));
+        loader.request(
+            key,
+            priority,
+            Box::new(move || texture_image(&category, &entry, &image_directory, geometry, size)),
         );
+    }
+
+    fn receive_loaded(&mut self, ctx: &egui::Context) {
+        let Some(loader) = &self.loader else {
+            return;
+        };
 
-        if let Ok(image) = load_cached_image(category, entry, &self.image_directory) {
-            texture = ctx.load_texture(key.clone(), image, egui::TextureOptions::LINEAR);
+        let finished: Vec<(String, LoadedImage)> = loader.finished().collect();
+        for (key, loaded) in finished {
+            // Renamed, deleted or replaced while loading.
+            let Some((category, entry)) = self.pending.remove(&key) else {
+                continue;
+            };
+            self.cache_texture(key, loaded.image, ctx);
+            if let Some(hash) = loaded.hash {
+                self.record_hash(&category, &entry, hash);
+            }
         }
+    }
+
+    fn cache_texture(&mut self, key: String, image: ColorImage, ctx: &egui::Context) {
+        let bytes = image.pixels.len() * std::mem::size_of::<egui::Color32>();
+        let texture = ctx.load_texture(key.clone(), image, egui::TextureOptions::LINEAR);
+        self.texture_cache
+            .insert(key, texture, bytes, ctx.frame_nr());
+    }
+
+    fn placeholder(
+        &mut self,
+        size: TextureSize,
+        geometry: ImageGeometry,
+        ctx: &egui::Context,
+    ) -> egui::TextureHandle {
+        self.placeholders
+            .entry((size, geometry))
+            .or_insert_with(|| {
+                ctx.load_texture(
+                    format!("image-store-placeholder-{size:?}-{geometry:?}"),
+                    ColorImage::new(size.dimensions(geometry), egui::Color32::from_gray(40)),
+                    egui::TextureOptions::LINEAR,
+                )
+            })
+            .clone()
+    }
 
-        self.texture_cache.insert(key, texture.clone());
-        texture
+    // The image file currently on disk for an entry, under either naming scheme.
+    pub fn existing_image_file(&self, category: &str, title: &str) -> Option<PathBuf> {
+        image_path_candidates(&self.image_directory, category, title)
+            .into_iter()
+            .find(|path| path.exists())
     }
 
-    pub fn rename_image(&mut self, category: &str, old_title: &str, new_title: &str) {
-        match rename_image_file(category, old_title, new_title, &self.image_directory) {
-            Ok(()) => {}
-            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
-            Err(e) => eprintln!("Error renaming image: {e}"),
+    // The textures follow the new name even if the file could not be moved,
+    // so the entry keeps showing its image until the next check.
+    pub fn rename_image(
+        &mut self,
+        category: &str,
+        old_title: &str,
+        new_title: &str,
+    ) -> io::Result<()> {
+        let renamed = rename_image_file(category, old_title, new_title, &self.image_directory);
+        // Without a file the textures are title cards showing the old title.
+        let missing = matches!(&renamed, Err(e) if e.kind() == io::ErrorKind::NotFound);
+        let mut result = if missing { Ok(()) } else { renamed };
+
+        let geometry = self.geometry(category);
+        for size in TextureSize::ALL {
+            let old_key = size.key(old_title, category, geometry);
+            self.pending.remove(&old_key);
+            if let Some((texture, bytes)) = self.texture_cache.remove(&old_key) {
+                if !missing {
+                    self.texture_cache.insert(
+                        size.key(new_title, category, geometry),
+                        texture,
+                        bytes,
+                        0,
+                    );
+                }
+            }
         }
 
-        let old_key = texture_key(old_title, category);
-        if let Some(texture) = self.texture_cache.remove(&old_key) {
-            self.texture_cache
-                .insert(texture_key(new_title, category), texture);
+        let placeholder = self.is_placeholder(category, old_title);
+        if self.provenance.rename(category, old_title, new_title) {
+            self.provenance_changed = true;
         }
+        // A saved title card is drawn again with the new title.
+        if placeholder && !missing && result.is_ok() {
+            result = title_card::render(new_title, category, geometry)
+                .save(image_path(&self.image_directory, category, new_title))
+                .map_err(io::Error::other);
+            self.forget_textures(category, new_title);
+        }
+
+        result
+    }
+
+    fn is_placeholder(&self, category: &str, title: &str) -> bool {
+        self.provenance
+            .get(category, title)
+            .is_some_and(|provenance| provenance.provider == image_provenance::PLACEHOLDER_PROVIDER)
     }
 
     pub fn delete_image(&mut self, category: &str, title: &str) {
         delete_image_file(category, title, &self.image_directory);
-        self.texture_cache.remove(&texture_key(title, category));
+        self.forget_textures(category, title);
+        self.forget_provenance(category, title);
+    }
+
+    // Moves an entry's image along with the entry to another category.
+    pub fn move_image(
+        &mut self,
+        from_category: &str,
+        to_category: &str,
+        title: &str,
+    ) -> io::Result<()> {
+        let Some(old_path) = self.existing_image_file(from_category, title) else {
+            return Ok(());
+        };
+        let placeholder = self.is_placeholder(from_category, title);
+        let new_path = image_path(&self.image_directory, to_category, title);
+        fs::rename(&old_path, &new_path)?;
+        self.forget_textures(from_category, title);
+        self.forget_textures(to_category, title);
+
+        if let Some(provenance) = self.provenance.remove(from_category, title) {
+            self.provenance.restore(to_category, title, provenance);
+            self.provenance_changed = true;
+        }
+        if let Some(hash) = self.provenance.remove_hash(from_category, title) {
+            // A title card drawn again has a hash of its own.
+            if !placeholder {
+                self.provenance.set_hash(to_category, title, hash);
+            }
+            self.provenance_changed = true;
+        }
+        // Title cards name their category, so this one is drawn again.
+        if placeholder {
+            title_card::render(title, to_category, self.geometry(to_category))
+                .save(&new_path)
+                .map_err(io::Error::other)?;
+        }
+        Ok(())
+    }
+
+    // Copies the entry's image to the trash, leaving it in place.
+    pub fn snapshot_image(&self, category: &str, title: &str) -> Option<SavedImage> {
+        let path = self.existing_image_file(category, title)?;
+        let trash_path = self.trash_path(&path).ok()?;
+        fs::copy(&path, &trash_path).ok()?;
+        Some(SavedImage {
+            category: category.to_string(),
+            title: title.to_string(),
+            path,
+            trash_path,
+            provenance: self.provenance.get(category, title).cloned(),
+        })
+    }
+
+    // Moves the entry's image to the trash, as `delete_image` would remove it.
+    pub fn take_image(&mut self, category: &str, title: &str) -> Option<SavedImage> {
+        let saved = self.existing_image_file(category, title).and_then(|path| {
+            let trash_path = self.trash_path(&path).ok()?;
+            fs::rename(&path, &trash_path).ok()?;
+            Some(SavedImage {
+                category: category.to_string(),
+                title: title.to_string(),
+                path,
+                trash_path,
+                provenance: self.provenance.get(category, title).cloned(),
+            })
+        });
+        self.delete_image(category, title);
+        saved
+    }
+
+    // Puts back a snapshot, replacing whatever image the entry has now.
+    pub fn restore_image(&mut self, saved: &SavedImage) -> io::Result<()> {
+        delete_image_file(&saved.category, &saved.title, &self.image_directory);
+        fs::rename(&saved.trash_path, &saved.path)?;
+        self.forget_textures(&saved.category, &saved.title);
+
+        match &saved.provenance {
+            Some(provenance) => {
+                self.provenance
+                    .restore(&saved.category, &saved.title, provenance.clone());
+            }
+            None => {
+                self.provenance.remove(&saved.category, &saved.title);
+            }
+        }
+        self.provenance.remove_hash(&saved.category, &saved.title);
+        self.provenance_changed = true;
+        Ok(())
+    }
+
+    // A free name in the trash for a copy of `path`.
+    fn trash_path(&self, path: &Path) -> io::Result<PathBuf> {
+        let directory = self.image_directory.join(TRASH_DIRECTORY);
+        fs::create_dir_all(&directory)?;
+        let id = NEXT_TRASH_ID.fetch_add(1, Ordering::Relaxed);
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        Ok(directory.join(format!("{id} {file_name}")))
+    }
+
+    // For when the entry's file was replaced by other means, e.g. reattached.
+    pub fn forget_provenance(&mut self, category: &str, title: &str) {
+        let removed = self.provenance.remove(category, title).is_some();
+        if self.provenance.remove_hash(category, title).is_some() || removed {
+            self.provenance_changed = true;
+        }
     }
 
     pub fn replace_for_category_switch(
@@ -122,45 +553,145 @@ impl ImageStore {
         category: &str,
         entry: &str,
         image: image::DynamicImage,
+        provenance: ImageProvenance,
         ctx: &egui::Context,
     ) -> Result<(), ImageFetchError> {
-        let image = resize_entry_image(image);
+        let geometry = self.geometry(category);
+        let image = resize_entry_image(image, geometry);
         let full_path = image_path(&self.image_directory, category, entry);
         image.save(&full_path)?;
+        self.provenance.set(category, entry, provenance);
+        self.provenance
+            .set_hash(category, entry, perceptual_hash::dhash(&image));
+        self.provenance_changed = true;
 
-        let key = texture_key(entry, category);
-        let texture = ctx.load_texture(
-            key.clone(),
+        self.forget_textures(category, entry);
+        self.cache_texture(
+            TextureSize::Full.key(entry, category, geometry),
             dynamic_image_to_color_image(&image),
-            egui::TextureOptions::LINEAR,
+            ctx,
         );
-        self.texture_cache.insert(key, texture);
 
         Ok(())
     }
+
+    // Groups of entries, in any categories, whose images look the same, by
+    // the stored hashes; `unhashed_images` lists the ones still to hash.
+    pub fn near_duplicates(&self, model: &Model) -> Vec<Vec<(String, String)>> {
+        let hashes: Vec<_> = self
+            .comparable_images(model)
+            .into_iter()
+            .filter_map(|(category, entry, _)| {
+                let hash = self.provenance.hash(&category, &entry)?;
+                Some(((category, entry), hash))
+            })
+            .collect();
+        perceptual_hash::near_duplicate_groups(&hashes)
+    }
+
+    // Images neither shown nor saved since hashes were kept, with their files.
+    pub fn unhashed_images(&self, model: &Model) -> Vec<(String, String, PathBuf)> {
+        self.comparable_images(model)
+            .into_iter()
+            .filter(|(category, entry, _)| self.provenance.hash(category, entry).is_none())
+            .collect()
+    }
+
+    // Entries with an image of their own, in category order. Entries sharing
+    // one file are left to the collision check, and title cards are alike by
+    // design.
+    fn comparable_images(&self, model: &Model) -> Vec<(String, String, PathBuf)> {
+        let mut categories: Vec<_> = model.categories().collect();
+        categories.sort_by_key(|(category, _)| *category);
+
+        let mut files = HashSet::new();
+        let mut images = Vec::new();
+        for (category, entries) in categories {
+            for entry in entries {
+                let Some(path) = self.existing_image_file(category, entry) else {
+                    continue;
+                };
+                if !files.insert(path.clone()) || self.is_placeholder(category, entry) {
+                    continue;
+                }
+                images.push((category.to_string(), entry.clone(), path));
+            }
+        }
+        images
+    }
+
+    pub fn record_hash(&mut self, category: &str, title: &str, hash: u64) {
+        if self.provenance.set_hash(category, title, hash) {
+            self.provenance_changed = true;
+        }
+    }
+
+    // Drop every cached texture, e.g. after image files were moved on disk.
+    pub fn clear_textures(&mut self) {
+        self.pending.clear();
+        self.texture_cache.clear();
+    }
+
+    // Writes the manifest if it changed since it was last written. The image
+    // itself is what matters, so a manifest that cannot be written is
+    // reported but does not fail the change.
+    pub fn save_provenance(&mut self) {
+        if !std::mem::take(&mut self.provenance_changed) {
+            return;
+        }
+        if let Err(e) = self.provenance.save(&self.image_directory) {
+            eprintln!("Could not save image provenance: {e}");
+        }
+    }
+
+    fn forget_textures(&mut self, category: &str, title: &str) {
+        let geometry = self.geometry(category);
+        for size in TextureSize::ALL {
+            let key = size.key(title, category, geometry);
+            self.pending.remove(&key);
+            self.texture_cache.remove(&key);
+        }
+    }
 }
 
-pub fn resize_entry_image(image: image::DynamicImage) -> image::DynamicImage {
-    image.resize_exact(
-        ENTRY_IMAGE_WIDTH,
-        ENTRY_IMAGE_HEIGHT,
-        image::imageops::FilterType::CatmullRom,
-    )
+// Bring an image to the category's size, cropping rather than stretching when
+// the aspect ratio differs.
+pub fn resize_entry_image(
+    image: image::DynamicImage,
+    geometry: ImageGeometry,
+) -> image::DynamicImage {
+    if image.width() == geometry.width && image.height() == geometry.height {
+        return image;
+    }
+
+    image_crop::apply_crop(&image, CropMode::default(), geometry.size())
 }
 
 pub fn dynamic_image_to_color_image(image: &image::DynamicImage) -> ColorImage {
-    let image = if image.width() != ENTRY_IMAGE_WIDTH || image.height() != ENTRY_IMAGE_HEIGHT {
-        resize_entry_image(image.clone())
-    } else {
-        image.clone()
-    };
-
     ColorImage::from_rgba_unmultiplied(
-        [ENTRY_IMAGE_WIDTH as usize, ENTRY_IMAGE_HEIGHT as usize],
+        [image.width() as usize, image.height() as usize],
         &image.to_rgba8(),
     )
 }
 
+fn shrink_color_image(image: &ColorImage, [width, height]: [usize; 2]) -> ColorImage {
+    let Some(buffer) = image::RgbaImage::from_raw(
+        image.width() as u32,
+        image.height() as u32,
+        image.as_raw().to_vec(),
+    ) else {
+        return ColorImage::new([width, height], egui::Color32::BLACK);
+    };
+
+    let resized = image::imageops::resize(
+        &buffer,
+        width as u32,
+        height as u32,
+        image::imageops::FilterType::Triangle,
+    );
+    ColorImage::from_rgba_premultiplied([width, height], &resized)
+}
+
 fn texture_key(entry: &str, category: &str) -> String {
     format!("{entry} {category}")
 }
@@ -197,17 +728,41 @@ fn safe_file_component(value: &str) -> String {
     }
 }
 
+// The cleaned-up title and category, and whether cleaning lost anything that
+// could tell this entry apart from another one: part of the title, a replaced
+// character, or the category's usual colon.
+fn file_name_parts(category: &str, title: &str) -> (String, String, bool) {
+    let clean_category = safe_file_component(&clean_category(category));
+    let clean_title = safe_file_component(&clean_title(title));
+    let lossless = clean_title == title && format!("{clean_category}:") == category;
+    (clean_title, clean_category, lossless)
+}
+
+// "Dune (1984)" and "Dune (2021)" both clean up to "Dune", so names that lost
+// something get a hash of the full name to keep them apart.
 fn image_file_name(category: &str, title: &str) -> String {
-    let category = safe_file_component(&clean_category(category));
-    let title = safe_file_component(&clean_title(title));
-    format!("{title} {category}.png")
+    let (title_part, category_part, lossless) = file_name_parts(category, title);
+    if lossless {
+        format!("{title_part} {category_part}.png")
+    } else {
+        format!(
+            "{title_part} {category_part} {:08x}.png",
+            stable_hash::fnv1a_32(&[category, title])
+        )
+    }
 }
 
-fn image_path(image_directory: &Path, category: &str, title: &str) -> PathBuf {
+pub fn image_path(image_directory: &Path, category: &str, title: &str) -> PathBuf {
     image_directory.join(image_file_name(category, title))
 }
 
-fn legacy_image_path(image_directory: &Path, category: &str, title: &str) -> PathBuf {
+// The name used before colliding names were told apart.
+fn unhashed_image_path(image_directory: &Path, category: &str, title: &str) -> PathBuf {
+    let (title_part, category_part, _) = file_name_parts(category, title);
+    image_directory.join(format!("{title_part} {category_part}.png"))
+}
+
+pub fn legacy_image_path(image_directory: &Path, category: &str, title: &str) -> PathBuf {
     let mut title = title.to_string();
     if let Some(index) = title.find('(') {
         title.truncate(index);
@@ -220,37 +775,40 @@ fn legacy_image_path(image_directory: &Path, category: &str, title: &str) -> Pat
     image_directory.join(format!("{title} {}.png", category.trim()))
 }
 
-fn image_path_candidates(image_directory: &Path, category: &str, title: &str) -> Vec<PathBuf> {
-    let primary = image_path(image_directory, category, title);
-    let legacy = legacy_image_path(image_directory, category, title);
-    if primary == legacy {
-        vec![primary]
-    } else {
-        vec![primary, legacy]
+// Where an entry's image may be, current name first. The older names are
+// still read so documents from earlier versions keep their images.
+pub fn image_path_candidates(image_directory: &Path, category: &str, title: &str) -> Vec<PathBuf> {
+    let mut candidates = vec![image_path(image_directory, category, title)];
+    for path in [
+        unhashed_image_path(image_directory, category, title),
+        legacy_image_path(image_directory, category, title),
+    ] {
+        if !candidates.contains(&path) {
+            candidates.push(path);
+        }
     }
+    candidates
 }
 
+// Images of another size are fitted in memory only, so the file survives a
+// category's geometry being changed and changed back. Also returns the
+// perceptual hash of the file.
 fn load_cached_image(
     category: &str,
     title: &str,
     image_directory: &Path,
-) -> Result<ColorImage, ImageFetchError> {
+    geometry: ImageGeometry,
+) -> Result<(ColorImage, u64), ImageFetchError> {
     let full_path = image_path(image_directory, category, title);
 
     for candidate in image_path_candidates(image_directory, category, title) {
         if let Ok(image) = image::open(&candidate) {
-            let image =
-                if image.width() != ENTRY_IMAGE_WIDTH || image.height() != ENTRY_IMAGE_HEIGHT {
-                    let resized_image = resize_entry_image(image);
-                    resized_image.save(&full_path)?;
-                    resized_image
-                } else {
-                    if candidate != full_path {
-                        fs::copy(&candidate, &full_path)?;
-                    }
-                    image
-                };
-            return Ok(dynamic_image_to_color_image(&image));
+            if candidate != full_path {
+                fs::copy(&candidate, &full_path)?;
+            }
+            let hash = perceptual_hash::dhash(&image);
+            let image = resize_entry_image(image, geometry);
+            return Ok((dynamic_image_to_color_image(&image), hash));
         }
     }
 
@@ -259,8 +817,51 @@ fn load_cached_image(
     })
 }
 
+// The entry's image at the texture size, or a title card if it has none. The
+// card is drawn at the size shown, so even thumbnails stay readable.
+fn texture_image(
+    category: &str,
+    title: &str,
+    image_directory: &Path,
+    geometry: ImageGeometry,
+    size: TextureSize,
+) -> LoadedImage {
+    match load_cached_image(category, title, image_directory, geometry) {
+        Ok((image, hash)) if size == TextureSize::Full => LoadedImage {
+            image,
+            hash: Some(hash),
+        },
+        Ok((image, hash)) => LoadedImage {
+            image: shrink_color_image(&image, size.dimensions(geometry)),
+            hash: Some(hash),
+        },
+        Err(_) => {
+            let [width, height] = size.dimensions(geometry);
+            let card_geometry = ImageGeometry {
+                width: width as u32,
+                height: height as u32,
+            };
+            LoadedImage {
+                image: dynamic_image_to_color_image(&title_card::render(
+                    title,
+                    category,
+                    card_geometry,
+                )),
+                hash: None,
+            }
+        }
+    }
+}
+
+// An older name of an entry whose name lost something may be shared with
+// other entries, so only the current file is removed for those.
 fn delete_image_file(category: &str, title: &str, image_directory: &Path) {
-    for path in image_path_candidates(image_directory, category, title) {
+    let (_, _, lossless) = file_name_parts(category, title);
+    let mut candidates = image_path_candidates(image_directory, category, title);
+    if !lossless {
+        candidates.truncate(1);
+    }
+    for path in candidates {
         fs::remove_file(path).ok();
     }
 }
@@ -294,11 +895,210 @@ mod tests {
     #[test]
     fn image_file_name_trims_category_without_chopping_last_character() {
         assert_eq!(image_file_name("Movies:", "Alien"), "Alien Movies.png");
-        assert_eq!(image_file_name("Movies", "Alien"), "Alien Movies.png");
+        assert_eq!(
+            image_file_name("Movies", "Alien"),
+            format!(
+                "Alien Movies {:08x}.png",
+                stable_hash::fnv1a_32(&["Movies", "Alien"])
+            )
+        );
         assert_eq!(
             image_file_name("Sci/Fi:", "Alien/Predator"),
-            "Alien_Predator Sci_Fi.png"
+            format!(
+                "Alien_Predator Sci_Fi {:08x}.png",
+                stable_hash::fnv1a_32(&["Sci/Fi:", "Alien/Predator"])
+            )
+        );
+    }
+
+    #[test]
+    fn image_file_names_keep_entries_that_clean_up_alike_apart() {
+        let first = image_file_name("Movies:", "Dune (1984)");
+        let second = image_file_name("Movies:", "Dune (2021)");
+        assert_ne!(first, second);
+        assert_ne!(first, image_file_name("Movies:", "Dune"));
+        assert!(first.starts_with("Dune Movies "));
+
+        // Categories differing only by the colon get files of their own.
+        assert_ne!(
+            image_file_name("Movies:", "Dune"),
+            image_file_name("Movies", "Dune")
+        );
+
+        // The name from before the hash is still looked for.
+        let candidates = image_path_candidates(Path::new("images"), "Movies:", "Dune (1984)");
+        assert_eq!(candidates[1], Path::new("images").join("Dune Movies.png"));
+    }
+
+    #[test]
+    fn load_cached_image_fits_other_sizes_without_rewriting_the_file() {
+        let root = env::temp_dir().join(format!(
+            "media-rating-image-geometry-test-{}",
+            std::process::id()
+        ));
+        let image_directory = root.join("images");
+        fs::create_dir_all(&image_directory).unwrap();
+        let path = image_path(&image_directory, "Albums:", "Blue");
+        image::DynamicImage::new_rgb8(380, 475).save(&path).unwrap();
+
+        let (square, _) =
+            load_cached_image("Albums:", "Blue", &image_directory, ImageGeometry::SQUARE).unwrap();
+        assert_eq!(square.size, [420, 420]);
+        assert_eq!(image::image_dimensions(&path).unwrap(), (380, 475));
+        assert_eq!(
+            TextureSize::Full.key("Blue", "Albums:", ImageGeometry::SQUARE),
+            "Blue Albums: 420x420"
+        );
+
+        fs::remove_dir_all(root).ok();
+    }
+
+    #[test]
+    fn entries_without_an_image_get_a_title_card() {
+        let image_directory = env::temp_dir().join(format!(
+            "media-rating-title-card-test-{}",
+            std::process::id()
+        ));
+        let [red, green, blue] = title_card::card_colour("Dune", "Movies:");
+        let background = egui::Color32::from_rgb(red, green, blue);
+
+        let full = texture_image(
+            "Movies:",
+            "Dune",
+            &image_directory,
+            ImageGeometry::POSTER,
+            TextureSize::Full,
+        );
+        assert_eq!(full.image.size, [380, 475]);
+        assert_eq!(full.image.pixels[0], background);
+        assert_eq!(full.hash, None);
+
+        let thumbnail = texture_image(
+            "Movies:",
+            "Dune",
+            &image_directory,
+            ImageGeometry::POSTER,
+            TextureSize::Thumbnail,
+        );
+        assert_eq!(thumbnail.image.size, [95, 118]);
+        assert_eq!(thumbnail.image.pixels[0], background);
+    }
+
+    #[test]
+    fn near_duplicates_are_found_across_categories() {
+        let root = env::temp_dir().join(format!(
+            "media-rating-near-duplicates-test-{}",
+            std::process::id()
+        ));
+        let image_directory = root.join("images");
+        fs::create_dir_all(&image_directory).unwrap();
+        let picture = |flipped: bool| {
+            image::DynamicImage::ImageRgb8(image::RgbImage::from_fn(380, 475, |x, y| {
+                let x = if flipped { 379 - x } else { x };
+                let shade = ((x * 255 / 380) ^ (y * 255 / 475)) as u8;
+                image::Rgb([shade, shade / 2, 255 - shade])
+            }))
+        };
+
+        let mut model = Model::default();
+        model.create_category("Movies:".to_string());
+        model.create_category("Books:".to_string());
+        for (category, entry) in [
+            ("Movies:", "Dune"),
+            ("Movies:", "Alien"),
+            ("Books:", "Dune"),
+        ] {
+            model.insert_entry_at(category, entry.to_string(), 0);
+        }
+        picture(false)
+            .save(image_path(&image_directory, "Movies:", "Dune"))
+            .unwrap();
+        picture(true)
+            .save(image_path(&image_directory, "Movies:", "Alien"))
+            .unwrap();
+        picture(false)
+            .thumbnail(190, 240)
+            .save(image_path(&image_directory, "Books:", "Dune"))
+            .unwrap();
+
+        let mut store = ImageStore::new(&root, 1 << 20);
+        assert!(store.near_duplicates(&model).is_empty());
+        for (category, entry, path) in store.unhashed_images(&model) {
+            let hash = perceptual_hash::dhash(&image::open(path).unwrap());
+            store.record_hash(&category, &entry, hash);
+        }
+        assert!(store.unhashed_images(&model).is_empty());
+        assert_eq!(
+            store.near_duplicates(&model),
+            vec![vec![
+                ("Books:".to_string(), "Dune".to_string()),
+                ("Movies:".to_string(), "Dune".to_string()),
+            ]]
+        );
+        // The hashes are kept, so the next check need not read the files.
+        let stored = store.provenance.hash("Books:", "Dune");
+        assert!(stored.is_some());
+        assert!(store.provenance("Books:", "Dune").is_none());
+        store.save_provenance();
+        assert_eq!(
+            ProvenanceManifest::load(&image_directory).hash("Books:", "Dune"),
+            stored
         );
+
+        fs::remove_dir_all(root).ok();
+    }
+
+    #[test]
+    fn snapshots_put_back_replaced_images_and_moves_carry_provenance() {
+        let root =
+            env::temp_dir().join(format!("media-rating-snapshot-test-{}", std::process::id()));
+        let image_directory = root.join("images");
+        fs::create_dir_all(&image_directory).unwrap();
+        let path = image_path(&image_directory, "Movies:", "Dune");
+        fs::write(&path, b"original image").unwrap();
+
+        let mut store = ImageStore::new(&root, 1 << 20);
+        store.provenance.restore(
+            "Movies:",
+            "Dune",
+            ImageProvenance {
+                provider: "Clipboard".to_string(),
+                ..ImageProvenance::default()
+            },
+        );
+        let saved = store.snapshot_image("Movies:", "Dune").unwrap();
+        assert!(store.snapshot_image("Movies:", "Alien").is_none());
+        assert!(path.exists());
+
+        fs::write(&path, b"replaced image").unwrap();
+        store.restore_image(&saved).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"original image");
+        assert_eq!(
+            store.provenance("Movies:", "Dune").unwrap().provider,
+            "Clipboard"
+        );
+        drop(saved);
+
+        store.move_image("Movies:", "Films:", "Dune").unwrap();
+        assert!(!path.exists());
+        assert!(image_path(&image_directory, "Films:", "Dune").exists());
+        assert!(store.provenance("Movies:", "Dune").is_none());
+        assert!(store.provenance("Films:", "Dune").is_some());
+
+        // Taken images wait in the trash until they are put back or dropped.
+        let path = image_path(&image_directory, "Films:", "Dune");
+        let trash = image_directory.join(TRASH_DIRECTORY);
+        let taken = store.take_image("Films:", "Dune").unwrap();
+        assert!(!path.exists());
+        assert!(store.provenance("Films:", "Dune").is_none());
+        assert_eq!(fs::read_dir(&trash).unwrap().count(), 1);
+        store.restore_image(&taken).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"original image");
+        assert!(store.provenance("Films:", "Dune").is_some());
+        drop(store.take_image("Films:", "Dune"));
+        assert_eq!(fs::read_dir(&trash).unwrap().count(), 0);
+
+        fs::remove_dir_all(root).ok();
     }
 
     #[test]
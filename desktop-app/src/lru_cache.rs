@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    pub evicted_bytes: usize,
+    pub used_bytes: usize,
+    pub budget_bytes: usize,
+}
+
+struct CacheEntry<V> {
+    value: V,
+    bytes: usize,
+    last_used: u64,
+}
+
+// A least-recently-used cache bounded by the total size of its values.
+// Entries touched at the current tick are never evicted, so everything on
+// screen stays resident even when it alone exceeds the budget.
+pub struct LruCache<V> {
+    entries: HashMap<String, CacheEntry<V>>,
+    stats: CacheStats,
+}
+
+impl<V: Clone> LruCache<V> {
+    pub fn new(budget_bytes: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            stats: CacheStats {
+                budget_bytes,
+                ..CacheStats::default()
+            },
+        }
+    }
+
+    pub fn get(&mut self, key: &str, now: u64) -> Option<V> {
+        match self.entries.get_mut(key) {
+            Some(entry) => {
+                entry.last_used = now;
+                self.stats.hits += 1;
+                Some(entry.value.clone())
+            }
+            None => {
+                self.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    pub fn insert(&mut self, key: String, value: V, bytes: usize, now: u64) {
+        self.remove(&key);
+        self.stats.used_bytes += bytes;
+        self.entries.insert(
+            key,
+            CacheEntry {
+                value,
+                bytes,
+                last_used: now,
+            },
+        );
+        self.evict(now);
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<(V, usize)> {
+        let entry = self.entries.remove(key)?;
+        self.stats.used_bytes -= entry.bytes;
+        Some((entry.value, entry.bytes))
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.stats.used_bytes = 0;
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    fn evict(&mut self, now: u64) {
+        while self.stats.used_bytes > self.stats.budget_bytes {
+            let Some(oldest) = self
+                .entries
+                .iter()
+                .filter(|(_, entry)| entry.last_used < now)
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone())
+            else {
+                return;
+            };
+
+            if let Some((_, bytes)) = self.remove(&oldest) {
+                self.stats.evictions += 1;
+                self.stats.evicted_bytes += bytes;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_evicts_least_recently_used_outside_current_tick() {
+        let mut cache = LruCache::new(30);
+        cache.insert("a".to_string(), 'a', 10, 1);
+        cache.insert("b".to_string(), 'b', 10, 2);
+        cache.insert("c".to_string(), 'c', 10, 3);
+        assert_eq!(cache.get("a", 4), Some('a'));
+
+        cache.insert("d".to_string(), 'd', 10, 5);
+        assert!(!cache.contains("b"));
+        assert!(cache.contains("a"));
+
+        // Everything touched this tick stays, even over budget.
+        cache.get("a", 6);
+        cache.get("c", 6);
+        cache.get("d", 6);
+        cache.insert("e".to_string(), 'e', 10, 6);
+
+        let stats = cache.stats();
+        assert_eq!(stats.used_bytes, 40);
+        assert_eq!(stats.evictions, 1);
+        assert_eq!(stats.evicted_bytes, 10);
+        assert_eq!(stats.hits, 4);
+    }
+}
@@ -1,22 +1,49 @@
 mod app;
+mod backups;
+mod bulk_fetch;
+mod bundle;
+mod connectivity;
+mod file_watch;
 mod home_screen;
+mod image_crop;
+mod image_input;
+mod image_maintenance;
+mod image_maintenance_popup;
 mod image_picker_popup;
+mod image_provenance;
+mod image_provider;
+mod image_provider_popup;
 mod image_search;
 mod image_store;
+mod lru_cache;
 mod main_screen;
+mod merge;
+mod metadata;
+mod metadata_popup;
 mod model;
+mod perceptual_hash;
 mod popup;
 mod ranking_screen;
+mod search_cache;
+mod settings;
 mod splash_screen;
 mod spreadsheet;
+mod stable_hash;
+#[cfg(test)]
+mod test_server;
+mod texture_loader;
+mod title_card;
+mod undo;
 
 fn main() {
+    let settings = settings::AppSettings::load();
     let mut options = eframe::NativeOptions::default();
+    // The screens are laid out for a fixed window size.
     options.viewport.resizable = Some(false);
 
     let _ = eframe::run_native(
         "Media Rating",
         options,
-        Box::new(|_cc| Ok(Box::new(app::MediaRatingApp::default()))),
+        Box::new(|_cc| Ok(Box::new(app::MediaRatingApp::new(settings)))),
     );
 }
@@ -0,0 +1,213 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::model::Model;
+
+// Three-way merge of two edited versions of a model against the version both
+// started from. Our ordering wins when both sides reorder a category; entries
+// and categories removed or added on either side are removed or added.
+pub fn merge_models(base: &Model, ours: &Model, theirs: &Model) -> Model {
+    let base_categories: HashMap<&str, &[String]> = base.categories().collect();
+    let our_categories: HashMap<&str, &[String]> = ours.categories().collect();
+    let their_categories: HashMap<&str, &[String]> = theirs.categories().collect();
+
+    let names: HashSet<&str> = our_categories
+        .keys()
+        .chain(their_categories.keys())
+        .copied()
+        .collect();
+
+    let mut merged = HashMap::new();
+    for name in names {
+        let base_entries = base_categories.get(name).copied();
+        let our_entries = our_categories.get(name).copied();
+        let their_entries = their_categories.get(name).copied();
+
+        let entries = match (base_entries, our_entries, their_entries) {
+            (base_entries, Some(our_entries), Some(their_entries)) => {
+                merge_entries(base_entries.unwrap_or(&[]), our_entries, their_entries)
+            }
+            // Deleted on one side: keep it only if the other side changed it.
+            (Some(base_entries), Some(entries), None)
+            | (Some(base_entries), None, Some(entries)) => {
+                if entries == base_entries {
+                    continue;
+                }
+                entries.to_vec()
+            }
+            (None, Some(entries), None) | (None, None, Some(entries)) => entries.to_vec(),
+            (_, None, None) => continue,
+        };
+
+        merged.insert(name.to_string(), entries);
+    }
+
+    let mut merged = Model::from_categories(merged);
+    for category in merged.get_categories() {
+        // Records of surviving entries are ours unless only they changed them,
+        // so a record either side removed stays removed.
+        for entry in merged.get_category_entries(&category).to_vec() {
+            let base_metadata = base.entry_metadata(&category, &entry);
+            let our_metadata = ours.entry_metadata(&category, &entry);
+            let metadata = if our_metadata == base_metadata {
+                theirs.entry_metadata(&category, &entry)
+            } else {
+                our_metadata
+            };
+            merged.set_entry_metadata(&category, entry, metadata.cloned());
+        }
+
+        // Category settings are ours unless only they changed them.
+        let geometry = if ours.image_geometry(&category) == base.image_geometry(&category) {
+            theirs.image_geometry(&category)
+        } else {
+            ours.image_geometry(&category)
+        };
+        if let Some(geometry) = geometry {
+            merged.set_image_geometry(&category, geometry);
+        }
+    }
+
+    merged
+}
+
+pub fn merge_entries(base: &[String], ours: &[String], theirs: &[String]) -> Vec<String> {
+    if ours == base {
+        return theirs.to_vec();
+    }
+    if theirs == base || ours == theirs {
+        return ours.to_vec();
+    }
+
+    let mut merged: Vec<String> = ours
+        .iter()
+        .filter(|entry| !base.contains(entry) || theirs.contains(entry))
+        .cloned()
+        .collect();
+
+    // Place each entry they added after the nearest entry preceding it in
+    // their list that is also in the merged list.
+    for (index, entry) in theirs.iter().enumerate() {
+        if base.contains(entry) || merged.contains(entry) {
+            continue;
+        }
+
+        let position = theirs[..index]
+            .iter()
+            .rev()
+            .find_map(|previous| merged.iter().position(|existing| existing == previous))
+            .map_or(0, |previous_index| previous_index + 1);
+        merged.insert(position, entry.clone());
+    }
+
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{image_store::ImageGeometry, metadata::EntryMetadata};
+
+    fn entries(entries: &[&str]) -> Vec<String> {
+        entries.iter().map(|entry| entry.to_string()).collect()
+    }
+
+    #[test]
+    fn merge_entries_combines_additions_and_removals_from_both_sides() {
+        let base = entries(&["A", "B", "C", "D"]);
+        let ours = entries(&["A", "New", "C", "B", "D"]);
+        let theirs = entries(&["A", "B", "C", "Theirs", "D"]);
+
+        assert_eq!(
+            merge_entries(&base, &ours, &theirs),
+            entries(&["A", "New", "C", "Theirs", "B", "D"])
+        );
+
+        let theirs = entries(&["A", "C", "D"]);
+        assert_eq!(
+            merge_entries(&base, &ours, &theirs),
+            entries(&["A", "New", "C", "D"])
+        );
+    }
+
+    #[test]
+    fn merge_models_keeps_categories_deleted_on_one_side_only_if_edited_on_the_other() {
+        let mut base = Model::default();
+        base.create_category("Movies:".to_string());
+        base.insert_entry_at("Movies:", "Alien".to_string(), 0);
+        base.create_category("Books:".to_string());
+        base.insert_entry_at("Books:", "Dune".to_string(), 0);
+
+        let mut ours = base.clone();
+        ours.delete_category("Movies:");
+        ours.delete_category("Books:");
+
+        let mut theirs = base.clone();
+        theirs.insert_entry_at("Books:", "Emma".to_string(), 1);
+        theirs.create_category("Games:".to_string());
+
+        let merged = merge_models(&base, &ours, &theirs);
+
+        assert_eq!(merged.get_categories(), entries(&["Books:", "Games:"]));
+        assert_eq!(
+            merged.get_category_entries("Books:"),
+            entries(&["Dune", "Emma"]).as_slice()
+        );
+    }
+
+    #[test]
+    fn merge_models_keeps_metadata_changed_or_removed_on_either_side() {
+        let record = |title: &str| EntryMetadata {
+            title: title.to_string(),
+            year: None,
+            creator: None,
+            poster_url: None,
+            source: "Open Library".to_string(),
+            id: title.to_string(),
+        };
+        let mut base = Model::default();
+        base.create_category("Books:".to_string());
+        for (index, entry) in ["Dune", "Emma", "Ulysses"].into_iter().enumerate() {
+            base.insert_entry_at("Books:", entry.to_string(), index);
+            base.set_entry_metadata("Books:", entry.to_string(), Some(record(entry)));
+        }
+
+        let mut ours = base.clone();
+        ours.set_entry_metadata("Books:", "Dune".to_string(), None);
+        ours.set_entry_metadata("Books:", "Emma".to_string(), Some(record("Emma (1815)")));
+        let mut theirs = base.clone();
+        theirs.set_entry_metadata("Books:", "Dune".to_string(), Some(record("Dune")));
+        theirs.set_entry_metadata("Books:", "Emma".to_string(), Some(record("Emma")));
+        theirs.set_entry_metadata("Books:", "Ulysses".to_string(), None);
+
+        let merged = merge_models(&base, &ours, &theirs);
+        assert_eq!(merged.entry_metadata("Books:", "Dune"), None);
+        assert_eq!(
+            merged.entry_metadata("Books:", "Emma"),
+            Some(&record("Emma (1815)"))
+        );
+        assert_eq!(merged.entry_metadata("Books:", "Ulysses"), None);
+    }
+
+    #[test]
+    fn merge_models_keeps_image_geometries_changed_on_either_side() {
+        let mut base = Model::default();
+        base.create_category("Movies:".to_string());
+        base.create_category("Albums:".to_string());
+
+        let mut ours = base.clone();
+        ours.set_image_geometry("Movies:", ImageGeometry::BANNER);
+        let mut theirs = base.clone();
+        theirs.set_image_geometry("Albums:", ImageGeometry::SQUARE);
+        theirs.set_image_geometry("Movies:", ImageGeometry::SQUARE);
+
+        let merged = merge_models(&base, &ours, &theirs);
+        assert_eq!(
+            merged.image_geometry("Movies:"),
+            Some(ImageGeometry::BANNER)
+        );
+        assert_eq!(
+            merged.image_geometry("Albums:"),
+            Some(ImageGeometry::SQUARE)
+        );
+    }
+}
@@ -0,0 +1,308 @@
+use chrono::{DateTime, Datelike};
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{image_provider::json_path, image_store::ImageFetchError};
+
+const MAX_CANDIDATES: usize = 10;
+
+// The catalogue record chosen for an entry.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EntryMetadata {
+    pub title: String,
+    pub year: Option<i32>,
+    pub creator: Option<String>,
+    pub poster_url: Option<String>,
+    // Which catalogue the record came from and its id there.
+    pub source: String,
+    pub id: String,
+}
+
+impl EntryMetadata {
+    pub fn summary(&self) -> String {
+        let mut summary = self.title.clone();
+        if let Some(year) = self.year {
+            summary.push_str(&format!(" ({year})"));
+        }
+        if let Some(creator) = &self.creator {
+            summary.push_str(&format!(", {creator}"));
+        }
+        summary
+    }
+}
+
+// The response shapes we understand.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CatalogueKind {
+    #[default]
+    Tmdb,
+    OpenLibrary,
+    Igdb,
+}
+
+impl CatalogueKind {
+    pub const ALL: [CatalogueKind; 3] = [
+        CatalogueKind::Tmdb,
+        CatalogueKind::OpenLibrary,
+        CatalogueKind::Igdb,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CatalogueKind::Tmdb => "TMDB",
+            CatalogueKind::OpenLibrary => "Open Library",
+            CatalogueKind::Igdb => "IGDB",
+        }
+    }
+
+    pub fn default_config(self) -> CatalogueConfig {
+        let (base_url, image_base_url) = match self {
+            CatalogueKind::Tmdb => (
+                "https://api.themoviedb.org/3",
+                "https://image.tmdb.org/t/p/w500",
+            ),
+            CatalogueKind::OpenLibrary => (
+                "https://openlibrary.org",
+                "https://covers.openlibrary.org/b/id",
+            ),
+            CatalogueKind::Igdb => ("https://api.igdb.com/v4", "https:"),
+        };
+
+        CatalogueConfig {
+            kind: self,
+            base_url: base_url.to_string(),
+            image_base_url: image_base_url.to_string(),
+            api_key: String::new(),
+            client_id: String::new(),
+        }
+    }
+}
+
+// Where a category looks up metadata, as stored in the settings. The base URLs
+// can point at a mirror or proxy with the same response shape.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CatalogueConfig {
+    pub kind: CatalogueKind,
+    pub base_url: String,
+    // Prefix for the poster paths the catalogue returns.
+    pub image_base_url: String,
+    // Kept only for the session: settings are stored as plain text, so the key
+    // is never written and has to be entered again after a restart.
+    #[serde(skip)]
+    pub api_key: String,
+    // Only used by IGDB, which wants a Twitch client id next to the token.
+    pub client_id: String,
+}
+
+impl Default for CatalogueConfig {
+    fn default() -> Self {
+        CatalogueKind::default().default_config()
+    }
+}
+
+// Candidate records for a title, best match first.
+pub fn search(config: &CatalogueConfig, query: &str) -> Result<Vec<EntryMetadata>, String> {
+    let client = Client::new();
+    let base_url = config.base_url.trim_end_matches('/');
+
+    let request = match config.kind {
+        CatalogueKind::Tmdb => client
+            .get(format!("{base_url}/search/multi"))
+            .query(&[("query", query), ("api_key", config.api_key.as_str())]),
+        CatalogueKind::OpenLibrary => client
+            .get(format!("{base_url}/search.json"))
+            .query(&[("title", query), ("limit", &MAX_CANDIDATES.to_string())]),
+        CatalogueKind::Igdb => client
+            .post(format!("{base_url}/games"))
+            .header("Client-ID", &config.client_id)
+            .bearer_auth(&config.api_key)
+            .body(format!(
+                "search \"{}\"; fields name,first_release_date,cover.url,involved_companies.company.name; limit {MAX_CANDIDATES};",
+                query.replace('"', "")
+            )),
+    };
+
+    let response = request
+        .send()
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| format!("{} lookup failed: {e}", config.kind.name()))?;
+    let json: Value = serde_json::from_str(&response.text().map_err(|e| e.to_string())?)
+        .map_err(|e| format!("{} returned invalid JSON: {e}", config.kind.name()))?;
+
+    let records = match config.kind {
+        CatalogueKind::Tmdb => parse_tmdb(&json, &config.image_base_url),
+        CatalogueKind::OpenLibrary => parse_open_library(&json, &config.image_base_url),
+        CatalogueKind::Igdb => parse_igdb(&json, &config.image_base_url),
+    };
+    Ok(records.into_iter().take(MAX_CANDIDATES).collect())
+}
+
+pub fn fetch_poster(url: &str) -> Result<image::DynamicImage, ImageFetchError> {
+    let response = Client::new().get(url).send()?.error_for_status()?;
+    Ok(image::load_from_memory(&response.bytes()?)?)
+}
+
+// Catalogues index titles without the notes people add in brackets.
+pub fn lookup_query(entry: &str) -> String {
+    entry
+        .split(['(', '['])
+        .next()
+        .unwrap_or(entry)
+        .trim()
+        .to_string()
+}
+
+fn parse_tmdb(json: &Value, image_base_url: &str) -> Vec<EntryMetadata> {
+    records(json, "results")
+        // Multi-search also returns people, which have no poster or year.
+        .filter(|record| matches!(text(record, "media_type").as_deref(), Some("movie" | "tv")))
+        .filter_map(|record| {
+            Some(EntryMetadata {
+                // Movies have titles and release dates, shows names and air dates.
+                title: text(record, "title").or_else(|| text(record, "name"))?,
+                year: text(record, "release_date")
+                    .or_else(|| text(record, "first_air_date"))
+                    .and_then(|date| date.get(..4)?.parse().ok()),
+                creator: None,
+                poster_url: text(record, "poster_path")
+                    .map(|path| format!("{image_base_url}{path}")),
+                source: CatalogueKind::Tmdb.name().to_string(),
+                id: id(record, "id")?,
+            })
+        })
+        .collect()
+}
+
+fn parse_open_library(json: &Value, image_base_url: &str) -> Vec<EntryMetadata> {
+    records(json, "docs")
+        .filter_map(|record| {
+            Some(EntryMetadata {
+                title: text(record, "title")?,
+                year: json_path(record, "first_publish_year")
+                    .and_then(Value::as_i64)
+                    .map(|year| year as i32),
+                creator: text(record, "author_name.0"),
+                poster_url: json_path(record, "cover_i")
+                    .and_then(Value::as_i64)
+                    .map(|cover| format!("{image_base_url}/{cover}-L.jpg")),
+                source: CatalogueKind::OpenLibrary.name().to_string(),
+                id: id(record, "key")?,
+            })
+        })
+        .collect()
+}
+
+fn parse_igdb(json: &Value, image_base_url: &str) -> Vec<EntryMetadata> {
+    records(json, "")
+        .filter_map(|record| {
+            Some(EntryMetadata {
+                title: text(record, "name")?,
+                year: json_path(record, "first_release_date")
+                    .and_then(Value::as_i64)
+                    .and_then(|seconds| DateTime::from_timestamp(seconds, 0))
+                    .map(|date| date.year()),
+                creator: text(record, "involved_companies.0.company.name"),
+                // Covers come as protocol-relative thumbnail links.
+                poster_url: text(record, "cover.url").map(|url| {
+                    format!("{image_base_url}{}", url.replace("t_thumb", "t_cover_big"))
+                }),
+                source: CatalogueKind::Igdb.name().to_string(),
+                id: id(record, "id")?,
+            })
+        })
+        .collect()
+}
+
+fn records<'a>(json: &'a Value, path: &str) -> impl Iterator<Item = &'a Value> {
+    json_path(json, path)
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+}
+
+fn text(record: &Value, path: &str) -> Option<String> {
+    json_path(record, path)
+        .and_then(Value::as_str)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+}
+
+fn id(record: &Value, path: &str) -> Option<String> {
+    match json_path(record, path)? {
+        Value::String(id) => Some(id.clone()),
+        Value::Number(id) => Some(id.to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::TestServer;
+
+    fn fixture_server() -> TestServer {
+        TestServer::start(|path| {
+            let body = if path.starts_with("/search/multi?query=Dune&api_key=key") {
+                serde_json::json!({"results": [
+                    {"id": 438631, "media_type": "movie", "title": "Dune", "release_date": "2021-09-15", "poster_path": "/dune.jpg"},
+                    {"id": 1396, "media_type": "tv", "name": "Dune", "first_air_date": "2000-12-03"},
+                    {"id": 7, "name": "Denis Villeneuve", "media_type": "person", "profile_path": "/denis.jpg"},
+                ]})
+            } else if path.starts_with("/search.json?title=Dune") {
+                serde_json::json!({"docs": [
+                    {"key": "/works/OL893415W", "title": "Dune", "first_publish_year": 1965, "author_name": ["Frank Herbert"], "cover_i": 11481354},
+                ]})
+            } else if path == "/games" {
+                serde_json::json!([
+                    {"id": 2140, "name": "Dune II", "first_release_date": 723081600, "cover": {"url": "//images.igdb.com/t_thumb/co1.jpg"}, "involved_companies": [{"company": {"name": "Westwood"}}]},
+                ])
+            } else {
+                return None;
+            };
+            Some(("application/json", body.to_string().into_bytes()))
+        })
+    }
+
+    #[test]
+    fn search_parses_each_catalogue_shape() {
+        let server = fixture_server();
+        let config = |kind: CatalogueKind| CatalogueConfig {
+            base_url: server.url(),
+            image_base_url: "https://images.test".to_string(),
+            api_key: "key".to_string(),
+            ..kind.default_config()
+        };
+
+        let movies = search(&config(CatalogueKind::Tmdb), "Dune").unwrap();
+        assert_eq!(movies.len(), 2);
+        assert_eq!(movies[0].summary(), "Dune (2021)");
+        assert_eq!(
+            movies[0].poster_url.as_deref(),
+            Some("https://images.test/dune.jpg")
+        );
+        assert_eq!(
+            (movies[1].year, movies[1].id.as_str()),
+            (Some(2000), "1396")
+        );
+
+        let books = search(&config(CatalogueKind::OpenLibrary), "Dune").unwrap();
+        assert_eq!(books[0].summary(), "Dune (1965), Frank Herbert");
+        assert_eq!(books[0].id, "/works/OL893415W");
+
+        let games = search(&config(CatalogueKind::Igdb), "Dune").unwrap();
+        assert_eq!(games[0].summary(), "Dune II (1992), Westwood");
+        assert_eq!(
+            games[0].poster_url.as_deref(),
+            Some("https://images.test//images.igdb.com/t_cover_big/co1.jpg")
+        );
+    }
+
+    #[test]
+    fn lookup_query_drops_bracketed_notes() {
+        assert_eq!(lookup_query("Alien (1979 director's cut)"), "Alien");
+        assert_eq!(lookup_query("Dune [reread]"), "Dune");
+        assert_eq!(lookup_query("Arrival"), "Arrival");
+    }
+}
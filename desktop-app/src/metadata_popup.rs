@@ -0,0 +1,270 @@
+use eframe::egui;
+use std::{
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+use crate::{
+    app::AppAction,
+    metadata::{self, CatalogueConfig, CatalogueKind, EntryMetadata},
+    popup::{Popup, PopupResponse},
+};
+
+// What the user settled on, boxed into the action to keep it small.
+pub struct MetadataChoice {
+    pub metadata: Option<EntryMetadata>,
+    pub poster: Option<image::DynamicImage>,
+    pub catalogue: CatalogueConfig,
+}
+
+enum LookupState {
+    Idle,
+    Searching(Receiver<Result<Vec<EntryMetadata>, String>>),
+    Loaded(Vec<EntryMetadata>),
+    Failed(String),
+    // A record was chosen and its poster is downloading.
+    FetchingPoster {
+        metadata: EntryMetadata,
+        receiver: Receiver<Result<image::DynamicImage, String>>,
+    },
+}
+
+// Looks an entry up in the category's catalogue and lets the user pick the
+// matching record. The catalogue settings used are remembered for the
+// category once a record is chosen.
+pub struct MetadataPopup {
+    category: String,
+    entry: String,
+    query: String,
+    catalogue: CatalogueConfig,
+    current: Option<EntryMetadata>,
+    use_poster: bool,
+    state: LookupState,
+}
+
+impl MetadataPopup {
+    pub fn new(
+        category: String,
+        entry: String,
+        catalogue: CatalogueConfig,
+        current: Option<EntryMetadata>,
+    ) -> Self {
+        let query = metadata::lookup_query(&entry);
+        Self {
+            category,
+            entry,
+            query,
+            catalogue,
+            current,
+            use_poster: true,
+            state: LookupState::Idle,
+        }
+    }
+
+    fn start_search(&mut self, ctx: &egui::Context) {
+        let (sender, receiver) = mpsc::channel();
+        let repaint_ctx = ctx.clone();
+        let catalogue = self.catalogue.clone();
+        let query = self.query.trim().to_string();
+        thread::spawn(move || {
+            let _ = sender.send(metadata::search(&catalogue, &query));
+            repaint_ctx.request_repaint();
+        });
+
+        self.state = LookupState::Searching(receiver);
+    }
+
+    fn choose(&mut self, metadata: EntryMetadata, ctx: &egui::Context) -> PopupResponse {
+        let poster_url = metadata.poster_url.clone().filter(|_| self.use_poster);
+        let Some(poster_url) = poster_url else {
+            return self.finish(Some(metadata), None);
+        };
+
+        let (sender, receiver) = mpsc::channel();
+        let repaint_ctx = ctx.clone();
+        thread::spawn(move || {
+            let _ = sender.send(metadata::fetch_poster(&poster_url).map_err(|e| e.details));
+            repaint_ctx.request_repaint();
+        });
+
+        self.state = LookupState::FetchingPoster { metadata, receiver };
+        PopupResponse::KeepOpen
+    }
+
+    fn finish(
+        &self,
+        metadata: Option<EntryMetadata>,
+        poster: Option<image::DynamicImage>,
+    ) -> PopupResponse {
+        PopupResponse::Action(AppAction::SetEntryMetadata {
+            category: self.category.clone(),
+            entry: self.entry.clone(),
+            choice: Box::new(MetadataChoice {
+                metadata,
+                poster,
+                catalogue: self.catalogue.clone(),
+            }),
+        })
+    }
+
+    fn poll(&mut self) -> Option<PopupResponse> {
+        match &self.state {
+            LookupState::Searching(receiver) => {
+                let result = receiver.try_recv().ok()?;
+                self.state = match result {
+                    Ok(records) if records.is_empty() => {
+                        LookupState::Failed("No matching records found.".to_string())
+                    }
+                    Ok(records) => LookupState::Loaded(records),
+                    Err(message) => LookupState::Failed(message),
+                };
+                None
+            }
+            LookupState::FetchingPoster { metadata, receiver } => {
+                let result = receiver.try_recv().ok()?;
+                let metadata = metadata.clone();
+                match result {
+                    Ok(poster) => Some(self.finish(Some(metadata), Some(poster))),
+                    Err(message) => {
+                        self.state = LookupState::Failed(format!(
+                            "Could not download the poster: {message}"
+                        ));
+                        None
+                    }
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn catalogue_settings(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new(format!("Catalogue: {}", self.catalogue.kind.name())).show(
+            ui,
+            |ui| {
+                ui.horizontal(|ui| {
+                    for kind in CatalogueKind::ALL {
+                        if ui.radio(self.catalogue.kind == kind, kind.name()).clicked()
+                            && self.catalogue.kind != kind
+                        {
+                            self.catalogue = kind.default_config();
+                        }
+                    }
+                });
+
+                egui::Grid::new("catalogue-settings")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        let catalogue = &mut self.catalogue;
+                        let mut fields = vec![
+                            ("API URL", &mut catalogue.base_url),
+                            ("Image URL", &mut catalogue.image_base_url),
+                            ("API key", &mut catalogue.api_key),
+                        ];
+                        if catalogue.kind == CatalogueKind::Igdb {
+                            fields.push(("Client ID", &mut catalogue.client_id));
+                        }
+
+                        for (label, value) in fields {
+                            ui.label(label);
+                            let mut field = egui::TextEdit::singleline(value);
+                            if label == "API key" {
+                                field = field.password(true).hint_text("For this session only");
+                            }
+                            ui.add_sized([320.0, 20.0], field);
+                            ui.end_row();
+                        }
+                        // The key is never written to disk, so say so where it is typed.
+                        ui.label("");
+                        ui.label(
+                            egui::RichText::new(
+                                "The API key is kept only until the app closes. \
+                                 It is not saved and has to be entered again next time.",
+                            )
+                            .color(ui.visuals().warn_fg_color),
+                        );
+                        ui.end_row();
+                    });
+            },
+        );
+    }
+}
+
+impl Popup for MetadataPopup {
+    fn title(&self) -> &str {
+        "Look Up Details"
+    }
+
+    fn show_body(&mut self, ui: &mut egui::Ui) -> PopupResponse {
+        if let Some(response) = self.poll() {
+            return response;
+        }
+
+        let mut response = PopupResponse::KeepOpen;
+
+        ui.label(format!("{} - {}", self.entry, self.category));
+        if let Some(current) = &self.current {
+            ui.label(format!("Currently: {}", current.summary()));
+        }
+
+        self.catalogue_settings(ui);
+
+        ui.horizontal(|ui| {
+            let query_response =
+                ui.add_sized([300.0, 24.0], egui::TextEdit::singleline(&mut self.query));
+            let enter_pressed = query_response.lost_focus()
+                && ui.input(|input| input.key_pressed(egui::Key::Enter));
+            if (ui.button("Search").clicked() || enter_pressed) && !self.query.trim().is_empty() {
+                self.start_search(ui.ctx());
+            }
+        });
+        ui.checkbox(&mut self.use_poster, "Use the official poster as the image");
+        ui.add_space(8.0);
+
+        let mut chosen = None;
+        match &self.state {
+            LookupState::Idle => {}
+            LookupState::Searching(_) => {
+                ui.spinner();
+            }
+            LookupState::FetchingPoster { metadata, .. } => {
+                ui.label(format!("Downloading the poster for {}...", metadata.title));
+                ui.spinner();
+            }
+            LookupState::Failed(message) => {
+                ui.label(message);
+            }
+            LookupState::Loaded(records) => {
+                egui::ScrollArea::vertical()
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        for record in records {
+                            let mut text = record.summary();
+                            if record.poster_url.is_some() {
+                                text.push_str("  [poster]");
+                            }
+                            if ui.selectable_label(false, text).clicked() {
+                                chosen = Some(record.clone());
+                            }
+                        }
+                    });
+            }
+        }
+
+        if let Some(metadata) = chosen {
+            response = self.choose(metadata, ui.ctx());
+        }
+
+        ui.add_space(8.0);
+        ui.horizontal(|ui| {
+            if self.current.is_some() && ui.button("Clear Details").clicked() {
+                response = self.finish(None, None);
+            }
+
+            if ui.button("Cancel").clicked() {
+                response = PopupResponse::Close;
+            }
+        });
+
+        response
+    }
+}
@@ -1,14 +1,25 @@
 use std::collections::HashMap;
 
-#[derive(Debug, Default)]
+use crate::{image_store::ImageGeometry, metadata::EntryMetadata};
+
+#[derive(Debug, Default, Clone, PartialEq)]
 pub struct Model {
     // Name of category mapped to vector of all entries in it.
     categories: HashMap<String, Vec<String>>,
+    // Catalogue records by category, then entry.
+    metadata: HashMap<String, HashMap<String, EntryMetadata>>,
+    // The size each category's images are kept at; missing ones use the
+    // default.
+    image_geometries: HashMap<String, ImageGeometry>,
 }
 
 impl Model {
     pub(crate) fn from_categories(categories: HashMap<String, Vec<String>>) -> Self {
-        Self { categories }
+        Self {
+            categories,
+            metadata: HashMap::new(),
+            image_geometries: HashMap::new(),
+        }
     }
 
     pub fn categories(&self) -> impl Iterator<Item = (&str, &[String])> {
@@ -25,6 +36,8 @@ impl Model {
     // Delete a category.
     pub fn delete_category(&mut self, category: &str) {
         self.categories.remove(category);
+        self.metadata.remove(category);
+        self.image_geometries.remove(category);
     }
 
     // Get a vector of all categories.
@@ -85,7 +98,10 @@ impl Model {
         if let Some(entries) = self.categories.get_mut(category) {
             if index < entries.len() {
                 let old_title = entries[index].clone();
-                entries[index] = new_name;
+                entries[index] = new_name.clone();
+                if let Some(metadata) = self.take_entry_metadata(category, &old_title) {
+                    self.set_entry_metadata(category, new_name, Some(metadata));
+                }
                 return Some(old_title);
             }
         }
@@ -100,7 +116,85 @@ impl Model {
             return None;
         }
 
-        Some(entries.remove(index))
+        let entry = entries.remove(index);
+        self.take_entry_metadata(category, &entry);
+        Some(entry)
+    }
+
+    pub fn entry_metadata(&self, category: &str, entry: &str) -> Option<&EntryMetadata> {
+        self.metadata.get(category)?.get(entry)
+    }
+
+    // Every stored record, for saving.
+    pub fn all_metadata(&self) -> impl Iterator<Item = (&str, &str, &EntryMetadata)> {
+        self.metadata.iter().flat_map(|(category, records)| {
+            records
+                .iter()
+                .map(move |(entry, metadata)| (category.as_str(), entry.as_str(), metadata))
+        })
+    }
+
+    // Records for entries that do not exist are dropped.
+    pub fn set_entry_metadata(
+        &mut self,
+        category: &str,
+        entry: String,
+        metadata: Option<EntryMetadata>,
+    ) {
+        match metadata {
+            Some(metadata) if self.contains_entry(category, &entry) => {
+                self.metadata
+                    .entry(category.to_string())
+                    .or_default()
+                    .insert(entry, metadata);
+            }
+            _ => {
+                self.take_entry_metadata(category, &entry);
+            }
+        }
+    }
+
+    pub fn take_entry_metadata(&mut self, category: &str, entry: &str) -> Option<EntryMetadata> {
+        self.metadata.get_mut(category)?.remove(entry)
+    }
+
+    pub fn image_geometry(&self, category: &str) -> Option<ImageGeometry> {
+        self.image_geometries.get(category).copied()
+    }
+
+    pub fn image_geometries(&self) -> impl Iterator<Item = (&str, ImageGeometry)> {
+        self.image_geometries
+            .iter()
+            .map(|(category, geometry)| (category.as_str(), *geometry))
+    }
+
+    // Geometries of categories that do not exist are dropped.
+    pub fn set_image_geometry(&mut self, category: &str, geometry: ImageGeometry) {
+        if self.categories.contains_key(category) {
+            self.image_geometries.insert(category.to_string(), geometry);
+        }
+    }
+
+    // Just the given entries of one category, with their records, in the
+    // order they are ranked here.
+    pub fn subset(&self, category: &str, entries: &[String]) -> Model {
+        let mut subset = Model::default();
+        subset.create_category(category.to_string());
+        if let Some(geometry) = self.image_geometry(category) {
+            subset.set_image_geometry(category, geometry);
+        }
+        for entry in self.get_category_entries(category) {
+            if entries.contains(entry) {
+                let index = subset.get_category_entries(category).len();
+                subset.insert_entry_at(category, entry.clone(), index);
+                subset.set_entry_metadata(
+                    category,
+                    entry.clone(),
+                    self.entry_metadata(category, entry).cloned(),
+                );
+            }
+        }
+        subset
     }
 }
 
@@ -141,4 +235,52 @@ mod tests {
             category_entries(&["A", "C", "B"]).as_slice()
         );
     }
+
+    #[test]
+    fn entry_metadata_follows_renames_and_deletes() {
+        let mut model = Model::default();
+        model.create_category("Books:".to_string());
+        model.insert_entry_at("Books:", "Dune".to_string(), 0);
+        let metadata = EntryMetadata {
+            title: "Dune".to_string(),
+            year: Some(1965),
+            ..EntryMetadata::default()
+        };
+
+        model.set_entry_metadata("Books:", "Missing".to_string(), Some(metadata.clone()));
+        model.set_entry_metadata("Books:", "Dune".to_string(), Some(metadata.clone()));
+        model.rename_entry("Books:", 0, "Dune (1965)".to_string());
+
+        assert_eq!(model.all_metadata().count(), 1);
+        assert_eq!(
+            model.entry_metadata("Books:", "Dune (1965)"),
+            Some(&metadata)
+        );
+
+        model.delete_entry("Books:", 0);
+        assert_eq!(model.all_metadata().count(), 0);
+    }
+
+    #[test]
+    fn subset_keeps_rank_order_and_records() {
+        let mut model = Model::default();
+        model.create_category("Books:".to_string());
+        for (index, entry) in ["Dune", "Emma", "Ulysses"].into_iter().enumerate() {
+            model.insert_entry_at("Books:", entry.to_string(), index);
+        }
+        let metadata = EntryMetadata {
+            title: "Ulysses".to_string(),
+            ..EntryMetadata::default()
+        };
+        model.set_entry_metadata("Books:", "Ulysses".to_string(), Some(metadata.clone()));
+
+        let subset = model.subset("Books:", &category_entries(&["Ulysses", "Dune"]));
+
+        assert_eq!(
+            subset.get_category_entries("Books:"),
+            category_entries(&["Dune", "Ulysses"]).as_slice()
+        );
+        assert_eq!(subset.entry_metadata("Books:", "Ulysses"), Some(&metadata));
+        assert_eq!(subset.get_categories(), vec!["Books:".to_string()]);
+    }
 }
@@ -0,0 +1,92 @@
+use image::DynamicImage;
+
+// Hashes at most this many bits apart are treated as the same picture, e.g.
+// one poster saved at two sizes or re-encoded.
+pub const NEAR_DUPLICATE_DISTANCE: u32 = 6;
+
+// Difference hash: the image shrunk to 9×8 grey pixels, one bit per pair of
+// horizontal neighbours saying whether brightness rises. Survives resizing
+// and recompression but not cropping.
+pub fn dhash(image: &DynamicImage) -> u64 {
+    let small = image.thumbnail_exact(9, 8).to_luma8();
+    let mut hash = 0;
+    for y in 0..8 {
+        for x in 0..8 {
+            let brighter = small.get_pixel(x + 1, y)[0] > small.get_pixel(x, y)[0];
+            hash = (hash << 1) | u64::from(brighter);
+        }
+    }
+    hash
+}
+
+pub fn is_near_duplicate(a: u64, b: u64) -> bool {
+    (a ^ b).count_ones() <= NEAR_DUPLICATE_DISTANCE
+}
+
+// Groups of two or more keys whose hashes are near one another, directly or
+// through other members, in the order they were given.
+pub fn near_duplicate_groups<K: Clone>(hashes: &[(K, u64)]) -> Vec<Vec<K>> {
+    // Union-find over indices, the lower index always the root.
+    let mut parents: Vec<usize> = (0..hashes.len()).collect();
+    for a in 0..hashes.len() {
+        for b in a + 1..hashes.len() {
+            if is_near_duplicate(hashes[a].1, hashes[b].1) {
+                let (root_a, root_b) = (root(&mut parents, a), root(&mut parents, b));
+                parents[root_a.max(root_b)] = root_a.min(root_b);
+            }
+        }
+    }
+
+    let mut groups: Vec<(usize, Vec<K>)> = Vec::new();
+    for (index, (key, _)) in hashes.iter().enumerate() {
+        let group_root = root(&mut parents, index);
+        match groups.iter_mut().find(|(root, _)| *root == group_root) {
+            Some((_, keys)) => keys.push(key.clone()),
+            None => groups.push((group_root, vec![key.clone()])),
+        }
+    }
+    groups
+        .into_iter()
+        .map(|(_, keys)| keys)
+        .filter(|keys| keys.len() > 1)
+        .collect()
+}
+
+fn root(parents: &mut [usize], mut index: usize) -> usize {
+    while parents[index] != index {
+        parents[index] = parents[parents[index]];
+        index = parents[index];
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{imageops::FilterType, Rgb, RgbImage};
+
+    fn gradient(width: u32, height: u32, flipped: bool) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, y| {
+            let x = if flipped { width - 1 - x } else { x };
+            let shade = ((x * 255 / width) ^ (y * 255 / height)) as u8;
+            Rgb([shade, shade / 2, 255 - shade])
+        }))
+    }
+
+    #[test]
+    fn resized_copies_match_and_different_images_do_not() {
+        let poster = gradient(380, 475, false);
+        let smaller = poster.resize_exact(190, 240, FilterType::Triangle);
+        let other = gradient(380, 475, true);
+
+        assert!(is_near_duplicate(dhash(&poster), dhash(&smaller)));
+        assert!(!is_near_duplicate(dhash(&poster), dhash(&other)));
+
+        let groups = near_duplicate_groups(&[
+            ("Dune", dhash(&poster)),
+            ("Alien", dhash(&other)),
+            ("Dune: Part Two", dhash(&smaller)),
+        ]);
+        assert_eq!(groups, vec![vec!["Dune", "Dune: Part Two"]]);
+    }
+}
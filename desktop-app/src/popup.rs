@@ -1,6 +1,7 @@
 use eframe::egui;
+use std::path::PathBuf;
 
-use crate::app::AppAction;
+use crate::{app::AppAction, backups::Backup};
 
 pub enum PopupResponse {
     KeepOpen,
@@ -116,6 +117,424 @@ impl Popup for ConfirmDuplicateSwitchPopup {
     }
 }
 
+pub struct ConfirmDeleteEntriesPopup {
+    category: String,
+    entries: Vec<String>,
+}
+
+impl ConfirmDeleteEntriesPopup {
+    pub fn new(category: String, entries: Vec<String>) -> Self {
+        Self { category, entries }
+    }
+}
+
+impl Popup for ConfirmDeleteEntriesPopup {
+    fn title(&self) -> &str {
+        "Warning"
+    }
+
+    fn show_body(&mut self, ui: &mut egui::Ui) -> PopupResponse {
+        let mut response = PopupResponse::KeepOpen;
+
+        ui.label(format!(
+            "This will delete {} entries from {} along with their images:",
+            self.entries.len(),
+            self.category
+        ));
+        egui::ScrollArea::vertical()
+            .max_height(200.0)
+            .show(ui, |ui| {
+                for entry in &self.entries {
+                    ui.label(format!("- {entry}"));
+                }
+            });
+        ui.label("Use Undo to bring them back.");
+
+        ui.add_space(8.0);
+        ui.horizontal(|ui| {
+            if ui.button("Delete").clicked() {
+                response = PopupResponse::Action(AppAction::DeleteEntries {
+                    category: self.category.clone(),
+                    entries: self.entries.clone(),
+                });
+            }
+
+            ui.add_space(50.0);
+
+            if ui.button("Cancel").clicked() {
+                response = PopupResponse::Close;
+            }
+        });
+
+        response
+    }
+}
+
+// A quick head-to-head before a dragged entry jumps more than a few places:
+// the move goes ahead only if the entry wins against the last one it passes.
+pub struct ConfirmMovePopup {
+    category: String,
+    from_index: usize,
+    to_index: usize,
+    moved: (String, egui::TextureHandle),
+    passed: (String, egui::TextureHandle),
+}
+
+impl ConfirmMovePopup {
+    pub fn new(
+        category: String,
+        from_index: usize,
+        to_index: usize,
+        moved: (String, egui::TextureHandle),
+        passed: (String, egui::TextureHandle),
+    ) -> Self {
+        Self {
+            category,
+            from_index,
+            to_index,
+            moved,
+            passed,
+        }
+    }
+}
+
+impl Popup for ConfirmMovePopup {
+    fn title(&self) -> &str {
+        "Which Is Better?"
+    }
+
+    fn show_body(&mut self, ui: &mut egui::Ui) -> PopupResponse {
+        let mut response = PopupResponse::KeepOpen;
+        let moving_up = self.to_index < self.from_index;
+
+        ui.label(format!(
+            "Moving \"{}\" from #{} to #{} puts it {} \"{}\".",
+            self.moved.0,
+            self.from_index + 1,
+            self.to_index + 1,
+            if moving_up { "above" } else { "below" },
+            self.passed.0
+        ));
+        ui.add_space(8.0);
+
+        let mut winner = None;
+        ui.horizontal(|ui| {
+            for (is_moved, (entry, thumbnail)) in [(true, &self.moved), (false, &self.passed)] {
+                ui.vertical(|ui| {
+                    let image = egui::Image::new(thumbnail).max_size(egui::vec2(95.0, 118.0));
+                    if ui.add(egui::ImageButton::new(image)).clicked() {
+                        winner = Some(is_moved);
+                    }
+                    ui.add(egui::Label::new(entry.as_str()).truncate());
+                });
+            }
+        });
+
+        // Moving up needs the moved entry to win, moving down needs it to lose.
+        match winner {
+            Some(moved_won) if moved_won == moving_up => {
+                response = PopupResponse::Action(AppAction::MoveEntry {
+                    category: self.category.clone(),
+                    from_index: self.from_index,
+                    to_index: self.to_index,
+                });
+            }
+            Some(_) => response = PopupResponse::Close,
+            None => {}
+        }
+
+        ui.add_space(8.0);
+        if ui.button("Cancel").clicked() {
+            response = PopupResponse::Close;
+        }
+
+        response
+    }
+}
+
+pub struct UnrecognizedContentPopup {
+    findings: Vec<String>,
+}
+
+impl UnrecognizedContentPopup {
+    pub fn new(findings: Vec<String>) -> Self {
+        Self { findings }
+    }
+}
+
+impl Popup for UnrecognizedContentPopup {
+    fn title(&self) -> &str {
+        "Unrecognized Workbook Content"
+    }
+
+    fn show_body(&mut self, ui: &mut egui::Ui) -> PopupResponse {
+        let mut response = PopupResponse::KeepOpen;
+
+        ui.label("This workbook contains data the app does not manage. Saving any change will:");
+        for finding in &self.findings {
+            ui.label(format!("- {finding}"));
+        }
+
+        ui.add_space(8.0);
+        ui.horizontal(|ui| {
+            if ui.button("Open Anyway").clicked() {
+                response = PopupResponse::Close;
+            }
+
+            ui.add_space(50.0);
+
+            if ui.button("Cancel").clicked() {
+                response = PopupResponse::Action(AppAction::ReturnToSplash);
+            }
+        });
+
+        response
+    }
+}
+
+pub struct MessagePopup {
+    title: String,
+    message: String,
+}
+
+impl MessagePopup {
+    pub fn new(title: String, message: String) -> Self {
+        Self { title, message }
+    }
+}
+
+impl Popup for MessagePopup {
+    fn title(&self) -> &str {
+        &self.title
+    }
+
+    fn show_body(&mut self, ui: &mut egui::Ui) -> PopupResponse {
+        let mut response = PopupResponse::KeepOpen;
+
+        ui.label(&self.message);
+        ui.add_space(8.0);
+        if ui.button("OK").clicked() {
+            response = PopupResponse::Close;
+        }
+
+        response
+    }
+}
+
+// Images picked by a bulk fetch, and entries it found nothing for. Clicking
+// one opens the image picker to replace it.
+pub struct FetchPlaceholdersPopup {
+    count: usize,
+}
+
+impl FetchPlaceholdersPopup {
+    pub fn new(count: usize) -> Self {
+        Self { count }
+    }
+}
+
+impl Popup for FetchPlaceholdersPopup {
+    fn title(&self) -> &str {
+        "Back Online"
+    }
+
+    fn show_body(&mut self, ui: &mut egui::Ui) -> PopupResponse {
+        let mut response = PopupResponse::KeepOpen;
+
+        let entries = if self.count == 1 { "entry" } else { "entries" };
+        ui.label(format!(
+            "{} {entries} still show a title card made while offline. Fetch images for them now?",
+            self.count
+        ));
+        ui.horizontal(|ui| {
+            if ui.button("Fetch").clicked() {
+                response = PopupResponse::Action(AppAction::FetchPlaceholders);
+            }
+
+            ui.add_space(50.0);
+
+            if ui.button("Not Now").clicked() {
+                response = PopupResponse::Close;
+            }
+        });
+
+        response
+    }
+}
+
+pub struct BulkFetchReviewPopup {
+    fetched: Vec<(String, String, egui::TextureHandle)>,
+    failed: Vec<(String, String, String)>,
+}
+
+impl BulkFetchReviewPopup {
+    pub fn new(
+        fetched: Vec<(String, String, egui::TextureHandle)>,
+        failed: Vec<(String, String, String)>,
+    ) -> Self {
+        Self { fetched, failed }
+    }
+}
+
+impl Popup for BulkFetchReviewPopup {
+    fn title(&self) -> &str {
+        "Review Fetched Images"
+    }
+
+    fn show_body(&mut self, ui: &mut egui::Ui) -> PopupResponse {
+        let mut response = PopupResponse::KeepOpen;
+        let replace = |category: &str, entry: &str| {
+            PopupResponse::Action(AppAction::RefreshImage {
+                category: category.to_string(),
+                entry: entry.to_string(),
+            })
+        };
+
+        egui::ScrollArea::vertical()
+            .max_height(480.0)
+            .show(ui, |ui| {
+                egui::Grid::new("bulk-fetch-review")
+                    .num_columns(5)
+                    .spacing([8.0, 8.0])
+                    .show(ui, |ui| {
+                        for (index, (category, entry, thumbnail)) in self.fetched.iter().enumerate()
+                        {
+                            ui.vertical(|ui| {
+                                let image =
+                                    egui::Image::new(thumbnail).max_size(egui::vec2(95.0, 118.0));
+                                if ui
+                                    .add(egui::ImageButton::new(image))
+                                    .on_hover_text("Replace")
+                                    .clicked()
+                                {
+                                    response = replace(category, entry);
+                                }
+                                ui.add(egui::Label::new(entry.as_str()).truncate());
+                            });
+
+                            if (index + 1) % 5 == 0 {
+                                ui.end_row();
+                            }
+                        }
+                    });
+
+                if !self.failed.is_empty() {
+                    ui.add_space(8.0);
+                    ui.label("Nothing found for:");
+                    for (category, entry, message) in &self.failed {
+                        ui.horizontal(|ui| {
+                            if ui.button("Choose").clicked() {
+                                response = replace(category, entry);
+                            }
+                            ui.label(format!("{entry} ({category})"))
+                                .on_hover_text(message);
+                        });
+                    }
+                }
+            });
+
+        ui.add_space(8.0);
+        if ui.button("Done").clicked() {
+            response = PopupResponse::Close;
+        }
+
+        response
+    }
+}
+
+pub struct RestoreBackupPopup {
+    spreadsheet: PathBuf,
+    backups: Vec<Backup>,
+}
+
+impl RestoreBackupPopup {
+    pub fn new(spreadsheet: PathBuf, backups: Vec<Backup>) -> Self {
+        Self {
+            spreadsheet,
+            backups,
+        }
+    }
+}
+
+impl Popup for RestoreBackupPopup {
+    fn title(&self) -> &str {
+        "Restore From Backup"
+    }
+
+    fn show_body(&mut self, ui: &mut egui::Ui) -> PopupResponse {
+        let mut response = PopupResponse::KeepOpen;
+
+        ui.label(format!(
+            "Choose a backup to replace {}. The current version is backed up first.",
+            self.spreadsheet.display()
+        ));
+        ui.add_space(8.0);
+
+        egui::ScrollArea::vertical()
+            .max_height(300.0)
+            .show(ui, |ui| {
+                for backup in &self.backups {
+                    ui.horizontal(|ui| {
+                        ui.label(backup.created.format("%Y-%m-%d %H:%M:%S").to_string());
+                        if ui.button("Restore").clicked() {
+                            response = PopupResponse::Action(AppAction::RestoreBackup {
+                                spreadsheet: self.spreadsheet.clone(),
+                                backup: backup.path.clone(),
+                            });
+                        }
+                    });
+                }
+            });
+
+        ui.add_space(8.0);
+        if ui.button("Cancel").clicked() {
+            response = PopupResponse::Close;
+        }
+
+        response
+    }
+}
+
+pub struct ExternalChangePopup {
+    spreadsheet: PathBuf,
+}
+
+impl ExternalChangePopup {
+    pub fn new(spreadsheet: PathBuf) -> Self {
+        Self { spreadsheet }
+    }
+}
+
+impl Popup for ExternalChangePopup {
+    fn title(&self) -> &str {
+        "Spreadsheet Changed On Disk"
+    }
+
+    fn show_body(&mut self, ui: &mut egui::Ui) -> PopupResponse {
+        let mut response = PopupResponse::KeepOpen;
+
+        ui.label(format!(
+            "{} was changed by another program since it was opened.",
+            self.spreadsheet.display()
+        ));
+        ui.vertical(|ui| {
+            if ui.button("Reload And Discard My Changes").clicked() {
+                response = PopupResponse::Action(AppAction::ReloadDocument);
+            }
+
+            if ui.button("Overwrite Their Changes").clicked() {
+                response = PopupResponse::Action(AppAction::OverwriteDocument);
+            }
+
+            if ui.button("Merge Both").clicked() {
+                response = PopupResponse::Action(AppAction::MergeDocument);
+            }
+        });
+
+        response
+    }
+}
+
 pub fn show_modal(ctx: &egui::Context, popup: &mut dyn Popup) -> PopupResponse {
     egui::Area::new(egui::Id::new("Blocking Overlay"))
         .anchor(egui::Align2::LEFT_TOP, egui::Vec2::ZERO)
@@ -1,9 +1,13 @@
 use eframe::egui;
 use egui::{vec2, Align, FontId, Image, ImageButton};
 use rand::Rng;
+use std::ops::Range;
 
 use crate::{app::AppAction, image_store::ImageStore};
 
+// How many of the likeliest pivots to preload for each possible answer.
+const PREFETCH_PER_BRANCH: usize = 3;
+
 #[derive(Clone, Debug)]
 pub enum RankingSource {
     NewEntry,
@@ -107,14 +111,23 @@ impl RankingScreen {
 
         let left_texture = images.get_entry_texture(&entry, &category, ctx);
         let right_texture = images.get_entry_texture(&opponent, &category, ctx);
+        images.prefetch(
+            &category,
+            self.likely_next_pivots()
+                .into_iter()
+                .map(|index| self.entries[index].as_str()),
+            ctx,
+        );
 
+        // Wide images shrink so both fit side by side.
+        let max_image_width = (ui.available_width() - 4.0 * ui.spacing().item_spacing.x) / 2.0;
         ui.horizontal(|ui| {
             let mut entry_won = false;
             let mut opponent_won = false;
 
             ui.vertical(|ui| {
-                let image = Image::new(&left_texture);
-                let width = image.size().unwrap().x;
+                let image = Image::new(&left_texture).max_width(max_image_width);
+                let width = left_texture.size_vec2().x.min(max_image_width);
 
                 if ui.add(ImageButton::new(image)).clicked() {
                     entry_won = true;
@@ -132,8 +145,8 @@ impl RankingScreen {
             });
 
             ui.vertical(|ui| {
-                let image = Image::new(&right_texture);
-                let width = image.size().unwrap().x;
+                let image = Image::new(&right_texture).max_width(max_image_width);
+                let width = right_texture.size_vec2().x.min(max_image_width);
 
                 if ui.add(ImageButton::new(image)).clicked() {
                     opponent_won = true;
@@ -189,16 +202,43 @@ impl RankingScreen {
     }
 
     fn choose_binary_pivot(lower_bound: usize, upper_bound: usize) -> usize {
+        rand::thread_rng().gen_range(Self::binary_pivot_range(lower_bound, upper_bound))
+    }
+
+    fn binary_pivot_range(lower_bound: usize, upper_bound: usize) -> Range<usize> {
         let range_len = upper_bound - lower_bound;
         if range_len <= 2 {
-            return rand::thread_rng().gen_range(lower_bound..upper_bound);
+            return lower_bound..upper_bound;
         }
 
         let midpoint = lower_bound + range_len / 2;
         let jitter = (range_len / 4).max(1);
         let start = midpoint.saturating_sub(jitter).max(lower_bound);
         let end = (midpoint + jitter + 1).min(upper_bound);
-        rand::thread_rng().gen_range(start..end)
+        start..end
+    }
+
+    // Pivots closest to the middle of the range left after either answer.
+    fn likely_next_pivots(&self) -> Vec<usize> {
+        if self.binary_index.is_some() {
+            return Vec::new();
+        }
+
+        [
+            (self.lower_bound, self.pivot_index),
+            (self.pivot_index + 1, self.upper_bound),
+        ]
+        .into_iter()
+        .filter(|(lower_bound, upper_bound)| lower_bound < upper_bound)
+        .flat_map(|(lower_bound, upper_bound)| {
+            let midpoint = lower_bound + (upper_bound - lower_bound) / 2;
+            let mut candidates: Vec<usize> =
+                Self::binary_pivot_range(lower_bound, upper_bound).collect();
+            candidates.sort_by_key(|index| index.abs_diff(midpoint));
+            candidates.truncate(PREFETCH_PER_BRANCH);
+            candidates
+        })
+        .collect()
     }
 
     fn final_index(&self) -> usize {
@@ -323,4 +363,18 @@ mod tests {
 
         assert_eq!(ranking.final_index(), 3);
     }
+
+    #[test]
+    fn likely_next_pivots_cover_both_answers() {
+        let mut ranking = ranking_with_comparisons(0, Vec::new());
+        ranking.binary_index = None;
+        ranking.lower_bound = 0;
+        ranking.upper_bound = 5;
+        ranking.pivot_index = 2;
+
+        let mut pivots = ranking.likely_next_pivots();
+        pivots.sort();
+
+        assert_eq!(pivots, vec![0, 1, 3, 4]);
+    }
 }
@@ -0,0 +1,247 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    image_provider::ImageProviderConfig, image_search::Image, image_store::ImageGeometry,
+    stable_hash,
+};
+
+pub const DEFAULT_SEARCH_CACHE_MB: u64 = 200;
+pub const DEFAULT_SEARCH_CACHE_HOURS: u64 = 72;
+const CACHE_DIRECTORY: &str = "media_rating";
+const INDEX_FILE: &str = "index.json";
+
+#[derive(Serialize, Deserialize)]
+struct CacheIndex {
+    query: String,
+    // Seconds since the Unix epoch.
+    created: u64,
+    results: Vec<CachedResult>,
+    // Entries from before this was kept assume there is more.
+    #[serde(default = "more_by_default")]
+    more: bool,
+    #[serde(default)]
+    leftover: Vec<Image>,
+}
+
+fn more_by_default() -> bool {
+    true
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedResult {
+    source: String,
+    file: String,
+}
+
+// One searched page: the raw bytes of each result with where it came from,
+// whether the provider had another page and the candidates not downloaded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CachedSearch {
+    pub results: Vec<(String, Vec<u8>)>,
+    pub more: bool,
+    pub leftover: Vec<Image>,
+}
+
+// Downloaded search results kept on disk, one directory per provider, query
+// and image size. Entries older than the TTL are searched again but still
+// used when the search fails, so queries seen before work offline.
+#[derive(Clone, Debug)]
+pub struct SearchCache {
+    directory: PathBuf,
+    ttl: Duration,
+    max_bytes: u64,
+}
+
+impl SearchCache {
+    pub fn new(directory: impl Into<PathBuf>, ttl: Duration, max_bytes: u64) -> Self {
+        Self {
+            directory: directory.into(),
+            ttl,
+            max_bytes,
+        }
+    }
+
+    // The cache in the platform cache directory.
+    pub fn in_user_cache(hours: u64, megabytes: u64) -> Option<Self> {
+        dirs::cache_dir().map(|directory| {
+            Self::new(
+                directory.join(CACHE_DIRECTORY).join("search"),
+                Duration::from_secs(hours * 60 * 60),
+                megabytes * 1024 * 1024,
+            )
+        })
+    }
+
+    pub fn key(
+        provider: &ImageProviderConfig,
+        query: &str,
+        geometry: ImageGeometry,
+        page: usize,
+    ) -> String {
+        let provider = serde_json::to_string(provider).unwrap_or_default();
+        let hash = stable_hash::fnv1a_64(&[
+            &provider,
+            &query.trim().to_lowercase(),
+            &format!("{}x{} {page}", geometry.width, geometry.height),
+        ]);
+        format!("{hash:016x}")
+    }
+
+    // Stale entries are only returned when `allow_stale` is set.
+    pub fn get(&self, key: &str, allow_stale: bool) -> Option<CachedSearch> {
+        let entry = self.directory.join(key);
+        let index: CacheIndex =
+            serde_json::from_str(&fs::read_to_string(entry.join(INDEX_FILE)).ok()?).ok()?;
+        if !allow_stale && now().saturating_sub(index.created) > self.ttl.as_secs() {
+            return None;
+        }
+
+        let results: Vec<(String, Vec<u8>)> = index
+            .results
+            .into_iter()
+            .filter_map(|result| {
+                let bytes = fs::read(entry.join(&result.file)).ok()?;
+                Some((result.source, bytes))
+            })
+            .collect();
+        (!results.is_empty()).then_some(CachedSearch {
+            results,
+            more: index.more,
+            leftover: index.leftover,
+        })
+    }
+
+    pub fn put(&self, key: &str, query: &str, search: &CachedSearch) -> io::Result<()> {
+        let entry = self.directory.join(key);
+        if entry.exists() {
+            fs::remove_dir_all(&entry)?;
+        }
+        fs::create_dir_all(&entry)?;
+
+        let mut index = CacheIndex {
+            query: query.to_string(),
+            created: now(),
+            results: Vec::new(),
+            more: search.more,
+            leftover: search.leftover.clone(),
+        };
+        for (number, (source, bytes)) in search.results.iter().enumerate() {
+            let file = format!("{number}.img");
+            fs::write(entry.join(&file), bytes)?;
+            index.results.push(CachedResult {
+                source: source.clone(),
+                file,
+            });
+        }
+        let json = serde_json::to_string_pretty(&index).map_err(io::Error::other)?;
+        fs::write(entry.join(INDEX_FILE), json)?;
+
+        self.prune(&entry)
+    }
+
+    // Oldest entries go first once the cache is over its size cap. The entry
+    // just written is kept even if it alone is over the cap.
+    fn prune(&self, keep: &Path) -> io::Result<()> {
+        let mut entries = Vec::new();
+        let mut total = 0;
+        for entry in fs::read_dir(&self.directory)? {
+            let path = entry?.path();
+            let created = fs::read_to_string(path.join(INDEX_FILE))
+                .ok()
+                .and_then(|json| serde_json::from_str::<CacheIndex>(&json).ok())
+                .map_or(0, |index| index.created);
+            let size = directory_size(&path);
+            total += size;
+            entries.push((created, size, path));
+        }
+
+        entries.sort_by_key(|(created, _, _)| *created);
+        for (_, size, path) in entries {
+            if total <= self.max_bytes {
+                break;
+            }
+            if path == keep {
+                continue;
+            }
+            fs::remove_dir_all(&path)?;
+            total -= size;
+        }
+        Ok(())
+    }
+}
+
+fn directory_size(directory: &Path) -> u64 {
+    fs::read_dir(directory)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok()?.metadata().ok())
+                .map(|metadata| metadata.len())
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn cache_expires_entries_and_stays_under_its_cap() {
+        let directory = env::temp_dir().join(format!(
+            "media-rating-search-cache-test-{}",
+            std::process::id()
+        ));
+        fs::remove_dir_all(&directory).ok();
+        let provider = ImageProviderConfig::default();
+        let dune = SearchCache::key(&provider, "Dune", ImageGeometry::POSTER, 0);
+        assert_eq!(
+            dune,
+            SearchCache::key(&provider, " dune ", ImageGeometry::POSTER, 0)
+        );
+        assert_ne!(
+            dune,
+            SearchCache::key(&provider, "Dune", ImageGeometry::SQUARE, 0)
+        );
+        assert_ne!(
+            dune,
+            SearchCache::key(&provider, "Dune", ImageGeometry::POSTER, 1)
+        );
+
+        let cache = SearchCache::new(&directory, Duration::from_secs(60), 1024);
+        let results = CachedSearch {
+            results: vec![("https://example.com/dune.jpg".to_string(), vec![7; 600])],
+            more: false,
+            leftover: Vec::new(),
+        };
+        cache.put(&dune, "Dune", &results).unwrap();
+        assert_eq!(cache.get(&dune, false), Some(results.clone()));
+
+        // A second entry pushes the cache over its cap, so the older one goes.
+        cache.put("second", "Alien", &results).unwrap();
+        assert_eq!(cache.get(&dune, true), None);
+        assert!(cache.get("second", false).is_some());
+
+        let expired = SearchCache::new(&directory, Duration::ZERO, 1024);
+        let index_path = directory.join("second").join(INDEX_FILE);
+        let mut index: CacheIndex =
+            serde_json::from_str(&fs::read_to_string(&index_path).unwrap()).unwrap();
+        index.created -= 10;
+        fs::write(&index_path, serde_json::to_string(&index).unwrap()).unwrap();
+        assert_eq!(expired.get("second", false), None);
+        assert!(expired.get("second", true).is_some());
+
+        fs::remove_dir_all(directory).ok();
+    }
+}
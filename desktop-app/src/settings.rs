@@ -0,0 +1,235 @@
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    backups::{self, DEFAULT_BACKUP_COUNT},
+    home_screen::HomeView,
+    image_provider::ImageProviderConfig,
+    image_store::{ImageGeometry, DEFAULT_TEXTURE_BUDGET_MB},
+    metadata::CatalogueConfig,
+    search_cache::{DEFAULT_SEARCH_CACHE_HOURS, DEFAULT_SEARCH_CACHE_MB},
+};
+
+const SETTINGS_DIRECTORY: &str = "media_rating";
+const SETTINGS_FILE: &str = "settings.json";
+const MAX_RECENT_DOCUMENTS: usize = 10;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Theme {
+    #[default]
+    Dark,
+    Light,
+}
+
+impl Theme {
+    pub fn visuals(self) -> egui::Visuals {
+        match self {
+            Theme::Dark => egui::Visuals::dark(),
+            Theme::Light => egui::Visuals::light(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DocumentPreferences {
+    pub last_category: Option<String>,
+    // Keyed by category; missing categories use the default provider.
+    pub image_providers: HashMap<String, ImageProviderConfig>,
+    pub catalogues: HashMap<String, CatalogueConfig>,
+    // Only read, for documents from before image geometries were kept in the
+    // document itself.
+    pub image_geometries: HashMap<String, ImageGeometry>,
+}
+
+// App-wide preferences, stored in the platform config directory.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppSettings {
+    // Most recently opened first.
+    pub recent_documents: Vec<PathBuf>,
+    pub reopen_last_document: bool,
+    pub theme: Theme,
+    pub backup_count: usize,
+    pub texture_budget_mb: usize,
+    // Zero turns the search cache off.
+    pub search_cache_mb: u64,
+    pub search_cache_hours: u64,
+    // Never search for images, even with a connection.
+    pub work_offline: bool,
+    pub home_view: HomeView,
+    // Ask for a head-to-head before a drag moves an entry far.
+    pub confirm_long_moves: bool,
+    pub documents: HashMap<PathBuf, DocumentPreferences>,
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        Self {
+            recent_documents: Vec::new(),
+            reopen_last_document: false,
+            theme: Theme::default(),
+            backup_count: DEFAULT_BACKUP_COUNT,
+            texture_budget_mb: DEFAULT_TEXTURE_BUDGET_MB,
+            search_cache_mb: DEFAULT_SEARCH_CACHE_MB,
+            search_cache_hours: DEFAULT_SEARCH_CACHE_HOURS,
+            work_offline: false,
+            home_view: HomeView::default(),
+            confirm_long_moves: false,
+            documents: HashMap::new(),
+        }
+    }
+}
+
+impl AppSettings {
+    pub fn load() -> Self {
+        settings_path()
+            .and_then(|path| Self::load_from(&path).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) {
+        let Some(path) = settings_path() else {
+            return;
+        };
+
+        if let Err(e) = self.save_to(&path) {
+            eprintln!("Could not save settings: {e}");
+        }
+    }
+
+    fn load_from(path: &Path) -> io::Result<Self> {
+        serde_json::from_slice(&fs::read(path)?).map_err(io::Error::other)
+    }
+
+    fn save_to(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let contents = serde_json::to_vec_pretty(self).map_err(io::Error::other)?;
+        backups::write_atomically(path, &contents)
+    }
+
+    pub fn add_recent_document(&mut self, path: &Path) {
+        self.recent_documents.retain(|existing| existing != path);
+        self.recent_documents.insert(0, path.to_path_buf());
+        self.recent_documents.truncate(MAX_RECENT_DOCUMENTS);
+    }
+
+    // Forgetting a document also forgets its preferences.
+    pub fn remove_recent_document(&mut self, path: &Path) {
+        self.recent_documents.retain(|existing| existing != path);
+        self.documents.remove(path);
+    }
+
+    pub fn last_category(&self, document: &Path) -> Option<&str> {
+        self.documents.get(document)?.last_category.as_deref()
+    }
+
+    // Returns whether anything changed.
+    pub fn set_last_category(&mut self, document: &Path, category: Option<&str>) -> bool {
+        let preferences = self.documents.entry(document.to_path_buf()).or_default();
+        if preferences.last_category.as_deref() == category {
+            return false;
+        }
+
+        preferences.last_category = category.map(str::to_string);
+        true
+    }
+
+    pub fn image_provider(&self, document: &Path, category: &str) -> ImageProviderConfig {
+        self.documents
+            .get(document)
+            .and_then(|preferences| preferences.image_providers.get(category))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn set_image_provider(
+        &mut self,
+        document: &Path,
+        category: &str,
+        provider: ImageProviderConfig,
+    ) {
+        self.documents
+            .entry(document.to_path_buf())
+            .or_default()
+            .image_providers
+            .insert(category.to_string(), provider);
+    }
+
+    pub fn image_geometries(&self, document: &Path) -> HashMap<String, ImageGeometry> {
+        self.documents
+            .get(document)
+            .map(|preferences| preferences.image_geometries.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn catalogue(&self, document: &Path, category: &str) -> CatalogueConfig {
+        self.documents
+            .get(document)
+            .and_then(|preferences| preferences.catalogues.get(category))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn set_catalogue(&mut self, document: &Path, category: &str, catalogue: CatalogueConfig) {
+        self.documents
+            .entry(document.to_path_buf())
+            .or_default()
+            .catalogues
+            .insert(category.to_string(), catalogue);
+    }
+}
+
+fn settings_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|directory| directory.join(SETTINGS_DIRECTORY).join(SETTINGS_FILE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn recent_documents_are_deduplicated_capped_and_persisted() {
+        let path = env::temp_dir()
+            .join(format!("media-rating-settings-test-{}", std::process::id()))
+            .join(SETTINGS_FILE);
+
+        let mut settings = AppSettings::default();
+        for index in 0..12 {
+            settings.add_recent_document(Path::new(&format!("/docs/{index}.xlsx")));
+        }
+        settings.add_recent_document(Path::new("/docs/5.xlsx"));
+        settings.set_last_category(Path::new("/docs/5.xlsx"), Some("Movies:"));
+        let catalogue = CatalogueConfig {
+            api_key: "secret key".to_string(),
+            ..CatalogueConfig::default()
+        };
+        settings.set_catalogue(Path::new("/docs/5.xlsx"), "Movies:", catalogue);
+        settings.save_to(&path).unwrap();
+        assert!(!fs::read_to_string(&path).unwrap().contains("secret key"));
+
+        let mut loaded = AppSettings::load_from(&path).unwrap();
+        assert_eq!(loaded.recent_documents.len(), MAX_RECENT_DOCUMENTS);
+        assert_eq!(loaded.recent_documents[0], Path::new("/docs/5.xlsx"));
+        assert_eq!(loaded.recent_documents[1], Path::new("/docs/11.xlsx"));
+        assert_eq!(
+            loaded.last_category(Path::new("/docs/5.xlsx")),
+            Some("Movies:")
+        );
+
+        loaded.remove_recent_document(Path::new("/docs/5.xlsx"));
+        assert_eq!(loaded.recent_documents[0], Path::new("/docs/11.xlsx"));
+        assert_eq!(loaded.last_category(Path::new("/docs/5.xlsx")), None);
+
+        fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+}
@@ -2,21 +2,26 @@ use eframe::egui;
 use native_dialog::FileDialog;
 use std::path::PathBuf;
 
-use crate::app::AppAction;
+use crate::{
+    app::AppAction,
+    bundle::BUNDLE_EXTENSION,
+    settings::{AppSettings, Theme},
+};
 
 #[derive(Default)]
 pub struct SplashScreen;
 
 impl SplashScreen {
-    pub fn ui(&mut self, ui: &mut egui::Ui) -> Vec<AppAction> {
+    pub fn ui(&mut self, ui: &mut egui::Ui, settings: &mut AppSettings) -> Vec<AppAction> {
         let mut actions = Vec::new();
 
         ui.vertical_centered(|ui| {
-            ui.add_space(200.0);
+            ui.add_space(80.0);
 
             if ui.button("Create New Spreadsheet").clicked() {
                 if let Some(path) = FileDialog::new()
                     .add_filter("Excel file", &["xlsx"])
+                    .add_filter("Media Rating bundle", &[BUNDLE_EXTENSION])
                     .set_filename("Media Ratings.xlsx")
                     .show_save_single_file()
                     .ok()
@@ -26,11 +31,11 @@ impl SplashScreen {
                 }
             }
 
-            ui.add_space(50.0);
+            ui.add_space(20.0);
 
             if ui.button("Open Spreadsheet").clicked() {
                 if let Some(path) = FileDialog::new()
-                    .add_filter("Excel file", &["xlsx"])
+                    .add_filter("Spreadsheet or bundle", &["xlsx", BUNDLE_EXTENSION])
                     .show_open_single_file()
                     .ok()
                     .flatten()
@@ -38,12 +43,132 @@ impl SplashScreen {
                     actions.push(AppAction::OpenSpreadsheet(path_to_buf(path)));
                 }
             }
+
+            ui.add_space(20.0);
+
+            if ui.button("Import Bundle To Folder").clicked() {
+                let bundle = FileDialog::new()
+                    .add_filter("Media Rating bundle", &[BUNDLE_EXTENSION])
+                    .show_open_single_file()
+                    .ok()
+                    .flatten();
+                let directory = bundle
+                    .as_ref()
+                    .and_then(|_| FileDialog::new().show_open_single_dir().ok().flatten());
+                if let (Some(bundle), Some(directory)) = (bundle, directory) {
+                    actions.push(AppAction::ImportBundle {
+                        bundle: path_to_buf(bundle),
+                        directory: path_to_buf(directory),
+                    });
+                }
+            }
+
+            ui.add_space(20.0);
+
+            if ui.button("Restore From Backup").clicked() {
+                if let Some(path) = FileDialog::new()
+                    .add_filter("Spreadsheet or bundle", &["xlsx", BUNDLE_EXTENSION])
+                    .show_open_single_file()
+                    .ok()
+                    .flatten()
+                {
+                    actions.push(AppAction::BrowseBackups(path_to_buf(path)));
+                }
+            }
+
+            if !settings.recent_documents.is_empty() {
+                ui.add_space(40.0);
+                ui.heading("Recent");
+
+                for path in &settings.recent_documents {
+                    let name = path
+                        .file_name()
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .unwrap_or_else(|| path.display().to_string());
+
+                    ui.horizontal(|ui| {
+                        let exists = path.exists();
+                        let open = ui
+                            .add_enabled(exists, egui::Button::new(name))
+                            .on_hover_text(path.display().to_string())
+                            .on_disabled_hover_text(format!("{} is missing", path.display()));
+                        if open.clicked() {
+                            actions.push(AppAction::OpenSpreadsheet(path.clone()));
+                        }
+
+                        if ui.small_button("Forget").clicked() {
+                            actions.push(AppAction::ForgetRecentDocument(path.clone()));
+                        }
+                    });
+                }
+            }
+
+            ui.add_space(40.0);
+
+            let mut changed = false;
+            changed |= ui
+                .checkbox(
+                    &mut settings.reopen_last_document,
+                    "Reopen last document on launch",
+                )
+                .changed();
+            changed |= ui
+                .checkbox(
+                    &mut settings.confirm_long_moves,
+                    "Confirm long drag moves with a comparison",
+                )
+                .changed();
+
+            ui.horizontal(|ui| {
+                ui.label("Backups kept per spreadsheet:");
+                changed |= edit_finished(
+                    &ui.add(egui::DragValue::new(&mut settings.backup_count).range(0..=100)),
+                );
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Image memory budget (MB):");
+                changed |= edit_finished(
+                    &ui.add(egui::DragValue::new(&mut settings.texture_budget_mb).range(16..=4096)),
+                );
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Search cache (MB, 0 to turn off):");
+                changed |= edit_finished(
+                    &ui.add(egui::DragValue::new(&mut settings.search_cache_mb).range(0..=10000)),
+                );
+                ui.label("kept for (hours):");
+                changed |= edit_finished(
+                    &ui.add(egui::DragValue::new(&mut settings.search_cache_hours).range(1..=720)),
+                );
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Theme:");
+                changed |= ui
+                    .selectable_value(&mut settings.theme, Theme::Dark, "Dark")
+                    .changed();
+                changed |= ui
+                    .selectable_value(&mut settings.theme, Theme::Light, "Light")
+                    .changed();
+            });
+
+            if changed {
+                actions.push(AppAction::SaveSettings);
+            }
         });
 
         actions
     }
 }
 
+// Drag values change on every frame of a drag or keystroke, so settings are
+// saved once the edit is over.
+fn edit_finished(response: &egui::Response) -> bool {
+    response.drag_stopped() || response.lost_focus()
+}
+
 fn path_to_buf(path: impl Into<PathBuf>) -> PathBuf {
     path.into()
 }
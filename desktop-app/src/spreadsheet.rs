@@ -1,8 +1,26 @@
-use calamine::{open_workbook, DataType, Reader, Xlsx};
-use rust_xlsxwriter::{Format, Workbook};
-use std::{collections::HashMap, path::Path};
+use calamine::{open_workbook, Data, DataType, ExcelDateTime, Range, Reader, Xlsx};
+use rust_xlsxwriter::{Format, Formula, Workbook, Worksheet};
+use std::{collections::HashMap, fs::File, io::BufReader, path::Path};
 
-use crate::model::Model;
+use crate::{backups, image_store::ImageGeometry, metadata::EntryMetadata, model::Model};
+
+const SORTED_SHEET_NAME: &str = "Sorted";
+// Named so it does not clash with a sheet the user keeps themselves.
+const METADATA_SHEET_NAME: &str = "Media Rating Metadata";
+const METADATA_COLUMNS: [&str; 8] = [
+    "Category", "Entry", "Title", "Year", "Creator", "Poster", "Source", "Id",
+];
+// Settings that belong to a category wherever the workbook goes.
+const CATEGORIES_SHEET_NAME: &str = "Media Rating Categories";
+const CATEGORY_COLUMNS: [&str; 3] = ["Category", "Image Width", "Image Height"];
+
+// A sheet the app does not manage, carried through saves with its values,
+// dates and formulas but not its formatting.
+struct PreservedSheet {
+    name: String,
+    cells: Vec<(u32, u16, Data)>,
+    formulas: HashMap<(u32, u16), String>,
+}
 
 pub fn create_empty(path: &Path) -> Result<(), String> {
     let mut workbook = Workbook::new();
@@ -11,10 +29,7 @@ pub fn create_empty(path: &Path) -> Result<(), String> {
 
 pub fn load(path: &Path) -> Result<Model, String> {
     let mut workbook: Xlsx<_> = open_workbook::<Xlsx<_>, _>(path).map_err(|e| e.to_string())?;
-    let sheet = workbook
-        .worksheet_range_at(0)
-        .ok_or_else(|| "Spreadsheet has no sheets".to_string())?
-        .map_err(|e| e.to_string())?;
+    let sheet = data_sheet(&mut workbook)?;
 
     let (height, width) = sheet.get_size();
     let mut categories = HashMap::new();
@@ -40,13 +55,159 @@ pub fn load(path: &Path) -> Result<Model, String> {
         categories.insert(category_name.to_string(), entries);
     }
 
-    Ok(Model::from_categories(categories))
+    let mut model = Model::from_categories(categories);
+    if workbook
+        .sheet_names()
+        .iter()
+        .any(|name| name == METADATA_SHEET_NAME)
+    {
+        let sheet = workbook
+            .worksheet_range(METADATA_SHEET_NAME)
+            .map_err(|e| e.to_string())?;
+        for (category, entry, metadata) in read_metadata(&sheet) {
+            model.set_entry_metadata(&category, entry, Some(metadata));
+        }
+    }
+    if workbook
+        .sheet_names()
+        .iter()
+        .any(|name| name == CATEGORIES_SHEET_NAME)
+    {
+        let sheet = workbook
+            .worksheet_range(CATEGORIES_SHEET_NAME)
+            .map_err(|e| e.to_string())?;
+        for (category, geometry) in read_category_settings(&sheet) {
+            model.set_image_geometry(&category, geometry);
+        }
+    }
+
+    Ok(model)
+}
+
+// One category per row below the header, in `CATEGORY_COLUMNS` order. Rows
+// without a usable size are skipped.
+fn read_category_settings(sheet: &Range<Data>) -> Vec<(String, ImageGeometry)> {
+    let size = |row: usize, column: usize| {
+        sheet
+            .get_value((row as u32, column as u32))
+            .and_then(|cell| cell.as_f64())
+            .filter(|size| *size >= 1.0)
+            .map(|size| size as u32)
+    };
+
+    (1..sheet.height())
+        .filter_map(|row| {
+            let category = sheet
+                .get_value((row as u32, 0))
+                .and_then(|cell| cell.get_string())?;
+            let geometry = ImageGeometry {
+                width: size(row, 1)?,
+                height: size(row, 2)?,
+            };
+            Some((category.to_string(), geometry))
+        })
+        .collect()
+}
+
+// One record per row below the header, in `METADATA_COLUMNS` order.
+fn read_metadata(sheet: &Range<Data>) -> Vec<(String, String, EntryMetadata)> {
+    let text = |row: usize, column: usize| {
+        sheet
+            .get_value((row as u32, column as u32))
+            .map(|cell| match cell {
+                Data::Int(number) => number.to_string(),
+                Data::Float(number) => number.to_string(),
+                cell => cell.get_string().unwrap_or_default().to_string(),
+            })
+            .filter(|value| !value.is_empty())
+    };
+
+    (1..sheet.height())
+        .filter_map(|row| {
+            let category = text(row, 0)?;
+            let entry = text(row, 1)?;
+            let metadata = EntryMetadata {
+                title: text(row, 2).unwrap_or_else(|| entry.clone()),
+                year: text(row, 3).and_then(|year| year.parse().ok()),
+                creator: text(row, 4),
+                poster_url: text(row, 5),
+                source: text(row, 6).unwrap_or_default(),
+                id: text(row, 7).unwrap_or_default(),
+            };
+            Some((category, entry, metadata))
+        })
+        .collect()
+}
+
+// Describe anything in the workbook that saving would change or drop, so the
+// user can be warned before the first autosave overwrites it.
+pub fn unrecognized_content(path: &Path) -> Result<Vec<String>, String> {
+    let mut workbook: Xlsx<_> = open_workbook::<Xlsx<_>, _>(path).map_err(|e| e.to_string())?;
+    let data_sheet_name = data_sheet_name(&workbook)?;
+    let sheet = data_sheet(&mut workbook)?;
+    let mut findings = Vec::new();
+
+    let (offset_row, offset_column) = sheet.start().unwrap_or((0, 0));
+    let cells: Vec<(u32, u32, &Data)> = sheet
+        .used_cells()
+        .map(|(row, column, value)| {
+            (
+                row as u32 + offset_row,
+                column as u32 + offset_column,
+                value,
+            )
+        })
+        .collect();
+    let category_columns: Vec<u32> = cells
+        .iter()
+        .filter(|(row, _, value)| *row == 0 && value.get_string().is_some())
+        .map(|(_, column, _)| *column)
+        .collect();
+    let stray_cells = cells
+        .iter()
+        .filter(|(_, column, _)| !category_columns.contains(column))
+        .count();
+    let non_text_cells = cells
+        .iter()
+        .filter(|(_, column, value)| {
+            category_columns.contains(column) && value.get_string().is_none()
+        })
+        .count();
+
+    if stray_cells > 0 {
+        findings.push(format!(
+            "{stray_cells} cell(s) in \"{data_sheet_name}\" sit outside any category column and will be removed."
+        ));
+    }
+    if non_text_cells > 0 {
+        findings.push(format!(
+            "{non_text_cells} non-text cell(s) in \"{data_sheet_name}\" will be removed."
+        ));
+    }
+
+    for name in workbook.sheet_names() {
+        if name != data_sheet_name && name != CATEGORIES_SHEET_NAME && name != METADATA_SHEET_NAME {
+            findings.push(format!(
+                "Sheet \"{name}\" will be kept with its values and formulas, but its formatting will be lost."
+            ));
+        }
+    }
+
+    Ok(findings)
 }
 
 pub fn save(path: &Path, model: &Model) -> Result<(), String> {
+    let preserved_sheets = if path.exists() {
+        read_preserved_sheets(path)?
+    } else {
+        Vec::new()
+    };
+
     let mut workbook = Workbook::new();
     let sheet = workbook.add_worksheet();
-    sheet.set_name("Sorted").map_err(|e| e.to_string())?;
+    sheet
+        .set_name(SORTED_SHEET_NAME)
+        .map_err(|e| e.to_string())?;
 
     let colors = [
         rust_xlsxwriter::Color::RGB(0xd8bfd8),
@@ -61,7 +222,7 @@ pub fn save(path: &Path, model: &Model) -> Result<(), String> {
     sheet.set_row_height(0, 30).map_err(|e| e.to_string())?;
 
     let mut categories: Vec<_> = model.categories().collect();
-    categories.sort_by(|(left, _), (right, _)| left.cmp(right));
+    categories.sort_by_key(|(name, _)| *name);
 
     let mut column: u16 = 0;
     for (name, entries) in categories {
@@ -100,7 +261,263 @@ pub fn save(path: &Path, model: &Model) -> Result<(), String> {
         column += 2;
     }
 
-    workbook.save(path).map_err(|e| e.to_string())
+    let mut records: Vec<_> = model.all_metadata().collect();
+    if !records.is_empty() {
+        records.sort_by_key(|(category, entry, _)| (*category, *entry));
+        write_metadata(workbook.add_worksheet(), &records)?;
+    }
+
+    let mut geometries: Vec<_> = model.image_geometries().collect();
+    if !geometries.is_empty() {
+        geometries.sort_by_key(|(category, _)| *category);
+        write_category_settings(workbook.add_worksheet(), &geometries)?;
+    }
+
+    for preserved in preserved_sheets {
+        let sheet = workbook.add_worksheet();
+        sheet.set_name(&preserved.name).map_err(|e| e.to_string())?;
+        for (row, column, value) in &preserved.cells {
+            if !preserved.formulas.contains_key(&(*row, *column)) {
+                write_cell(sheet, *row, *column, value)?;
+            }
+        }
+        let values: HashMap<(u32, u16), &Data> = preserved
+            .cells
+            .iter()
+            .map(|(row, column, value)| ((*row, *column), value))
+            .collect();
+        for ((row, column), formula) in &preserved.formulas {
+            let value = values.get(&(*row, *column)).copied();
+            write_formula(sheet, *row, *column, formula, value)?;
+        }
+    }
+
+    let contents = workbook.save_to_buffer().map_err(|e| e.to_string())?;
+    backups::write_atomically(path, &contents).map_err(|e| e.to_string())
+}
+
+fn write_metadata(
+    sheet: &mut Worksheet,
+    records: &[(&str, &str, &EntryMetadata)],
+) -> Result<(), String> {
+    sheet
+        .set_name(METADATA_SHEET_NAME)
+        .map_err(|e| e.to_string())?;
+
+    let header_format = Format::new().set_bold();
+    for (column, header) in METADATA_COLUMNS.iter().enumerate() {
+        sheet
+            .write_string_with_format(0, column as u16, *header, &header_format)
+            .map_err(|e| e.to_string())?;
+    }
+
+    for (index, (category, entry, metadata)) in records.iter().enumerate() {
+        let row = index as u32 + 1;
+        let year = metadata.year.map(|year| year.to_string());
+        let values = [
+            Some(*category),
+            Some(*entry),
+            Some(metadata.title.as_str()),
+            year.as_deref(),
+            metadata.creator.as_deref(),
+            metadata.poster_url.as_deref(),
+            Some(metadata.source.as_str()),
+            Some(metadata.id.as_str()),
+        ];
+        for (column, value) in values.into_iter().enumerate() {
+            if let Some(value) = value.filter(|value| !value.is_empty()) {
+                sheet
+                    .write_string(row, column as u16, value)
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn write_category_settings(
+    sheet: &mut Worksheet,
+    geometries: &[(&str, ImageGeometry)],
+) -> Result<(), String> {
+    sheet
+        .set_name(CATEGORIES_SHEET_NAME)
+        .map_err(|e| e.to_string())?;
+
+    let header_format = Format::new().set_bold();
+    for (column, header) in CATEGORY_COLUMNS.iter().enumerate() {
+        sheet
+            .write_string_with_format(0, column as u16, *header, &header_format)
+            .map_err(|e| e.to_string())?;
+    }
+
+    for (index, (category, geometry)) in geometries.iter().enumerate() {
+        let row = index as u32 + 1;
+        sheet
+            .write_string(row, 0, *category)
+            .map_err(|e| e.to_string())?;
+        sheet
+            .write_number(row, 1, geometry.width)
+            .map_err(|e| e.to_string())?;
+        sheet
+            .write_number(row, 2, geometry.height)
+            .map_err(|e| e.to_string())?;
+    }
+
+    Ok(())
+}
+
+// The sheet holding category lists: "Sorted" when present, otherwise the first.
+fn data_sheet_name(workbook: &Xlsx<BufReader<File>>) -> Result<String, String> {
+    let names = workbook.sheet_names();
+    if names.iter().any(|name| name == SORTED_SHEET_NAME) {
+        return Ok(SORTED_SHEET_NAME.to_string());
+    }
+
+    names
+        .into_iter()
+        .next()
+        .ok_or_else(|| "Spreadsheet has no sheets".to_string())
+}
+
+fn data_sheet(workbook: &mut Xlsx<BufReader<File>>) -> Result<Range<Data>, String> {
+    let name = data_sheet_name(workbook)?;
+    workbook.worksheet_range(&name).map_err(|e| e.to_string())
+}
+
+fn read_preserved_sheets(path: &Path) -> Result<Vec<PreservedSheet>, String> {
+    let mut workbook: Xlsx<_> = open_workbook::<Xlsx<_>, _>(path)
+        .map_err(|e| format!("Could not read existing workbook to keep its sheets: {e}"))?;
+    let data_sheet_name = data_sheet_name(&workbook)?;
+
+    let mut sheets = Vec::new();
+    for name in workbook.sheet_names() {
+        if name == data_sheet_name
+            || name == SORTED_SHEET_NAME
+            || name == CATEGORIES_SHEET_NAME
+            || name == METADATA_SHEET_NAME
+        {
+            continue;
+        }
+
+        let range = workbook.worksheet_range(&name).map_err(|e| e.to_string())?;
+        let (offset_row, offset_column) = range.start().unwrap_or((0, 0));
+        let cells = range
+            .used_cells()
+            .map(|(row, column, value)| {
+                (
+                    row as u32 + offset_row,
+                    (column as u32 + offset_column) as u16,
+                    value.clone(),
+                )
+            })
+            .collect();
+
+        let formula_range = workbook
+            .worksheet_formula(&name)
+            .map_err(|e| e.to_string())?;
+        let (offset_row, offset_column) = formula_range.start().unwrap_or((0, 0));
+        let formulas = formula_range
+            .used_cells()
+            .filter(|(_, _, formula)| !formula.is_empty())
+            .map(|(row, column, formula)| {
+                (
+                    (
+                        row as u32 + offset_row,
+                        (column as u32 + offset_column) as u16,
+                    ),
+                    formula.clone(),
+                )
+            })
+            .collect();
+        sheets.push(PreservedSheet {
+            name,
+            cells,
+            formulas,
+        });
+    }
+
+    Ok(sheets)
+}
+
+fn write_cell(sheet: &mut Worksheet, row: u32, column: u16, value: &Data) -> Result<(), String> {
+    let result = match value {
+        Data::Int(number) => sheet.write_number(row, column, *number as f64),
+        Data::Float(number) => sheet.write_number(row, column, *number),
+        Data::String(text) | Data::DateTimeIso(text) | Data::DurationIso(text) => {
+            sheet.write_string(row, column, text)
+        }
+        Data::Bool(value) => sheet.write_boolean(row, column, *value),
+        Data::DateTime(date_time) => {
+            let (serial, format) = date_time_cell(date_time);
+            sheet.write_number_with_format(row, column, serial, &format)
+        }
+        Data::Error(error) => sheet.write_string(row, column, error.to_string()),
+        Data::Empty => return Ok(()),
+    };
+
+    result.map(|_| ()).map_err(|e| e.to_string())
+}
+
+// The formula keeps its last result, so the value shows before Excel
+// calculates it again.
+fn write_formula(
+    sheet: &mut Worksheet,
+    row: u32,
+    column: u16,
+    formula: &str,
+    value: Option<&Data>,
+) -> Result<(), String> {
+    let result = match value {
+        Some(Data::DateTime(date_time)) => {
+            let (serial, format) = date_time_cell(date_time);
+            let formula = Formula::new(formula).set_result(serial.to_string());
+            sheet.write_formula_with_format(row, column, formula, &format)
+        }
+        Some(value) => sheet.write_formula(
+            row,
+            column,
+            Formula::new(formula).set_result(value.to_string()),
+        ),
+        None => sheet.write_formula(row, column, formula),
+    };
+
+    result.map(|_| ()).map_err(|e| e.to_string())
+}
+
+// The serial number of a date, time or duration in a workbook counting from
+// 1900, with a number format that shows it as one.
+fn date_time_cell(date_time: &ExcelDateTime) -> (f64, Format) {
+    if date_time.is_duration() {
+        return (
+            date_time.as_f64(),
+            Format::new().set_num_format("[h]:mm:ss"),
+        );
+    }
+
+    // Workbooks counting from 1904 store other numbers for the same date.
+    let serial = date_time
+        .as_datetime()
+        .map_or(date_time.as_f64(), |date_time| {
+            let epoch = chrono::NaiveDate::from_ymd_opt(1899, 12, 30)
+                .unwrap_or_default()
+                .and_time(chrono::NaiveTime::MIN);
+            let days = (date_time - epoch).num_milliseconds() as f64 / 86_400_000.0;
+            // Excel counts 1900 as a leap year.
+            if days < 61.0 {
+                days - 1.0
+            } else {
+                days
+            }
+        });
+    let format = if serial < 1.0 {
+        "hh:mm:ss"
+    } else if serial.fract() == 0.0 {
+        "yyyy-mm-dd"
+    } else {
+        "yyyy-mm-dd hh:mm:ss"
+    };
+    (serial, Format::new().set_num_format(format))
 }
 
 #[cfg(test)]
@@ -130,4 +547,92 @@ mod tests {
 
         fs::remove_file(path).ok();
     }
+
+    #[test]
+    fn save_and_load_round_trips_entry_metadata() {
+        let path = env::temp_dir().join(format!(
+            "media-rating-spreadsheet-metadata-test-{}.xlsx",
+            std::process::id()
+        ));
+
+        let mut model = Model::default();
+        model.create_category("Books:".to_string());
+        model.insert_entry_at("Books:", "Dune".to_string(), 0);
+        model.insert_entry_at("Books:", "Emma".to_string(), 1);
+        let metadata = EntryMetadata {
+            title: "Dune".to_string(),
+            year: Some(1965),
+            creator: Some("Frank Herbert".to_string()),
+            poster_url: None,
+            source: "Open Library".to_string(),
+            id: "/works/OL893415W".to_string(),
+        };
+        model.set_entry_metadata("Books:", "Dune".to_string(), Some(metadata.clone()));
+        model.set_image_geometry("Books:", ImageGeometry::SQUARE);
+
+        save(&path, &model).unwrap();
+        let loaded = load(&path).unwrap();
+
+        assert_eq!(loaded.entry_metadata("Books:", "Dune"), Some(&metadata));
+        assert_eq!(loaded.image_geometry("Books:"), Some(ImageGeometry::SQUARE));
+        assert_eq!(loaded, model);
+        assert_eq!(loaded.entry_metadata("Books:", "Emma"), None);
+        assert!(unrecognized_content(&path).unwrap().is_empty());
+
+        fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn save_keeps_cell_values_of_other_sheets() {
+        let path = env::temp_dir().join(format!(
+            "media-rating-spreadsheet-extra-sheet-test-{}.xlsx",
+            std::process::id()
+        ));
+
+        let mut workbook = Workbook::new();
+        let sorted = workbook.add_worksheet();
+        sorted.set_name(SORTED_SHEET_NAME).unwrap();
+        sorted.write_string(0, 0, "Movies:").unwrap();
+        sorted.write_string(1, 0, "Arrival").unwrap();
+        let notes = workbook.add_worksheet();
+        notes.set_name("Notes").unwrap();
+        notes.write_string(2, 1, "Watch again").unwrap();
+        notes.write_number(3, 1, 4.5).unwrap();
+        let date = Format::new().set_num_format("dd/mm/yyyy");
+        notes
+            .write_number_with_format(4, 1, 45123.0, &date)
+            .unwrap();
+        notes
+            .write_formula(5, 1, Formula::new("=B4*2").set_result("9"))
+            .unwrap();
+        workbook.save(&path).unwrap();
+
+        assert_eq!(unrecognized_content(&path).unwrap().len(), 1);
+
+        let mut model = load(&path).unwrap();
+        model.insert_entry_at("Movies:", "Alien".to_string(), 1);
+        save(&path, &model).unwrap();
+
+        let mut reopened: Xlsx<_> = open_workbook(&path).unwrap();
+        assert_eq!(reopened.sheet_names(), vec!["Sorted", "Notes"]);
+        let notes = reopened.worksheet_range("Notes").unwrap();
+        assert_eq!(
+            notes.get_value((2, 1)),
+            Some(&Data::String("Watch again".to_string()))
+        );
+        assert_eq!(notes.get_value((3, 1)), Some(&Data::Float(4.5)));
+        match notes.get_value((4, 1)) {
+            Some(Data::DateTime(date_time)) => assert_eq!(date_time.as_f64(), 45123.0),
+            other => panic!("expected a date, found {other:?}"),
+        }
+        assert_eq!(notes.get_value((5, 1)), Some(&Data::Float(9.0)));
+        let formulas = reopened.worksheet_formula("Notes").unwrap();
+        assert_eq!(formulas.get_value((5, 1)).map(String::as_str), Some("B4*2"));
+        assert_eq!(
+            load(&path).unwrap().get_category_entries("Movies:"),
+            ["Arrival".to_string(), "Alien".to_string()].as_slice()
+        );
+
+        fs::remove_file(path).ok();
+    }
 }
@@ -0,0 +1,102 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+type Handler = dyn Fn(&str) -> Option<(&'static str, Vec<u8>)> + Send + Sync;
+
+// A minimal HTTP server on a local port for tests that would otherwise reach
+// real web services. The handler maps a request path to a content type and
+// body, or `None` for a 404.
+pub struct TestServer {
+    address: String,
+    requests: Arc<Mutex<Vec<String>>>,
+    stopped: Arc<AtomicBool>,
+}
+
+impl TestServer {
+    pub fn start(
+        handler: impl Fn(&str) -> Option<(&'static str, Vec<u8>)> + Send + Sync + 'static,
+    ) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let stopped = Arc::new(AtomicBool::new(false));
+
+        let handler: Arc<Handler> = Arc::new(handler);
+        let thread_requests = Arc::clone(&requests);
+        let thread_stopped = Arc::clone(&stopped);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                if thread_stopped.load(Ordering::SeqCst) {
+                    return;
+                }
+                let Ok(stream) = stream else {
+                    continue;
+                };
+                let handler = Arc::clone(&handler);
+                let requests = Arc::clone(&thread_requests);
+                thread::spawn(move || respond(stream, handler.as_ref(), &requests));
+            }
+        });
+
+        Self {
+            address,
+            requests,
+            stopped,
+        }
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}", self.address)
+    }
+
+    // Paths requested so far, in order.
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        // Wake the accept loop so it sees the flag.
+        TcpStream::connect(&self.address).ok();
+    }
+}
+
+fn respond(stream: TcpStream, handler: &Handler, requests: &Mutex<Vec<String>>) {
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).is_err() {
+        return;
+    }
+    let mut header = String::new();
+    while reader.read_line(&mut header).is_ok_and(|read| read > 2) {
+        header.clear();
+    }
+
+    let path = request_line
+        .split_whitespace()
+        .nth(1)
+        .unwrap_or_default()
+        .to_string();
+    requests.lock().unwrap().push(path.clone());
+
+    let (status, content_type, body) = match handler(&path) {
+        Some((content_type, body)) => ("200 OK", content_type, body),
+        None => ("404 Not Found", "text/plain", b"not found".to_vec()),
+    };
+    let mut stream = &stream;
+    let head = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).ok();
+    stream.write_all(&body).ok();
+}
@@ -0,0 +1,123 @@
+use eframe::egui;
+use egui::ColorImage;
+use std::{
+    collections::VecDeque,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Condvar, Mutex,
+    },
+    thread,
+};
+
+const MAX_WORKERS: usize = 4;
+
+// A decoded image, with the perceptual hash of the file it was read from.
+pub struct LoadedImage {
+    pub image: ColorImage,
+    pub hash: Option<u64>,
+}
+
+pub type LoadJob = Box<dyn FnOnce() -> LoadedImage + Send>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Priority {
+    // Needed for the current frame.
+    Visible,
+    // Likely needed soon; loaded once visible work is done.
+    Prefetch,
+}
+
+#[derive(Default)]
+struct Queue {
+    jobs: VecDeque<(String, LoadJob)>,
+    closed: bool,
+}
+
+// A small pool of worker threads decoding images off the UI thread. Finished
+// images are handed back by key and the UI is asked to repaint.
+pub struct TextureLoader {
+    queue: Arc<(Mutex<Queue>, Condvar)>,
+    results: Receiver<(String, LoadedImage)>,
+}
+
+impl TextureLoader {
+    pub fn new(ctx: &egui::Context) -> Self {
+        let queue = Arc::new((Mutex::new(Queue::default()), Condvar::new()));
+        let (sender, results) = mpsc::channel();
+
+        let workers = thread::available_parallelism()
+            .map_or(1, |count| count.get())
+            .clamp(1, MAX_WORKERS);
+        for _ in 0..workers {
+            let queue = Arc::clone(&queue);
+            let sender = sender.clone();
+            let ctx = ctx.clone();
+            thread::spawn(move || run_worker(&queue, &sender, &ctx));
+        }
+
+        Self { queue, results }
+    }
+
+    pub fn request(&self, key: String, priority: Priority, job: LoadJob) {
+        let (lock, condvar) = &*self.queue;
+        let mut queue = lock.lock().unwrap();
+        match priority {
+            Priority::Visible => queue.jobs.push_front((key, job)),
+            Priority::Prefetch => queue.jobs.push_back((key, job)),
+        }
+        condvar.notify_one();
+    }
+
+    // Move an already queued job to the front of the queue.
+    pub fn prioritize(&self, key: &str) {
+        let (lock, _) = &*self.queue;
+        let mut queue = lock.lock().unwrap();
+        if let Some(position) = queue.jobs.iter().position(|(queued, _)| queued == key) {
+            if let Some(job) = queue.jobs.remove(position) {
+                queue.jobs.push_front(job);
+            }
+        }
+    }
+
+    pub fn finished(&self) -> impl Iterator<Item = (String, LoadedImage)> + '_ {
+        self.results.try_iter()
+    }
+}
+
+impl Drop for TextureLoader {
+    fn drop(&mut self) {
+        let (lock, condvar) = &*self.queue;
+        if let Ok(mut queue) = lock.lock() {
+            queue.closed = true;
+            queue.jobs.clear();
+        }
+        condvar.notify_all();
+    }
+}
+
+fn run_worker(
+    queue: &(Mutex<Queue>, Condvar),
+    sender: &Sender<(String, LoadedImage)>,
+    ctx: &egui::Context,
+) {
+    let (lock, condvar) = queue;
+    loop {
+        let (key, job) = {
+            let mut queue = lock.lock().unwrap();
+            loop {
+                if queue.closed {
+                    return;
+                }
+                if let Some(next) = queue.jobs.pop_front() {
+                    break next;
+                }
+                queue = condvar.wait(queue).unwrap();
+            }
+        };
+
+        if sender.send((key, job())).is_err() {
+            return;
+        }
+        ctx.request_repaint();
+    }
+}
@@ -0,0 +1,192 @@
+use ab_glyph::{point, Font, FontArc, PxScale, ScaleFont};
+use eframe::egui;
+use image::{DynamicImage, Rgba, RgbaImage};
+use std::sync::OnceLock;
+
+use crate::{image_store::ImageGeometry, stable_hash};
+
+// One of the fonts egui ships with, so cards need no font of their own.
+const CARD_FONT: &str = "Ubuntu-Light";
+const MIN_TITLE_SIZE: f32 = 10.0;
+
+// A stand-in image for an entry: its title on a card whose colour comes from
+// the title and category, so entries without a poster still look apart.
+pub fn render(title: &str, category: &str, geometry: ImageGeometry) -> DynamicImage {
+    let [width, height] = geometry.size();
+    let [red, green, blue] = card_colour(title, category);
+    let mut card = RgbaImage::from_pixel(width, height, Rgba([red, green, blue, 255]));
+
+    // A darker band along the bottom for the category.
+    let band_top = height - height / 8;
+    for (_, y, pixel) in card.enumerate_pixels_mut() {
+        if y >= band_top {
+            for channel in &mut pixel.0[..3] {
+                *channel = (f32::from(*channel) * 0.7) as u8;
+            }
+        }
+    }
+
+    let Some(font) = card_font() else {
+        return DynamicImage::ImageRgba8(card);
+    };
+    let margin = width as f32 / 10.0;
+    let max_width = width as f32 - 2.0 * margin;
+
+    // Shrink the title until it fits in the upper part of the card.
+    let mut size = height as f32 / 8.0;
+    let lines = loop {
+        let lines = wrap(font, PxScale::from(size), title, max_width);
+        let fits = lines.len() as f32 * size * 1.2 <= band_top as f32 - 2.0 * margin
+            && lines
+                .iter()
+                .all(|line| line_width(font, PxScale::from(size), line) <= max_width);
+        if fits || size <= MIN_TITLE_SIZE {
+            break lines;
+        }
+        size *= 0.9;
+    };
+
+    let scale = PxScale::from(size);
+    let line_height = size * 1.2;
+    let mut baseline =
+        (band_top as f32 - lines.len() as f32 * line_height) / 2.0 + font.as_scaled(scale).ascent();
+    for line in &lines {
+        let x = (width as f32 - line_width(font, scale, line)) / 2.0;
+        draw_line(&mut card, font, scale, line, point(x, baseline), 1.0);
+        baseline += line_height;
+    }
+
+    let category = category.trim().trim_end_matches(':').trim();
+    let category_scale = PxScale::from((height / 8) as f32 * 0.5);
+    let x = (width as f32 - line_width(font, category_scale, category)) / 2.0;
+    let y = band_top as f32 + ((height - band_top) as f32 + category_scale.y * 0.6) / 2.0;
+    draw_line(&mut card, font, category_scale, category, point(x, y), 0.8);
+
+    DynamicImage::ImageRgba8(card)
+}
+
+pub fn card_colour(title: &str, category: &str) -> [u8; 3] {
+    let hash = stable_hash::fnv1a_32(&[title, category]);
+    let hue = (hash % 360) as f32 / 360.0;
+    egui::ecolor::Hsva::new(hue, 0.45, 0.55, 1.0).to_srgb()
+}
+
+fn card_font() -> Option<&'static FontArc> {
+    static FONT: OnceLock<Option<FontArc>> = OnceLock::new();
+    FONT.get_or_init(|| {
+        let data = egui::FontDefinitions::default()
+            .font_data
+            .remove(CARD_FONT)?;
+        FontArc::try_from_vec(data.font.into_owned()).ok()
+    })
+    .as_ref()
+}
+
+// Greedy word wrap; a word wider than the card gets a line of its own.
+fn wrap(font: &FontArc, scale: PxScale, text: &str, max_width: f32) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for word in text.split_whitespace() {
+        match lines.last_mut() {
+            Some(line) if line_width(font, scale, &format!("{line} {word}")) <= max_width => {
+                line.push(' ');
+                line.push_str(word);
+            }
+            _ => lines.push(word.to_string()),
+        }
+    }
+    lines
+}
+
+fn line_width(font: &FontArc, scale: PxScale, text: &str) -> f32 {
+    let font = font.as_scaled(scale);
+    let mut width = 0.0;
+    let mut previous = None;
+    for c in text.chars() {
+        let glyph = font.glyph_id(c);
+        if let Some(previous) = previous {
+            width += font.kern(previous, glyph);
+        }
+        width += font.h_advance(glyph);
+        previous = Some(glyph);
+    }
+    width
+}
+
+// White text starting at `origin` on its baseline.
+fn draw_line(
+    card: &mut RgbaImage,
+    font: &FontArc,
+    scale: PxScale,
+    text: &str,
+    origin: ab_glyph::Point,
+    opacity: f32,
+) {
+    let scaled = font.as_scaled(scale);
+    let mut caret = origin.x;
+    let mut previous = None;
+    for c in text.chars() {
+        let id = scaled.glyph_id(c);
+        if let Some(previous) = previous {
+            caret += scaled.kern(previous, id);
+        }
+        let glyph = id.with_scale_and_position(scale, point(caret, origin.y));
+        caret += scaled.h_advance(id);
+        previous = Some(id);
+
+        let Some(outline) = font.outline_glyph(glyph) else {
+            continue;
+        };
+        let bounds = outline.px_bounds();
+        outline.draw(|x, y, coverage| {
+            let x = bounds.min.x as i64 + i64::from(x);
+            let y = bounds.min.y as i64 + i64::from(y);
+            if x < 0 || y < 0 || x >= i64::from(card.width()) || y >= i64::from(card.height()) {
+                return;
+            }
+            let pixel = card.get_pixel_mut(x as u32, y as u32);
+            let alpha = coverage.clamp(0.0, 1.0) * opacity;
+            for channel in &mut pixel.0[..3] {
+                *channel = (f32::from(*channel) * (1.0 - alpha) + 255.0 * alpha) as u8;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cards_are_stable_and_differ_between_entries() {
+        assert_eq!(
+            card_colour("Dune", "Movies:"),
+            card_colour("Dune", "Movies:")
+        );
+        assert_ne!(
+            card_colour("Dune", "Movies:"),
+            card_colour("Alien", "Movies:")
+        );
+
+        let card = render("Dune: Part Two", "Movies:", ImageGeometry::POSTER).to_rgba8();
+        assert_eq!(card.dimensions(), (380, 475));
+        let [red, green, blue] = card_colour("Dune: Part Two", "Movies:");
+        let background = Rgba([red, green, blue, 255]);
+        assert_eq!(*card.get_pixel(0, 0), background);
+        // The title was drawn somewhere on the card.
+        assert!(card
+            .pixels()
+            .any(|pixel| pixel.0[..3].iter().all(|c| *c > 240)));
+
+        // Long titles still fit inside a small card.
+        let small = ImageGeometry {
+            width: 64,
+            height: 64,
+        };
+        let card = render(
+            "The Lord of the Rings: The Fellowship of the Ring",
+            "Movies",
+            small,
+        );
+        assert_eq!((card.width(), card.height()), (64, 64));
+    }
+}
@@ -0,0 +1,168 @@
+use crate::{image_store::SavedImage, model::Model};
+
+// Older steps are dropped, as each holds a copy of the model and keeps the
+// images it replaced in the trash.
+const MAX_UNDO_STEPS: usize = 20;
+
+// How to put an entry's image back when a step is undone.
+pub enum ImageUndo {
+    Restore(SavedImage),
+    // The entry had no image before.
+    Added {
+        category: String,
+        title: String,
+    },
+    Moved {
+        from_category: String,
+        to_category: String,
+        title: String,
+    },
+}
+
+impl ImageUndo {
+    // Whether this puts back the image `entry` of `category` has now.
+    fn covers(&self, category: &str, entry: &str) -> bool {
+        let (image_category, title) = match self {
+            ImageUndo::Restore(saved) => (&saved.category, &saved.title),
+            ImageUndo::Added { category, title } => (category, title),
+            ImageUndo::Moved {
+                to_category, title, ..
+            } => (to_category, title),
+        };
+        image_category == category && title == entry
+    }
+}
+
+// One bulk change. It can only be undone while the model is still as the
+// change left it, so later edits are never silently thrown away.
+pub struct UndoStep {
+    pub description: String,
+    pub before: Model,
+    pub after: Model,
+    pub images: Vec<ImageUndo>,
+}
+
+#[derive(Default)]
+pub struct UndoStack {
+    // Oldest first.
+    steps: Vec<UndoStep>,
+}
+
+impl UndoStack {
+    pub fn push(&mut self, step: UndoStep) {
+        self.steps.push(step);
+        if self.steps.len() > MAX_UNDO_STEPS {
+            self.steps.remove(0);
+        }
+    }
+
+    pub fn pop(&mut self) -> Option<UndoStep> {
+        self.steps.pop()
+    }
+
+    // For a step whose change goes on after it was pushed, such as the
+    // rankings that follow a bulk move.
+    pub fn update_after(&mut self, model: &Model) {
+        if let Some(step) = self.steps.last_mut() {
+            step.after = model.clone();
+        }
+    }
+
+    // Edits that are not steps of their own leave the model unlike any step
+    // left it, so none of them can be undone any more.
+    pub fn forget_if_changed(&mut self, model: &Model) {
+        if self.steps.last().is_some_and(|step| step.after != *model) {
+            self.steps.clear();
+        }
+    }
+
+    // Undoing a step would throw away an image changed after it, such as one
+    // picked by hand after a refresh, so such steps cannot be undone any more.
+    pub fn forget_if_image_changed(&mut self, category: &str, entry: &str) {
+        let covered = self.steps.iter().any(|step| {
+            step.images
+                .iter()
+                .any(|image| image.covers(category, entry))
+        });
+        if covered {
+            self.steps.clear();
+        }
+    }
+
+    pub fn next_description(&self) -> Option<&str> {
+        self.steps.last().map(|step| step.description.as_str())
+    }
+
+    pub fn clear(&mut self) {
+        self.steps.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(description: String) -> UndoStep {
+        UndoStep {
+            description,
+            before: Model::default(),
+            after: Model::default(),
+            images: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn newest_steps_come_back_first_and_old_ones_are_dropped() {
+        let mut stack = UndoStack::default();
+        for number in 0..MAX_UNDO_STEPS + 2 {
+            stack.push(step(format!("Step {number}")));
+        }
+
+        let last = format!("Step {}", MAX_UNDO_STEPS + 1);
+        assert_eq!(stack.next_description(), Some(last.as_str()));
+        let mut undone = Vec::new();
+        while let Some(step) = stack.pop() {
+            undone.push(step.description);
+        }
+        assert_eq!(undone.len(), MAX_UNDO_STEPS);
+        assert_eq!(undone.last().map(String::as_str), Some("Step 2"));
+        assert_eq!(stack.next_description(), None);
+    }
+
+    #[test]
+    fn steps_are_forgotten_once_the_model_changes_outside_them() {
+        let mut stack = UndoStack::default();
+        stack.push(step("Move".to_string()));
+        let mut model = Model::default();
+        stack.forget_if_changed(&model);
+        assert_eq!(stack.next_description(), Some("Move"));
+
+        model.create_category("Movies:".to_string());
+        model.insert_entry_at("Movies:", "Dune".to_string(), 0);
+        stack.update_after(&model);
+        stack.forget_if_changed(&model);
+        assert_eq!(stack.next_description(), Some("Move"));
+
+        model.insert_entry_at("Movies:", "Alien".to_string(), 0);
+        stack.forget_if_changed(&model);
+        assert_eq!(stack.next_description(), None);
+    }
+
+    #[test]
+    fn steps_are_forgotten_once_an_image_they_cover_changes() {
+        let mut stack = UndoStack::default();
+        let mut refresh = step("Refresh 1 Images".to_string());
+        refresh.images.push(ImageUndo::Added {
+            category: "Movies:".to_string(),
+            title: "Dune".to_string(),
+        });
+        stack.push(refresh);
+
+        stack.forget_if_image_changed("Movies:", "Alien");
+        stack.forget_if_image_changed("Books:", "Dune");
+        assert_eq!(stack.next_description(), Some("Refresh 1 Images"));
+
+        stack.forget_if_image_changed("Movies:", "Dune");
+        assert_eq!(stack.next_description(), None);
+    }
+}